@@ -10,6 +10,119 @@ pub struct GameState {
     pub last_user_id: Option<String>,
     pub last_user_color: Option<String>,
     pub ended: bool,
+    pub board_rows: u32,
+    pub board_columns: u32,
+    pub win_length: u32,
+    pub draw: bool,
+    pub winner_color: Option<String>,
+    pub winning_cells: Option<String>,
+    /// sent back with a move, the server rejects the move if the game has changed since
+    #[serde(default)]
+    pub version: i32,
+    /// player waiting for the other player to accept taking back their last move
+    #[serde(default)]
+    pub takeback_requested_by: Option<String>,
+    /// 1-based columns of the moves, separated by spaces
+    #[serde(default)]
+    pub move_list: String,
+    /// rated games cannot be analysed, so there are no hints
+    #[serde(default)]
+    pub rated: bool,
+    /// name of the rule set, e.g. "standard", "popout" or "gomoku"
+    #[serde(default)]
+    pub rules: String,
+    /// "freestyle", "exact" or "renju": which sequences of `win_length` or more pieces win
+    #[serde(default)]
+    pub win_rule: String,
+}
+
+impl GameState {
+    /// Pieces can also be popped out of the bottom row
+    pub fn popout(&self) -> bool {
+        self.rules == "popout"
+    }
+
+    /// Stones are placed on any empty cell instead of dropped into a column
+    pub fn gomoku(&self) -> bool {
+        self.rules == "gomoku"
+    }
+
+    /// Some moves of the first player are forbidden, the engine doesn't know which
+    pub fn renju(&self) -> bool {
+        self.win_rule == "renju"
+    }
+
+    /// Board string indices of the winning sequence, empty while there is no winner
+    pub fn winning_cells(&self) -> Vec<usize> {
+        self.winning_cells
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|idx| idx.parse().ok())
+            .collect()
+    }
+
+    /// The final result of an ended game: `Some(winner_id)`, or `None` for a draw.
+    pub fn game_over_result(&self) -> Option<Option<String>> {
+        if !self.ended {
+            return None;
+        }
+        if self.winner {
+            Some(self.last_user_id.clone())
+        } else {
+            Some(None)
+        }
+    }
+}
+
+/// Evaluation of a column by `/api/analysis`, for the player to move.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColumnEvaluation {
+    /// 1-based
+    pub column: u32,
+    /// `win`, `loss`, `draw` or `score`
+    pub result: String,
+    /// moves until the end of the game, for a win or a loss
+    #[serde(default)]
+    pub moves: Option<u32>,
+    /// heuristic score, if the result is not known
+    #[serde(default)]
+    pub score: Option<i32>,
+    pub pv: Vec<u32>,
+}
+
+impl ColumnEvaluation {
+    pub fn describe(&self) -> String {
+        match (self.result.as_str(), self.moves, self.score) {
+            ("win", Some(moves), _) => format!("wins in {} moves", moves),
+            ("loss", Some(moves), _) => format!("loses in {} moves", moves),
+            ("draw", _, _) => "draws".to_string(),
+            (_, _, Some(score)) => format!("scores {}", score),
+            (result, _, _) => result.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Analysis {
+    pub depth: u32,
+    pub columns: Vec<ColumnEvaluation>,
+    /// 1-based column of the best move
+    pub best: Option<u32>,
+    pub pv: Vec<u32>,
+}
+
+impl Analysis {
+    /// One line hint for the best move.
+    pub fn hint(&self) -> String {
+        let best = self
+            .best
+            .and_then(|best| self.columns.iter().find(|column| column.column == best));
+        match best {
+            Some(column) => format!("Hint: column {} {}", column.column, column.describe()),
+            None => "Hint: there is no move to play".to_string(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -22,7 +135,15 @@ pub struct User {
 pub enum ClientState {
     WaitingForThisUserTurn,
     WaitingForOtherUserTurn,
-    GameOver(String),
+    /// Holds the winner's user id, `None` if the game ended in a draw
+    GameOver(Option<String>),
+}
+
+pub fn game_over_msg(winner: &Option<String>) -> String {
+    match winner {
+        Some(winner) => format!("GameOver. User {} won.", winner),
+        None => "GameOver. It's a draw.".to_string(),
+    }
 }
 
 pub const USER_INFO_KEY: &str = "user_info";
@@ -1,31 +1,37 @@
-use crate::models::{ClientState, GameState, User, USER_INFO_KEY};
+use crate::models::{game_over_msg, ClientState, GameState, User, USER_INFO_KEY};
 use crate::pages::game_state_worker::{ClientRequest, GameWorker, ServerResponse};
-use std::cmp;
 use yew::format::Json;
 use yew::prelude::*;
 use yew::services::storage::Area;
 use yew::services::{ConsoleService, DialogService, StorageService};
 
-// const ROWS: u32 = 6; //TODO: make these parameters
-const COLUMNS: u32 = 9;
-
 pub struct Game {
     link: ComponentLink<Self>,
     selected_column: Option<u32>,
+    /// the row of the selected cell, in the games where stones are placed on cells
+    selected_row: Option<u32>,
     hover_column: Option<u32>,
     game_state: Option<GameState>,
     game_state_worker: Box<dyn Bridge<GameWorker>>,
     client_state: ClientState,
     this_user: User,
+    hint: Option<String>,
+    /// the next move pops out the bottom piece of the selected column instead of dropping one
+    pop_mode: bool,
 }
 
 pub enum Msg {
     Initialize,
     SelectColumn(u32),
+    SelectCell(u32, u32),
     MouseOver(u32),
     MouseOut(u32),
-    DataReceived(ServerResponse),
+    DataReceived(Box<ServerResponse>),
     MakeMoveClick,
+    TakebackClick,
+    TakebackAnswerClick(bool),
+    HintClick,
+    PopModeClick,
 }
 
 impl Component for Game {
@@ -33,7 +39,7 @@ impl Component for Game {
     type Properties = ();
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
-        let callback = link.callback(Msg::DataReceived);
+        let callback = link.callback(|data| Msg::DataReceived(Box::new(data)));
         let storage = StorageService::new(Area::Session).expect("storage was disabled by the user");
         let this_user = get_user_info(&storage).expect("User not registered"); //this must have value, after user registration, panic otherwise
         let game_state_worker = GameWorker::bridge(callback);
@@ -41,11 +47,14 @@ impl Component for Game {
         Self {
             link,
             selected_column: None,
+            selected_row: None,
             hover_column: None,
             game_state: None,
             game_state_worker,
             client_state: ClientState::WaitingForThisUserTurn,
             this_user,
+            hint: None,
+            pop_mode: false,
         }
     }
 
@@ -59,6 +68,11 @@ impl Component for Game {
                 self.selected_column = Some(column);
                 true
             }
+            Msg::SelectCell(row, column) => {
+                self.selected_row = Some(row);
+                self.selected_column = Some(column);
+                true
+            }
             Msg::MouseOver(column) => {
                 self.hover_column = Some(column);
                 true
@@ -69,26 +83,53 @@ impl Component for Game {
             }
             Msg::DataReceived(data) => {
                 ConsoleService::info("===============> Msg::DataReceived: ");
-                self.process_response_data(data);
+                self.process_response_data(*data);
                 true
             }
             Msg::MakeMoveClick => {
                 match &self.client_state {
                     ClientState::WaitingForThisUserTurn => {
-                        self.game_state_worker.send(ClientRequest::MakeMoveRequest(
-                            self.selected_column.unwrap(),
-                        ));
+                        let column = self.selected_column.unwrap();
+                        let request = match self.selected_row {
+                            Some(row) => ClientRequest::PlaceRequest(self.rows() - 1 - row, column),
+                            None if self.pop_mode => ClientRequest::PopRequest(column),
+                            None => ClientRequest::MakeMoveRequest(column),
+                        };
+                        self.game_state_worker.send(request);
                     }
                     ClientState::WaitingForOtherUserTurn => {
                         DialogService::alert("Please, wait for the other user turn to finish!");
                     }
                     ClientState::GameOver(winner) => {
-                        DialogService::alert(&format!("GameOver! User {} won.", winner));
+                        DialogService::alert(&game_over_msg(winner));
                     }
                 }
                 self.selected_column = None;
+                self.selected_row = None;
+                self.hint = None;
+                self.pop_mode = false;
                 true
             }
+            Msg::PopModeClick => {
+                self.pop_mode = !self.pop_mode;
+                true
+            }
+            Msg::HintClick => {
+                if let Some(game_state) = &self.game_state {
+                    self.game_state_worker
+                        .send(ClientRequest::HintRequest(Box::new(game_state.clone())));
+                }
+                false
+            }
+            Msg::TakebackClick => {
+                self.game_state_worker.send(ClientRequest::TakebackRequest);
+                false
+            }
+            Msg::TakebackAnswerClick(accept) => {
+                self.game_state_worker
+                    .send(ClientRequest::TakebackAnswer(accept));
+                false
+            }
         }
     }
 
@@ -104,20 +145,35 @@ impl Component for Game {
                 </div>
                 <div>
                     <table class="table is-bordered">
-                    { (0..6).map(|row| self.view_row(row)).collect::<Html>() }
+                    { (0..self.rows()).map(|row| self.view_row(row)).collect::<Html>() }
                     </table>
                 </div>
                 <div>{format!("Selected column:{}", Game::print_selected_column(self.selected_column)) }</div>
                 <button onclick={self.link.callback(|_| Msg::MakeMoveClick)}>{ "Make Move" }</button>
+                { self.view_pop_mode() }
+                { self.view_takeback() }
+                { self.view_hint() }
             </div>
         }
     }
 }
 
 impl Game {
+    fn rows(&self) -> u32 {
+        self.game_state
+            .as_ref()
+            .map_or(0, |game_state| game_state.board_rows)
+    }
+
+    fn columns(&self) -> u32 {
+        self.game_state
+            .as_ref()
+            .map_or(0, |game_state| game_state.board_columns)
+    }
+
     fn get_status_msg(&self) -> String {
         if let ClientState::GameOver(winner) = &self.client_state {
-            return format!("GameOver. User {} won,", winner);
+            return game_over_msg(winner);
         }
         match &self.game_state {
             Some(game_state) => match &game_state.last_user_id {
@@ -135,41 +191,106 @@ impl Game {
     }
 
     fn get_square_class(&self, row: u32, column: u32) -> &'static str {
-        let mut board = "------------------------------------------------------"; //....I know, needs refactoring.....TODO
-        if let Some(game_state) = self.game_state.as_ref() {
-            board = game_state.board.as_ref().unwrap();
-        }
-
-        match self.selected_column {
-            Some(x) if x == column => "square_red",
-            _ => match self.hover_column {
-                Some(x) if x == column => "col_grey",
-                _ => {
-                    let idx = (cmp::max(0, row) * COLUMNS + column) as usize;
-                    match &board[idx..idx + 1] {
-                        "X" => "X",
-                        "O" => "O",
-                        _ => "square_blue",
-                    }
-                }
-            },
+        let board = match self.game_state.as_ref() {
+            Some(game_state) => game_state.board.as_deref().unwrap_or_default(),
+            None => "",
+        };
+
+        let selected = match self.selected_row {
+            Some(selected_row) => selected_row == row && self.selected_column == Some(column),
+            None => self.selected_column == Some(column),
+        };
+        let gomoku = self.game_state.as_ref().is_some_and(GameState::gomoku);
+        if selected {
+            return "square_red";
+        }
+        // the whole column is highlighted only where the pieces fall down it
+        if !gomoku && self.hover_column == Some(column) {
+            return "col_grey";
+        }
+        let idx = (row * self.columns() + column) as usize;
+        let winning = self
+            .game_state
+            .as_ref()
+            .is_some_and(|game_state| game_state.winning_cells().contains(&idx));
+        match (board.get(idx..idx + 1), winning) {
+            (Some("X"), true) => "X winning",
+            (Some("O"), true) => "O winning",
+            (Some("X"), false) => "X",
+            (Some("O"), false) => "O",
+            _ => "square_blue",
         }
     }
 
     fn view_square(&self, row: u32, column: u32) -> Html {
+        let gomoku = self.game_state.as_ref().is_some_and(GameState::gomoku);
         html! {
             <td class={self.get_square_class(row, column)}
-                onclick={self.link.callback(move |_| Msg::SelectColumn(column))}
+                onclick={self.link.callback(move |_| if gomoku {
+                    Msg::SelectCell(row, column)
+                } else {
+                    Msg::SelectColumn(column)
+                })}
                 onmouseover={self.link.callback(move |_| Msg::MouseOver(column))}
                 onmouseout={self.link.callback(move |_| Msg::MouseOut(column))}>
             </td>
         }
     }
 
+    /// Toggles between dropping and popping out pieces, in the games with the PopOut rules.
+    fn view_pop_mode(&self) -> Html {
+        if !self.game_state.as_ref().is_some_and(GameState::popout) {
+            return html! {};
+        }
+        let label = if self.pop_mode { "Pop Out" } else { "Drop" };
+        html! {
+            <button onclick={self.link.callback(|_| Msg::PopModeClick)}>{ label }</button>
+        }
+    }
+
+    /// Hints are for practice games of the standard rules: not rated and on this user's turn.
+    fn view_hint(&self) -> Html {
+        let practice = self.game_state.as_ref().is_some_and(|game_state| {
+            !game_state.rated && !game_state.popout() && !game_state.gomoku() && !game_state.renju()
+        });
+        if !practice || !matches!(self.client_state, ClientState::WaitingForThisUserTurn) {
+            return html! {};
+        }
+        html! {
+            <div>
+                <button onclick={self.link.callback(|_| Msg::HintClick)}>{ "Hint" }</button>
+                { self.hint.clone().unwrap_or_default() }
+            </div>
+        }
+    }
+
+    fn view_takeback(&self) -> Html {
+        let game_state = match &self.game_state {
+            Some(game_state) if !game_state.ended => game_state,
+            _ => return html! {},
+        };
+        match &game_state.takeback_requested_by {
+            Some(user_id) if user_id == &self.this_user.id => html! {
+                <div>{ "Waiting for the other user to accept the takeback" }</div>
+            },
+            Some(_) => html! {
+                <div>
+                    { "The other user asks to take back their last move " }
+                    <button onclick={self.link.callback(|_| Msg::TakebackAnswerClick(true))}>{ "Accept" }</button>
+                    <button onclick={self.link.callback(|_| Msg::TakebackAnswerClick(false))}>{ "Decline" }</button>
+                </div>
+            },
+            None if game_state.last_user_id.as_ref() == Some(&self.this_user.id) => html! {
+                <button onclick={self.link.callback(|_| Msg::TakebackClick)}>{ "Take Back" }</button>
+            },
+            None => html! {},
+        }
+    }
+
     fn view_row(&self, row: u32) -> Html {
         html! {
             <tr>
-                {for (0..9).map(|column| {
+                {for (0..self.columns()).map(|column| {
                     self.view_square(row, column)
                 })}
             </tr>
@@ -200,6 +321,20 @@ impl Game {
                     Err(err) => DialogService::alert(&err.err),
                 };
             }
+            ServerResponse::HintResponse(event_data) => match event_data {
+                Ok(analysis) => {
+                    self.selected_column = analysis.best.map(|column| column - 1);
+                    self.hint = Some(analysis.hint());
+                }
+                Err(err) => DialogService::alert(&err.err),
+            },
+            ServerResponse::TakebackResponse(event_data) => match event_data {
+                Ok(game_state) => {
+                    self.game_state = Some(game_state);
+                    self.update_client_state();
+                }
+                Err(err) => DialogService::alert(&err.err),
+            },
             ServerResponse::GetGameStateResponse(event_data) => match event_data {
                 Ok(game_state) => {
                     self.game_state = Some(game_state);
@@ -214,16 +349,8 @@ impl Game {
     }
 
     fn update_client_state(&mut self) {
-        if self.game_state.as_ref().unwrap().winner {
-            self.client_state = ClientState::GameOver(
-                self.game_state
-                    .as_ref()
-                    .unwrap()
-                    .last_user_id
-                    .as_ref()
-                    .unwrap()
-                    .to_owned(),
-            );
+        if let Some(winner) = self.game_state.as_ref().unwrap().game_over_result() {
+            self.client_state = ClientState::GameOver(winner);
         } else {
             match &self.game_state {
                 Some(game_state) => match &game_state.last_user_id {
@@ -10,7 +10,7 @@ use yew::format::Json;
 use yew::services::storage::Area;
 use yew::services::{IntervalService, StorageService, Task};
 
-use crate::models::{ClientState, GameState, User, USER_INFO_KEY};
+use crate::models::{Analysis, ClientState, GameState, User, USER_INFO_KEY};
 use crate::rest_helper;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -30,6 +30,16 @@ impl Error for ServerError {}
 pub enum ClientRequest {
     InitializeBoard,
     MakeMoveRequest(u32),
+    /// pop out the bottom piece of the column, in the games with the PopOut rules
+    PopRequest(u32),
+    /// place a stone on the cell (row counted from the bottom, column), in the games with the
+    /// gomoku rules
+    PlaceRequest(u32, u32),
+    TakebackRequest,
+    /// accept or decline the takeback request of the other user
+    TakebackAnswer(bool),
+    /// analyse the position of the game, for a hint
+    HintRequest(Box<GameState>),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -37,7 +47,9 @@ pub enum ServerResponse {
     DataFetched(String),
     MakeMoveResponse(Result<GameState, ServerError>),
     GetGameStateResponse(Result<GameState, ServerError>),
-    GameOver(String),
+    TakebackResponse(Result<GameState, ServerError>),
+    HintResponse(Result<Analysis, ServerError>),
+    GameOver(Option<String>),
 }
 
 pub enum Msg {
@@ -45,6 +57,8 @@ pub enum Msg {
     Updating,
     MakeMoveResponse(HandlerId, Result<GameState, rest_helper::RestError>),
     GetGameStateResponse(HandlerId, Result<GameState, rest_helper::RestError>),
+    TakebackResponse(HandlerId, Result<GameState, rest_helper::RestError>),
+    HintResponse(HandlerId, Result<Analysis, rest_helper::RestError>),
 }
 
 pub struct GameWorker {
@@ -54,6 +68,8 @@ pub struct GameWorker {
     input_handler: Option<HandlerId>,
     client_state: ClientState,
     storage: StorageService,
+    /// version of the last game state received from the server
+    version: Option<i32>,
 }
 
 impl GameWorker {
@@ -97,6 +113,7 @@ impl Agent for GameWorker {
             input_handler: None,
             client_state: ClientState::WaitingForThisUserTurn,
             storage,
+            version: None,
         }
     }
 
@@ -121,12 +138,35 @@ impl Agent for GameWorker {
                                 .as_str(),
                         );
                         self.client_state = ClientState::WaitingForOtherUserTurn;
+                        self.version = Some(game_state.version);
                         ServerResponse::MakeMoveResponse(Ok(game_state))
                     }
-                    Err(err) => ServerResponse::MakeMoveResponse(Err(ServerError { err: err.err })),
+                    Err(err) => {
+                        if err.code.as_deref() == Some(rest_helper::VERSION_CONFLICT) {
+                            // show the current board, the user can then make the move again
+                            self.get_game_state();
+                        }
+                        ServerResponse::MakeMoveResponse(Err(ServerError { err: err.err }))
+                    }
+                };
+                self.link.respond(who, msg);
+            }
+            Msg::TakebackResponse(who, fetched_response) => {
+                let msg = match fetched_response {
+                    Ok(game_state) => {
+                        self.version = Some(game_state.version);
+                        ServerResponse::TakebackResponse(Ok(game_state))
+                    }
+                    Err(err) => ServerResponse::TakebackResponse(Err(ServerError { err: err.err })),
                 };
                 self.link.respond(who, msg);
             }
+            Msg::HintResponse(who, fetched_response) => {
+                let msg = ServerResponse::HintResponse(
+                    fetched_response.map_err(|err| ServerError { err: err.err }),
+                );
+                self.link.respond(who, msg);
+            }
             Msg::GetGameStateResponse(who, fetched_response) => {
                 match fetched_response {
                     Ok(game_state) => {
@@ -134,9 +174,9 @@ impl Agent for GameWorker {
                             format!("update::Msg::UpdateBoardResponse called: {:#?}", game_state)
                                 .as_str(),
                         );
-                        if game_state.winner {
-                            let winer = game_state.last_user_id.clone();
-                            self.client_state = ClientState::GameOver(winer.unwrap())
+                        self.version = Some(game_state.version);
+                        if let Some(winner) = game_state.game_over_result() {
+                            self.client_state = ClientState::GameOver(winner)
                         }
 
                         match &self.client_state {
@@ -153,7 +193,7 @@ impl Agent for GameWorker {
                                 }
                             }
                             ClientState::GameOver(winner) => {
-                                let msg = ServerResponse::GameOver(winner.to_string());
+                                let msg = ServerResponse::GameOver(winner.clone());
                                 self.link.respond(who, msg);
                             }
                         }
@@ -172,6 +212,7 @@ impl Agent for GameWorker {
         yew::services::ConsoleService::info(&format!("Request: {:?}", msg));
         self.input_handler = Some(who);
         let link = self.link.clone();
+        let version = self.version;
         let future = async move {
             match msg {
                 ClientRequest::InitializeBoard => {
@@ -179,9 +220,29 @@ impl Agent for GameWorker {
                     link.send_message(Msg::GetGameStateResponse(who, rest_response));
                 }
                 ClientRequest::MakeMoveRequest(column) => {
-                    let rest_response = rest_helper::make_move(column).await;
+                    let rest_response = rest_helper::make_move(column, version).await;
                     link.send_message(Msg::MakeMoveResponse(who, rest_response));
                 }
+                ClientRequest::PopRequest(column) => {
+                    let rest_response = rest_helper::pop(column, version).await;
+                    link.send_message(Msg::MakeMoveResponse(who, rest_response));
+                }
+                ClientRequest::PlaceRequest(row, column) => {
+                    let rest_response = rest_helper::place(row, column, version).await;
+                    link.send_message(Msg::MakeMoveResponse(who, rest_response));
+                }
+                ClientRequest::TakebackRequest => {
+                    let rest_response = rest_helper::request_takeback().await;
+                    link.send_message(Msg::TakebackResponse(who, rest_response));
+                }
+                ClientRequest::TakebackAnswer(accept) => {
+                    let rest_response = rest_helper::answer_takeback(accept).await;
+                    link.send_message(Msg::TakebackResponse(who, rest_response));
+                }
+                ClientRequest::HintRequest(game_state) => {
+                    let rest_response = rest_helper::analyse(&game_state).await;
+                    link.send_message(Msg::HintResponse(who, rest_response));
+                }
             };
         };
         wasm_bindgen_futures::spawn_local(future);
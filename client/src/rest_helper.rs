@@ -33,6 +33,14 @@ impl From<JsValue> for FetchError {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RestError {
     pub err: String,
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+impl RestError {
+    fn new(err: String) -> Self {
+        RestError { err, code: None }
+    }
 }
 
 impl Display for RestError {
@@ -82,13 +90,22 @@ fn return_string(resp_text: Result<JsValue, FetchError>) -> Result<String, RestE
 fn return_game_state(
     resp_text: Result<JsValue, FetchError>,
 ) -> Result<models::GameState, RestError> {
-    let result =
-        serde_json::from_str::<models::GameState>(&resp_text.unwrap().as_string().unwrap());
+    let text = resp_text.unwrap().as_string().unwrap();
+    let result = serde_json::from_str::<models::GameState>(&text);
     match result {
         Ok(game_state) => Ok(game_state),
-        Err(err) => Err(RestError {
-            err: err.to_string(),
-        }),
+        // the server reports rejected moves as `{"code": ..., "err": ...}`
+        Err(err) => Err(serde_json::from_str::<RestError>(&text)
+            .unwrap_or_else(|_| RestError::new(err.to_string()))),
+    }
+}
+
+fn return_analysis(resp_text: Result<JsValue, FetchError>) -> Result<models::Analysis, RestError> {
+    let text = resp_text.unwrap().as_string().unwrap();
+    match serde_json::from_str::<models::Analysis>(&text) {
+        Ok(analysis) => Ok(analysis),
+        Err(err) => Err(serde_json::from_str::<RestError>(&text)
+            .unwrap_or_else(|_| RestError::new(err.to_string()))),
     }
 }
 
@@ -96,9 +113,7 @@ fn return_user(resp_text: Result<JsValue, FetchError>) -> Result<models::User, R
     let result = serde_json::from_str::<models::User>(&resp_text.unwrap().as_string().unwrap());
     match result {
         Ok(user) => Ok(user),
-        Err(err) => Err(RestError {
-            err: err.to_string(),
-        }),
+        Err(err) => Err(RestError::new(err.to_string())),
     }
 }
 
@@ -134,8 +149,71 @@ pub async fn get_game_state() -> Result<models::GameState, RestError> {
     return_game_state(result)
 }
 
-pub async fn make_move(column: u32) -> Result<models::GameState, RestError> {
-    let url = format!("{}/{}/{}", get_base_url(), "make-move", column + 1);
+/// Evaluates the moves of the current position of the game.
+pub async fn analyse(game_state: &models::GameState) -> Result<models::Analysis, RestError> {
+    let mut url = format!(
+        "{}/{}?rows={}&columns={}&win_length={}&moves={}",
+        get_base_url(),
+        "analysis",
+        game_state.board_rows,
+        game_state.board_columns,
+        game_state.win_length,
+        game_state.move_list.replace(' ', "+")
+    );
+    if !game_state.win_rule.is_empty() {
+        url = format!("{}&win_rule={}", url, game_state.win_rule);
+    }
+    if !game_state.rules.is_empty() {
+        url = format!("{}&rules={}", url, game_state.rules);
+    }
+    let result = do_get(&url).await;
+    return_analysis(result)
+}
+
+pub async fn request_takeback() -> Result<models::GameState, RestError> {
+    let url = format!("{}/{}", get_base_url(), "takeback");
+    let result = do_post(&url).await;
+    return_game_state(result)
+}
+
+pub async fn answer_takeback(accept: bool) -> Result<models::GameState, RestError> {
+    let answer = if accept { "accept" } else { "decline" };
+    let url = format!("{}/{}/{}", get_base_url(), "takeback", answer);
+    let result = do_post(&url).await;
+    return_game_state(result)
+}
+
+/// Error code of a move rejected because the game changed since `version` was fetched.
+pub const VERSION_CONFLICT: &str = "version_conflict";
+
+pub async fn make_move(column: u32, version: Option<i32>) -> Result<models::GameState, RestError> {
+    let mut url = format!("{}/{}/{}", get_base_url(), "make-move", column + 1);
+    if let Some(version) = version {
+        url = format!("{}?version={}", url, version);
+    }
+    let result = do_post(&url).await;
+    return_game_state(result)
+}
+
+pub async fn pop(column: u32, version: Option<i32>) -> Result<models::GameState, RestError> {
+    let mut url = format!("{}/{}/{}", get_base_url(), "pop", column + 1);
+    if let Some(version) = version {
+        url = format!("{}?version={}", url, version);
+    }
+    let result = do_post(&url).await;
+    return_game_state(result)
+}
+
+/// Places a stone on the (zero based) cell, `row` counted from the bottom.
+pub async fn place(
+    row: u32,
+    column: u32,
+    version: Option<i32>,
+) -> Result<models::GameState, RestError> {
+    let mut url = format!("{}/{}/{}/{}", get_base_url(), "place", row + 1, column + 1);
+    if let Some(version) = version {
+        url = format!("{}?version={}", url, version);
+    }
     let result = do_post(&url).await;
     return_game_state(result)
 }
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE game_state DROP COLUMN board_rows;
+ALTER TABLE game_state DROP COLUMN board_columns;
+ALTER TABLE game_state DROP COLUMN win_length;
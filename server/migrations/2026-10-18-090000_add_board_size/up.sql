@@ -0,0 +1,4 @@
+-- Board dimensions and winning sequence length of each game
+ALTER TABLE game_state ADD COLUMN board_rows INTEGER NOT NULL DEFAULT 6;
+ALTER TABLE game_state ADD COLUMN board_columns INTEGER NOT NULL DEFAULT 9;
+ALTER TABLE game_state ADD COLUMN win_length INTEGER NOT NULL DEFAULT 5;
@@ -0,0 +1,2 @@
+-- Set when the board filled up without a winner
+ALTER TABLE game_state ADD COLUMN draw BOOLEAN NOT NULL DEFAULT 0;
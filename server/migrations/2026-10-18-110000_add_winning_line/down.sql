@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE game_state DROP COLUMN winner_color;
+ALTER TABLE game_state DROP COLUMN winning_cells;
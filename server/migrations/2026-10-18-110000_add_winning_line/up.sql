@@ -0,0 +1,3 @@
+-- Color and board string indices of the winning sequence
+ALTER TABLE game_state ADD COLUMN winner_color TEXT(1);
+ALTER TABLE game_state ADD COLUMN winning_cells TEXT;
@@ -0,0 +1,2 @@
+-- Engine of the computer player seated as user_2, NULL for games between two users
+ALTER TABLE game_state ADD COLUMN ai_engine TEXT;
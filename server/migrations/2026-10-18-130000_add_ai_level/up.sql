@@ -0,0 +1,2 @@
+-- Difficulty level of the computer player
+ALTER TABLE game_state ADD COLUMN ai_level TEXT;
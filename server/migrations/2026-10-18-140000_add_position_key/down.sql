@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX game_state_position_key;
+ALTER TABLE game_state DROP COLUMN position_key;
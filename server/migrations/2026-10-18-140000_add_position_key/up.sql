@@ -0,0 +1,3 @@
+-- Zobrist hash of the current position, the same for every move order leading to it
+ALTER TABLE game_state ADD COLUMN position_key TEXT;
+CREATE INDEX game_state_position_key ON game_state (position_key);
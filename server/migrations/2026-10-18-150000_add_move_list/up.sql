@@ -0,0 +1,2 @@
+-- 1-based columns of the moves played so far, separated by spaces
+ALTER TABLE game_state ADD COLUMN move_list TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,11 @@
+-- One row per move. col_num is 1-based like in make_move, row_num counts from 1 at the bottom
+CREATE TABLE moves (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    game_id TEXT NOT NULL REFERENCES game_state (id),
+    ply INTEGER NOT NULL,
+    user_id TEXT NOT NULL,
+    col_num INTEGER NOT NULL,
+    row_num INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (game_id, ply)
+);
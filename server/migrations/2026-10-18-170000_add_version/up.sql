@@ -0,0 +1,2 @@
+-- incremented on every change of the game, for optimistic concurrency control
+ALTER TABLE game_state ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- user who asked to take back their last move, until the other player answers
+ALTER TABLE game_state ADD COLUMN takeback_requested_by TEXT;
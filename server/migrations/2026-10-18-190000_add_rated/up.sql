@@ -0,0 +1,2 @@
+-- rated games cannot be analysed while they are being played
+ALTER TABLE game_state ADD COLUMN rated BOOLEAN NOT NULL DEFAULT 0;
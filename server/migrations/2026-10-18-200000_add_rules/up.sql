@@ -0,0 +1,2 @@
+-- name of the rule set of the game, see `rules::RuleSet`
+ALTER TABLE game_state ADD COLUMN rules TEXT NOT NULL DEFAULT 'standard';
@@ -0,0 +1,2 @@
+-- `drop` or `pop`, see `rules::Move::kind`
+ALTER TABLE moves ADD COLUMN kind TEXT NOT NULL DEFAULT 'drop';
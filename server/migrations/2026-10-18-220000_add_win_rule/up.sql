@@ -0,0 +1,2 @@
+-- which sequences of pieces win, see `board::WinRule`
+ALTER TABLE game_state ADD COLUMN win_rule TEXT NOT NULL DEFAULT 'freestyle';
@@ -1,12 +1,20 @@
 use actix_session::Session;
+use actix_web::error::BlockingError;
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 
+use serde::Deserialize;
 use serde_json::json;
 use std::result::Result;
 
+use crate::board::{Board, BoardSize, WinRule};
 pub use crate::db;
+use crate::engine::level::AiLevel;
+use crate::engine::AiEngine;
 pub use crate::game;
 pub use crate::models;
+use crate::notation::{Notation, DEFAULT_COLORS};
+use crate::record::GameRecord;
+use crate::rules::RuleSet;
 pub use crate::schema;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::SqliteConnection;
@@ -16,6 +24,196 @@ use uuid::Uuid;
 const SESSION_ID_KEY: &str = "session_id";
 const USER_ID_KEY: &str = "user_id";
 const USER_COLOR_KEY: &str = "user_color";
+const SPECTATOR_KEY: &str = "spectator";
+
+#[derive(Deserialize, Debug, Default)]
+pub struct NewGameParams {
+    pub rows: Option<usize>,
+    pub columns: Option<usize>,
+    pub win_length: Option<usize>,
+    /// engine of the computer opponent, e.g. `minimax`
+    pub ai: Option<String>,
+    /// strength of the computer opponent: `beginner`, `intermediate` or `expert`
+    pub level: Option<String>,
+    /// rated games cannot be analysed while they are being played
+    pub rated: Option<bool>,
+    /// name of the rule set, `standard` by default
+    pub rules: Option<String>,
+    /// `freestyle` by default, `exact` or `renju`, see `board::WinRule`
+    pub win_rule: Option<String>,
+}
+
+impl NewGameParams {
+    /// The requested size, the dimensions not given being those of the rule set's default board.
+    pub fn board_size(&self) -> BoardSize {
+        let default = self
+            .rule_set()
+            .map(|rule_set| rule_set.rules().default_size())
+            .unwrap_or_default();
+        board_size(self.rows, self.columns, self.win_length, default)
+    }
+
+    /// The requested rule set, checked against the requested win rule.
+    pub fn rule_set(&self) -> Result<RuleSet, String> {
+        let rule_set = match &self.rules {
+            Some(name) => name.parse()?,
+            None => RuleSet::default(),
+        };
+        let win_rule = self.win_rule()?;
+        if !rule_set.rules().allows(win_rule) {
+            return Err(format!(
+                "The {} rules can't be played with the {} win rule",
+                rule_set, win_rule
+            ));
+        }
+        Ok(rule_set)
+    }
+
+    pub fn win_rule(&self) -> Result<WinRule, String> {
+        win_rule(&self.win_rule)
+    }
+
+    /// The requested computer opponent and its level, if any. Fails for unknown engines or
+    /// levels and for an external engine that doesn't start. `game::new_game` checks that the
+    /// computer plays the requested rules.
+    pub fn ai_opponent(&self) -> Result<Option<(AiEngine, AiLevel)>, String> {
+        match &self.ai {
+            Some(name) => {
+                let engine = name.parse::<AiEngine>()?;
+                let level = match &self.level {
+                    Some(level) => level.parse::<AiLevel>()?,
+                    None => AiLevel::default(),
+                };
+                engine.check()?;
+                Ok(Some((engine, level)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct MoveParams {
+    /// `version` of the game the move was chosen on, the move is rejected if the game has
+    /// changed since
+    pub version: Option<i32>,
+}
+
+/// Position to analyse or to look up in the opening book, given either as a `board` string of the given size or as `moves`.
+#[derive(Deserialize, Debug, Default)]
+pub struct PositionParams {
+    /// board in the format of `game_state.board`
+    pub board: Option<String>,
+    /// moves in `notation` format. Without the header the size is taken from the parameters
+    pub moves: Option<String>,
+    pub rows: Option<usize>,
+    pub columns: Option<usize>,
+    pub win_length: Option<usize>,
+    /// color of the first player, `X` by default. Only needed for a `board` with as many pieces
+    /// of both colors
+    pub first: Option<char>,
+    /// `freestyle` by default or `exact`, see `board::WinRule`
+    pub win_rule: Option<String>,
+    /// name of the rule set of the game, only `standard` positions are known to the engine
+    pub rules: Option<String>,
+}
+
+impl PositionParams {
+    pub fn board_size(&self) -> BoardSize {
+        board_size(
+            self.rows,
+            self.columns,
+            self.win_length,
+            BoardSize::default(),
+        )
+    }
+
+    /// The board and the colors of the first and the second player. The engine only plays the
+    /// standard rules and doesn't know the forbidden moves of the renju rules, so other
+    /// positions are refused.
+    pub fn position(&self) -> Result<(Board, [char; 2]), String> {
+        let rule_set = match &self.rules {
+            Some(name) => name.parse()?,
+            None => RuleSet::Standard,
+        };
+        if rule_set != RuleSet::Standard {
+            return Err(format!(
+                "Games with the {} rules can't be analysed, only standard games can",
+                rule_set
+            ));
+        }
+        // pops like `p3` and placed stones like `h8` are moves of the other rule sets
+        let moves = self.moves.as_deref().unwrap_or_default();
+        if let Some(mv) = moves
+            .split_whitespace()
+            .find(|mv| mv.starts_with(|c: char| c.is_ascii_alphabetic()))
+        {
+            return Err(format!(
+                "Only games with the standard rules can be analysed, {} is not a drop",
+                mv
+            ));
+        }
+        let (board, colors) = self.board_and_colors()?;
+        if board.win_rule() == WinRule::Renju {
+            return Err("The engine doesn't know the renju restrictions".to_string());
+        }
+        Ok((board, colors))
+    }
+
+    fn board_and_colors(&self) -> Result<(Board, [char; 2]), String> {
+        let win_rule = win_rule(&self.win_rule)?;
+        match (&self.board, &self.moves) {
+            (Some(board), None) => {
+                self.board_size().validate()?;
+                let board = Board::parse(board, self.board_size())?;
+                let count = |color: char| {
+                    board
+                        .cells()
+                        .iter()
+                        .flatten()
+                        .filter(|cell| **cell == color)
+                        .count()
+                };
+                // the first player has one piece more after their move
+                let o_first = match count('O').cmp(&count('X')) {
+                    std::cmp::Ordering::Greater => true,
+                    std::cmp::Ordering::Equal => self.first == Some('O'),
+                    std::cmp::Ordering::Less => false,
+                };
+                let colors = if o_first { ['O', 'X'] } else { ['X', 'O'] };
+                Ok((board.with_win_rule(win_rule, colors[0]), colors))
+            }
+            (None, Some(moves)) => {
+                let notation = match moves.split_whitespace().next() {
+                    Some(header) if header.contains('x') => moves.parse::<Notation>()?,
+                    _ => Notation::from_move_list(self.board_size(), win_rule, moves)?,
+                };
+                Ok((notation.board()?, DEFAULT_COLORS))
+            }
+            _ => Err("Expected either a board or moves".to_string()),
+        }
+    }
+}
+
+fn win_rule(name: &Option<String>) -> Result<WinRule, String> {
+    match name {
+        Some(name) => name.parse(),
+        None => Ok(WinRule::default()),
+    }
+}
+
+fn board_size(
+    rows: Option<usize>,
+    columns: Option<usize>,
+    win_length: Option<usize>,
+    default: BoardSize,
+) -> BoardSize {
+    BoardSize::new(
+        rows.unwrap_or(default.rows),
+        columns.unwrap_or(default.columns),
+        win_length.unwrap_or(default.win_length),
+    )
+}
 
 fn get_db_connection(
     req: HttpRequest,
@@ -34,6 +232,35 @@ fn get_db_connection(
     }
 }
 
+fn game_error_response(error: game::GameError) -> Error {
+    let mut response = match error {
+        game::GameError::NotAPlayer => HttpResponse::Forbidden(),
+        game::GameError::NotFound | game::GameError::NoOpeningBook => HttpResponse::NotFound(),
+        game::GameError::NotYourTurn
+        | game::GameError::GameOver
+        | game::GameError::NotFinished
+        | game::GameError::Conflict
+        | game::GameError::TakebackNotAllowed(_) => HttpResponse::Conflict(),
+        game::GameError::AnalysisDisabled => HttpResponse::Forbidden(),
+        game::GameError::InvalidMove(_)
+        | game::GameError::InvalidRecord(_)
+        | game::GameError::InvalidGame(_)
+        | game::GameError::InvalidPosition(_) => HttpResponse::BadRequest(),
+        game::GameError::Internal(_) => HttpResponse::InternalServerError(),
+    };
+    Error::from(response.json(json!({ "code": error.code(), "err": error.to_string() })))
+}
+
+/// Error of a `game` function run with `web::block`.
+fn blocking_error_response(error: BlockingError<game::GameError>) -> Error {
+    match error {
+        BlockingError::Error(error) => game_error_response(error),
+        BlockingError::Canceled => game_error_response(game::GameError::Internal(
+            "The request was canceled".to_string(),
+        )),
+    }
+}
+
 pub async fn register(
     web::Path((user_name, user_color)): web::Path<(String, String)>,
     session: Session,
@@ -65,22 +292,46 @@ pub async fn register(
     }
 }
 
-pub async fn new_game(session: Session, req: HttpRequest) -> Result<HttpResponse, Error> {
+pub async fn new_game(
+    params: web::Query<NewGameParams>,
+    session: Session,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
     println!("NEW GAME REQ: {:?}", req);
 
+    let board_size = params.board_size();
+    if let Err(error) = board_size.validate() {
+        return Err(Error::from(HttpResponse::BadRequest().body(error)));
+    }
+    let rule_set = params
+        .rule_set()
+        .map_err(|error| Error::from(HttpResponse::BadRequest().body(error)))?;
+    let win_rule = params
+        .win_rule()
+        .map_err(|error| Error::from(HttpResponse::BadRequest().body(error)))?;
+    let ai_opponent = params
+        .ai_opponent()
+        .map_err(|error| Error::from(HttpResponse::BadRequest().body(error)))?;
+
     let conn = get_db_connection(req)?;
 
     if let Some(user_id) = session.get::<Uuid>(USER_ID_KEY)? {
-        match db::create_new_session(&user_id, conn.deref()) {
+        let rated = params.rated == Some(true);
+        match game::new_game(
+            user_id,
+            board_size,
+            win_rule,
+            rule_set,
+            rated,
+            ai_opponent,
+            conn.deref(),
+        ) {
             Ok(session_id) => {
                 session.set(SESSION_ID_KEY, session_id.to_string())?;
+                session.remove(SPECTATOR_KEY);
                 Ok(HttpResponse::Ok().body(json!({ SESSION_ID_KEY: session_id.to_string() })))
             }
-            Err(error) => {
-                return Err(Error::from(
-                    HttpResponse::BadGateway().body(format!("Cant create new session: {}", error)),
-                ))
-            }
+            Err(error) => Err(game_error_response(error)),
         }
     } else {
         Err(Error::from(
@@ -113,10 +364,20 @@ pub async fn join(
     let game_id = game_session_id.into_inner();
     if let Some(user_2_id) = session.get::<Uuid>(USER_ID_KEY)? {
         match db::join_game_session(&game_id, &user_2_id, conn.deref()) {
-            Ok(0) => Err(Error::from(
-                HttpResponse::NotFound().body(format!("No waiting sessions with id {}", &game_id)),
-            )),
-            Ok(1) => Ok(HttpResponse::Ok().body("OK")),
+            Ok(0) => match db::get_game_state(&game_id, conn.deref()) {
+                Ok(_) => Err(Error::from(
+                    HttpResponse::Conflict()
+                        .body(format!("The game {} has no free seat for you", &game_id)),
+                )),
+                Err(_) => Err(Error::from(
+                    HttpResponse::NotFound()
+                        .body(format!("No waiting sessions with id {}", &game_id)),
+                )),
+            },
+            Ok(1) => {
+                session.remove(SPECTATOR_KEY);
+                Ok(HttpResponse::Ok().body("OK"))
+            }
             Ok(_) => Err(Error::from(
                 HttpResponse::BadGateway().body("Multiple sessions updated"),
             )),
@@ -131,6 +392,27 @@ pub async fn join(
     }
 }
 
+/// Follows a game read-only. Spectators can fetch the game state, but can't make moves.
+pub async fn spectate(
+    game_session_id: web::Path<Uuid>,
+    session: Session,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let conn = get_db_connection(req)?;
+    let game_id = game_session_id.into_inner();
+    match db::get_game_state(&game_id, conn.deref()) {
+        Ok(_) => {
+            session.set(SESSION_ID_KEY, game_id.to_string())?;
+            session.set(SPECTATOR_KEY, true)?;
+            Ok(HttpResponse::Ok().body("OK"))
+        }
+        Err(_) => Err(Error::from(
+            HttpResponse::NotFound().body(format!("No game with id {}", &game_id)),
+        )),
+    }
+}
+
 #[deprecated]
 pub async fn board(session: Session, req: HttpRequest) -> Result<HttpResponse, Error> {
     println!("REQ: {:?}", req);
@@ -161,14 +443,30 @@ pub async fn game_state(session: Session, req: HttpRequest) -> Result<HttpRespon
         println!("API: board, session_id: {:?}", session_id);
         session.set(SESSION_ID_KEY, session_id)?;
 
-        // let id = session_id.into_inner();
-        let res = db::get_game_state(&session_id, conn.deref());
-        match res {
-            Ok(game_state) => Ok(HttpResponse::Ok().body(json!(game_state))),
-            _ => Err(Error::from(
-                HttpResponse::InternalServerError()
-                    .body(format!("Can't find game with session id {}", session_id)),
-            )),
+        let user_id = session.get::<Uuid>(USER_ID_KEY)?;
+        let spectator = session.get::<bool>(SPECTATOR_KEY)?.unwrap_or(false);
+        let game_state = match db::get_game_state(&session_id, conn.deref()) {
+            Ok(game_state) => game_state,
+            _ => {
+                return Err(Error::from(
+                    HttpResponse::InternalServerError()
+                        .body(format!("Can't find game with session id {}", session_id)),
+                ))
+            }
+        };
+        match user_id.filter(|user_id| game_state.is_seated(user_id)) {
+            // a player's request gets the computer to play its missing answer, the search takes a
+            // second, keep it off the worker thread
+            Some(user_id) if game_state.is_ai_turn() => {
+                let game_state =
+                    web::block(move || game::get_game_state(&session_id, &user_id, conn.deref()))
+                        .await
+                        .map_err(blocking_error_response)?;
+                Ok(HttpResponse::Ok().body(json!(game_state)))
+            }
+            Some(_) => Ok(HttpResponse::Ok().body(json!(game_state))),
+            None if spectator => Ok(HttpResponse::Ok().body(json!(game_state))),
+            None => Err(game_error_response(game::GameError::NotAPlayer)),
         }
     } else {
         Err(Error::from(
@@ -179,6 +477,39 @@ pub async fn game_state(session: Session, req: HttpRequest) -> Result<HttpRespon
 
 pub async fn make_move(
     web::Path(column): web::Path<u32>,
+    params: web::Query<MoveParams>,
+    session: Session,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    play(game::Move::Drop(column as usize), params, session, req).await
+}
+
+/// Pops the player's own piece out of the bottom of the column, in the games with the rules
+/// that allow it.
+pub async fn pop(
+    web::Path(column): web::Path<u32>,
+    params: web::Query<MoveParams>,
+    session: Session,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    play(game::Move::Pop(column as usize), params, session, req).await
+}
+
+/// Places a stone on the empty cell, the row counted from the bottom, in the games with the
+/// rules that allow it.
+pub async fn place(
+    web::Path((row, column)): web::Path<(u32, u32)>,
+    params: web::Query<MoveParams>,
+    session: Session,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    let mv = game::Move::Place(row as usize, column as usize);
+    play(mv, params, session, req).await
+}
+
+async fn play(
+    mv: game::Move,
+    params: web::Query<MoveParams>,
     session: Session,
     req: HttpRequest,
 ) -> Result<HttpResponse, Error> {
@@ -188,13 +519,17 @@ pub async fn make_move(
         session.get::<Uuid>(SESSION_ID_KEY)?,
         session.get::<Uuid>(USER_ID_KEY)?,
     ) {
-        let res = game::user_move(session_id, user_id, column as usize, conn.deref());
+        let version = params.version;
+        // the computer answers the move, its search takes a second, keep it off the worker thread
+        let res =
+            web::block(move || game::user_move(session_id, user_id, mv, version, conn.deref()))
+                .await;
         match res {
             Ok(game_state) => {
                 println!("API make_move returns: {:?}", game_state);
                 Ok(HttpResponse::Ok().json(game_state))
             }
-            Err(msg) => Err(Error::from(HttpResponse::InternalServerError().body(msg))),
+            Err(error) => Err(blocking_error_response(error)),
         }
     } else {
         Err(Error::from(
@@ -203,6 +538,114 @@ pub async fn make_move(
     }
 }
 
+pub async fn analysis(
+    params: web::Query<PositionParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let (board, colors) = params
+        .position()
+        .map_err(|error| Error::from(HttpResponse::BadRequest().body(error)))?;
+    let conn = get_db_connection(req)?;
+    // the search takes a second, keep it off the worker thread
+    match web::block(move || game::analyse_position(&board, colors, conn.deref())).await {
+        Ok(analysis) => Ok(HttpResponse::Ok().json(analysis)),
+        Err(error) => Err(blocking_error_response(error)),
+    }
+}
+
+pub async fn book(
+    params: web::Query<PositionParams>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let (board, colors) = params
+        .position()
+        .map_err(|error| Error::from(HttpResponse::BadRequest().body(error)))?;
+    match game::book_moves(&board, colors) {
+        Ok(moves) => Ok(HttpResponse::Ok().json(json!({ "moves": moves }))),
+        Err(error) => Err(game_error_response(error)),
+    }
+}
+
+pub async fn request_takeback(session: Session, req: HttpRequest) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let conn = get_db_connection(req)?;
+    if let (Some(session_id), Some(user_id)) = (
+        session.get::<Uuid>(SESSION_ID_KEY)?,
+        session.get::<Uuid>(USER_ID_KEY)?,
+    ) {
+        match game::request_takeback(session_id, user_id, conn.deref()) {
+            Ok(game_state) => Ok(HttpResponse::Ok().json(game_state)),
+            Err(error) => Err(game_error_response(error)),
+        }
+    } else {
+        Err(Error::from(
+            HttpResponse::InternalServerError().body("[request_takeback] No session info!"),
+        ))
+    }
+}
+
+pub async fn accept_takeback(session: Session, req: HttpRequest) -> Result<HttpResponse, Error> {
+    answer_takeback(true, session, req)
+}
+
+pub async fn decline_takeback(session: Session, req: HttpRequest) -> Result<HttpResponse, Error> {
+    answer_takeback(false, session, req)
+}
+
+fn answer_takeback(
+    accept: bool,
+    session: Session,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let conn = get_db_connection(req)?;
+    if let (Some(session_id), Some(user_id)) = (
+        session.get::<Uuid>(SESSION_ID_KEY)?,
+        session.get::<Uuid>(USER_ID_KEY)?,
+    ) {
+        match game::answer_takeback(session_id, user_id, accept, conn.deref()) {
+            Ok(game_state) => Ok(HttpResponse::Ok().json(game_state)),
+            Err(error) => Err(game_error_response(error)),
+        }
+    } else {
+        Err(Error::from(
+            HttpResponse::InternalServerError().body("[answer_takeback] No session info!"),
+        ))
+    }
+}
+
+/// Record of a finished game as text, see `record`.
+pub async fn export(
+    game_session_id: web::Path<Uuid>,
+    req: HttpRequest,
+) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let conn = get_db_connection(req)?;
+    match game::export_game(game_session_id.into_inner(), conn.deref()) {
+        Ok(record) => Ok(HttpResponse::Ok()
+            .content_type("text/plain")
+            .body(record.to_string())),
+        Err(error) => Err(game_error_response(error)),
+    }
+}
+
+/// Stores the game record sent in the body as a new game.
+pub async fn import(body: String, req: HttpRequest) -> Result<HttpResponse, Error> {
+    println!("REQ: {:?}", req);
+    let conn = get_db_connection(req)?;
+    let record = body
+        .parse::<GameRecord>()
+        .map_err(|error| game_error_response(game::GameError::InvalidRecord(error)))?;
+    match game::import_game(&record, conn.deref()) {
+        Ok(session_id) => {
+            Ok(HttpResponse::Ok().body(json!({ SESSION_ID_KEY: session_id.to_string() })))
+        }
+        Err(error) => Err(game_error_response(error)),
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -214,7 +657,8 @@ pub mod tests {
     use mocktopus::mocking::*;
     use std::panic;
 
-    use crate::utils;
+    use crate::engine::book::BookMove;
+    use crate::engine::zobrist::position_key;
     use db::create_conn_pool;
     use uuid::Uuid;
 
@@ -230,22 +674,41 @@ pub mod tests {
 
     fn mock_db_create_new_session(test_session_id: Uuid) {
         db::create_new_session
-            .mock_safe(move |_user, _conn| MockResult::Return(Result::Ok(test_session_id)));
+            .mock_safe(move |_user, _size, _conn| MockResult::Return(Result::Ok(test_session_id)));
+    }
+
+    /// A standard game on a 6x9 board of `user_1_id` alone, who has just moved.
+    fn game_state_fixture(test_session_id: Uuid, user_1_id: Uuid) -> models::GameState {
+        models::GameState {
+            id: test_session_id.to_string(),
+            board: Some("-".repeat(54)),
+            user_1: Some(user_1_id.to_string()),
+            user_2: None,
+            winner: false,
+            last_user_id: Some(user_1_id.to_string()),
+            last_user_color: Some("X".to_string()),
+            ended: false,
+            board_rows: 6,
+            board_columns: 9,
+            win_length: 5,
+            draw: false,
+            winner_color: None,
+            winning_cells: None,
+            ai_engine: None,
+            ai_level: None,
+            position_key: None,
+            move_list: String::new(),
+            version: 0,
+            takeback_requested_by: None,
+            rated: false,
+            rules: "standard".to_string(),
+            win_rule: "freestyle".to_string(),
+        }
     }
 
     fn mock_db_find_existing_game_session(test_session_id: Uuid, user_1_id: Uuid) {
         db::find_existing_game_session.mock_safe(move |_conn| {
-            let game_state = models::GameState {
-                id: test_session_id.to_string(),
-                board: Some("------------------------------------------------------".to_owned()),
-                user_1: Some(user_1_id.to_string()),
-                user_2: None,
-                winner: false,
-                last_user_id: Some(user_1_id.to_string()),
-                last_user_color: Some("X".to_string()),
-                ended: false,
-            };
-            MockResult::Return(Some(game_state))
+            MockResult::Return(Some(game_state_fixture(test_session_id, user_1_id)))
         });
     }
 
@@ -267,38 +730,44 @@ pub mod tests {
     ) {
         db::get_game_state.mock_safe(move |_sess, _conn| {
             let game_state = models::GameState {
-                id: test_session_id.to_string(),
                 board: Some(target_board.to_owned()),
-                user_1: Some(user_1_id.to_string()),
                 user_2: Some(user_2_id.to_string()),
-                winner: false,
-                last_user_id: Some(user_1_id.to_string()),
-                last_user_color: Some("X".to_string()),
-                ended: false,
+                ..game_state_fixture(test_session_id, user_1_id)
             };
             MockResult::Return(Result::Ok(game_state))
         });
     }
 
+    /// A game of two new players stored in the database, on the default board of the rules.
+    fn create_game(
+        pool: &Pool<ConnectionManager<SqliteConnection>>,
+        rule_set: RuleSet,
+    ) -> (Uuid, Uuid, Uuid) {
+        let conn = pool.get().unwrap();
+        let user_1 = db::create_new_user("test-user-1", "X", conn.deref()).unwrap();
+        let user_2 = db::create_new_user("test-user-2", "O", conn.deref()).unwrap();
+        let size = rule_set.rules().default_size();
+        let session_id = game::new_game(
+            user_1,
+            size,
+            WinRule::Freestyle,
+            rule_set,
+            false,
+            None,
+            conn.deref(),
+        )
+        .unwrap();
+        db::join_game_session(&session_id, &user_2, conn.deref()).unwrap();
+        (session_id, user_1, user_2)
+    }
+
     fn mock_db_join_game_session() {
         db::join_game_session
             .mock_safe(move |_sess, _user, _conn| MockResult::Return(Result::Ok(1)));
     }
 
-    fn mock_game_user_move(test_session_id: Uuid, user_1_id: Uuid, board: String) {
-        game::user_move.mock_safe(move |_sess, _user_id, _col_num, _conn| {
-            let game_state = models::GameState {
-                id: test_session_id.to_string(),
-                board: Some(board.to_owned()),
-                user_1: Some(user_1_id.to_string()),
-                user_2: None,
-                winner: false,
-                last_user_id: Some(user_1_id.to_string()),
-                last_user_color: Some("X".to_string()),
-                ended: false,
-            };
-            MockResult::Return(Result::Ok(game_state))
-        });
+    fn no_version() -> web::Query<MoveParams> {
+        web::Query::<MoveParams>::from_query("").unwrap()
     }
 
     fn create_user_session(test_session_id: Uuid, test_user_id: Uuid) -> Session {
@@ -325,6 +794,30 @@ pub mod tests {
         srv_req.get_session()
     }
 
+    fn create_spectator_session(test_session_id: Uuid, test_user_id: Uuid) -> Session {
+        let mut srv_req = TestRequest::get().to_srv_request();
+        Session::set_session(
+            vec![
+                (
+                    SESSION_ID_KEY.to_string(),
+                    serde_json::to_string(&test_session_id).unwrap(),
+                ),
+                (
+                    USER_ID_KEY.to_string(),
+                    serde_json::to_string(&test_user_id).unwrap(),
+                ),
+                (
+                    SPECTATOR_KEY.to_string(),
+                    serde_json::to_string(&true).unwrap(),
+                ),
+            ]
+            .into_iter(),
+            &mut srv_req,
+        );
+
+        srv_req.get_session()
+    }
+
     #[actix_rt::test]
     async fn test_register_user_post() {
         let pool = create_conn_pool();
@@ -378,7 +871,8 @@ pub mod tests {
         let user_1 = Uuid::new_v4();
         let session = create_user_session(test_session_id, user_1);
 
-        let response = new_game(session, req).await.unwrap();
+        let params = web::Query::<NewGameParams>::from_query("").unwrap();
+        let response = new_game(params, session, req).await.unwrap();
         assert!(response.status().is_success());
 
         // read response
@@ -394,6 +888,182 @@ pub mod tests {
         }
     }
 
+    #[actix_rt::test]
+    async fn test_new_post_invalid_board_size() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool)
+            .to_http_request();
+
+        let test_session_id = Uuid::new_v4();
+        mock_db_create_new_session(test_session_id);
+        let session = create_user_session(test_session_id, Uuid::new_v4());
+
+        let params =
+            web::Query::<NewGameParams>::from_query("rows=6&columns=7&win_length=9").unwrap();
+        let error = new_game(params, session, req).await.unwrap_err();
+        assert_eq!(
+            error.as_response_error().error_response().status(),
+            http::StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_new_post_ai() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool)
+            .to_http_request();
+
+        let test_session_id = Uuid::new_v4();
+        mock_db_create_new_session(test_session_id);
+        let user_1 = Uuid::new_v4();
+        let session = create_user_session(test_session_id, user_1);
+        game::add_ai_player.mock_safe(move |session_id, user_id, engine, level, _conn| {
+            assert_eq!(session_id, test_session_id);
+            assert_eq!(user_id, user_1);
+            assert_eq!(engine, AiEngine::Minimax);
+            assert_eq!(level, AiLevel::Beginner);
+            MockResult::Return(Ok(Uuid::new_v4()))
+        });
+
+        let params = web::Query::<NewGameParams>::from_query("ai=minimax&level=beginner").unwrap();
+        let response = new_game(params, session, req).await.unwrap();
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn test_new_post_ai_rules() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool)
+            .to_http_request();
+
+        for query in ["rules=popout&ai=mcts", "win_rule=renju&ai=minimax"].iter() {
+            let session = create_user_session(Uuid::new_v4(), Uuid::new_v4());
+            let params = web::Query::<NewGameParams>::from_query(query).unwrap();
+            let error = new_game(params, session, req.clone()).await.unwrap_err();
+            let response = error.as_response_error().error_response();
+            assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+            let body = get_body_str(response.body().as_ref().unwrap());
+            assert!(body.contains("invalid_game"));
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_new_post_rated() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool)
+            .to_http_request();
+
+        let test_session_id = Uuid::new_v4();
+        mock_db_create_new_session(test_session_id);
+        let session = create_user_session(test_session_id, Uuid::new_v4());
+        db::update_rated.mock_safe(move |session_id, rated, _conn| {
+            assert_eq!(*session_id, test_session_id);
+            assert!(rated);
+            MockResult::Return(Ok(1))
+        });
+
+        let params = web::Query::<NewGameParams>::from_query("rated=true").unwrap();
+        let response = new_game(params, session, req).await.unwrap();
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn test_new_post_unknown_ai() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool)
+            .to_http_request();
+
+        let test_session_id = Uuid::new_v4();
+        mock_db_create_new_session(test_session_id);
+        let session = create_user_session(test_session_id, Uuid::new_v4());
+
+        let params = web::Query::<NewGameParams>::from_query("ai=deep-blue").unwrap();
+        let error = new_game(params, session, req).await.unwrap_err();
+        assert_eq!(
+            error.as_response_error().error_response().status(),
+            http::StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_new_post_unknown_rules() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool)
+            .to_http_request();
+
+        let test_session_id = Uuid::new_v4();
+        mock_db_create_new_session(test_session_id);
+        let session = create_user_session(test_session_id, Uuid::new_v4());
+
+        let params = web::Query::<NewGameParams>::from_query("rules=chess").unwrap();
+        let error = new_game(params, session, req).await.unwrap_err();
+        assert_eq!(
+            error.as_response_error().error_response().status(),
+            http::StatusCode::BAD_REQUEST
+        );
+        let params = web::Query::<NewGameParams>::from_query("rules=standard").unwrap();
+        assert_eq!(params.rule_set(), Ok(RuleSet::Standard));
+    }
+
+    #[test]
+    fn test_new_game_params_rules_board_size() {
+        let params = web::Query::<NewGameParams>::from_query("rules=gomoku").unwrap();
+        assert_eq!(params.board_size(), BoardSize::new(15, 15, 5));
+        let params = web::Query::<NewGameParams>::from_query("rules=gomoku&rows=19").unwrap();
+        assert_eq!(params.board_size(), BoardSize::new(19, 15, 5));
+        let params = web::Query::<NewGameParams>::from_query("").unwrap();
+        assert_eq!(params.board_size(), BoardSize::default());
+    }
+
+    #[test]
+    fn test_new_game_params_win_rule() {
+        let query = "rules=gomoku&win_rule=renju";
+        let params = web::Query::<NewGameParams>::from_query(query).unwrap();
+        assert_eq!(params.win_rule(), Ok(WinRule::Renju));
+        assert_eq!(params.rule_set(), Ok(RuleSet::Gomoku));
+        let query = "rules=popout&win_rule=renju";
+        let params = web::Query::<NewGameParams>::from_query(query).unwrap();
+        assert!(params.rule_set().is_err());
+        let params = web::Query::<NewGameParams>::from_query("win_rule=sudden").unwrap();
+        assert!(params.rule_set().is_err());
+    }
+
+    #[test]
+    fn test_new_game_params_ai_opponent() {
+        let params = web::Query::<NewGameParams>::from_query("").unwrap();
+        assert_eq!(params.ai_opponent(), Ok(None));
+        let params = web::Query::<NewGameParams>::from_query("ai=minimax").unwrap();
+        assert_eq!(
+            params.ai_opponent(),
+            Ok(Some((AiEngine::Minimax, AiLevel::Intermediate)))
+        );
+        let params = web::Query::<NewGameParams>::from_query("ai=mcts&level=expert").unwrap();
+        assert_eq!(
+            params.ai_opponent(),
+            Ok(Some((AiEngine::Mcts, AiLevel::Expert)))
+        );
+        let params = web::Query::<NewGameParams>::from_query("ai=minimax&level=godlike").unwrap();
+        assert!(params.ai_opponent().is_err());
+        // no EXTERNAL_ENGINE in the tests
+        let params = web::Query::<NewGameParams>::from_query("ai=external").unwrap();
+        assert!(params
+            .ai_opponent()
+            .unwrap_err()
+            .contains("EXTERNAL_ENGINE"));
+    }
+
     #[actix_rt::test]
     async fn test_find_get() {
         let pool = create_conn_pool();
@@ -439,6 +1109,32 @@ pub mod tests {
         assert_eq!(resp.unwrap().status(), http::StatusCode::OK);
     }
 
+    #[actix_rt::test]
+    async fn test_join_post_full_game() {
+        let pool = create_conn_pool();
+        let conn = pool.get().unwrap();
+        let user_1 = Uuid::new_v4();
+        let session_id =
+            db::create_new_session(&user_1, &BoardSize::default(), conn.deref()).unwrap();
+
+        for (user_id, status) in vec![
+            (user_1, http::StatusCode::CONFLICT),
+            (Uuid::new_v4(), http::StatusCode::OK),
+            (Uuid::new_v4(), http::StatusCode::CONFLICT),
+        ] {
+            let req = TestRequest::with_header("content-type", "application/json")
+                .method(Method::POST)
+                .app_data(pool.clone())
+                .to_http_request();
+            let session = create_user_session(session_id, user_id);
+            let resp = match join(web::Path::from(session_id), session, req).await {
+                Ok(resp) => resp,
+                Err(error) => error.as_response_error().error_response(),
+            };
+            assert_eq!(resp.status(), status);
+        }
+    }
+
     #[actix_rt::test]
     async fn test_board_get() {
         let pool = create_conn_pool();
@@ -504,26 +1200,11 @@ pub mod tests {
             .app_data(pool.clone())
             .to_http_request();
 
-        let test_session_id = Uuid::new_v4();
-        let user_1 = Uuid::new_v4();
-        let column = 5;
-
-        let board = vec![
-            vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
-            vec!['-', '-', '-', 'X', 'X', 'X', '-', '-', '-'],
-        ];
-
-        mock_db_create_new_session(test_session_id);
-        mock_db_join_game_session();
-        mock_game_user_move(test_session_id, user_1, utils::arr_to_str(&board));
-
+        // the move is played on another thread, mocks don't reach it: play in a stored game
+        let (test_session_id, user_1, _user_2) = create_game(&pool, RuleSet::Standard);
         let session = create_user_session(test_session_id, user_1);
 
-        let response = make_move(web::Path::from(column), session, req)
+        let response = make_move(web::Path::from(5), no_version(), session, req)
             .await
             .unwrap();
 
@@ -535,9 +1216,400 @@ pub mod tests {
             Body::Bytes(ref body_bytes) => {
                 let game_state: models::GameState = serde_json::from_slice(body_bytes).unwrap();
                 assert_eq!(game_state.id, test_session_id.to_string());
-                assert_eq!(game_state.board.unwrap(), utils::arr_to_str(&board));
+                assert_eq!(game_state.board.unwrap(), "-".repeat(49) + "X----");
+                assert_eq!(game_state.move_list, "5");
             }
             _ => panic!("Response body is empty"),
         }
     }
+
+    #[actix_rt::test]
+    async fn test_make_move_not_your_turn() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let (test_session_id, _user_1, user_2) = create_game(&pool, RuleSet::Standard);
+        let session = create_user_session(test_session_id, user_2);
+
+        let error = make_move(web::Path::from(3), no_version(), session, req)
+            .await
+            .unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+    }
+
+    #[actix_rt::test]
+    async fn test_make_move_version_conflict() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let (test_session_id, user_1, _user_2) = create_game(&pool, RuleSet::Standard);
+        let session = create_user_session(test_session_id, user_1);
+        let params = web::Query::<MoveParams>::from_query("version=7").unwrap();
+
+        let error = make_move(web::Path::from(3), params, session, req)
+            .await
+            .unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+        let body = get_body_str(response.body().as_ref().unwrap());
+        assert!(body.contains(game::GameError::Conflict.code()));
+    }
+
+    #[actix_rt::test]
+    async fn test_pop() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let (test_session_id, user_1, _user_2) = create_game(&pool, RuleSet::PopOut);
+        let session = create_user_session(test_session_id, user_1);
+
+        let error = pop(web::Path::from(3), no_version(), session, req)
+            .await
+            .unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+        let body = get_body_str(response.body().as_ref().unwrap());
+        assert!(body.contains("You can only pop out your own pieces"));
+    }
+
+    #[actix_rt::test]
+    async fn test_place() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let (test_session_id, user_1, user_2) = create_game(&pool, RuleSet::Gomoku);
+        let session = create_user_session(test_session_id, user_1);
+        let response = place(web::Path::from((8, 7)), no_version(), session, req.clone())
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+
+        let session = create_user_session(test_session_id, user_2);
+        let error = place(web::Path::from((8, 7)), no_version(), session, req)
+            .await
+            .unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+        let body = get_body_str(response.body().as_ref().unwrap());
+        assert!(body.contains("This cell is already taken"));
+    }
+
+    #[actix_rt::test]
+    async fn test_takeback() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool.clone())
+            .to_http_request();
+        let test_session_id = Uuid::new_v4();
+        let user_1 = Uuid::new_v4();
+        game::request_takeback.mock_safe(|_sess, _user_id, _conn| {
+            MockResult::Return(Result::Err(game::GameError::TakebackNotAllowed(
+                "no".to_string(),
+            )))
+        });
+        game::answer_takeback.mock_safe(move |sess, _user_id, accept, _conn| {
+            assert_eq!(sess, test_session_id);
+            assert!(accept);
+            MockResult::Return(Result::Err(game::GameError::NotAPlayer))
+        });
+
+        let session = create_user_session(test_session_id, user_1);
+        let error = request_takeback(session, req.clone()).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::CONFLICT);
+
+        let session = create_user_session(test_session_id, user_1);
+        let error = accept_takeback(session, req).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn test_game_state_not_a_player() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::GET)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let new_session_id = Uuid::new_v4();
+        let target_board = "------------------------------------------------------";
+        mock_db_get_game_state(new_session_id, Uuid::new_v4(), Uuid::new_v4(), target_board);
+
+        let session = create_user_session(new_session_id, Uuid::new_v4());
+
+        let error = game_state(session, req).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn test_game_state_spectator() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::GET)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let new_session_id = Uuid::new_v4();
+        let target_board = "------------------------------------------------------";
+        mock_db_get_game_state(new_session_id, Uuid::new_v4(), Uuid::new_v4(), target_board);
+
+        let session = create_spectator_session(new_session_id, Uuid::new_v4());
+
+        let response = game_state(session, req).await.unwrap();
+        assert!(response.status().is_success());
+    }
+
+    #[actix_rt::test]
+    async fn test_game_state_ai_answer() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::GET)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let conn = pool.get().unwrap();
+        let user_1 = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let ai_opponent = Some((AiEngine::Minimax, AiLevel::Beginner));
+        let session_id = game::new_game(
+            user_1,
+            BoardSize::default(),
+            WinRule::Freestyle,
+            RuleSet::Standard,
+            false,
+            ai_opponent,
+            conn.deref(),
+        )
+        .unwrap();
+        // the computer hasn't answered the first move yet
+        let board = "-".repeat(48) + "X-----";
+        db::update_game_state(
+            &session_id,
+            &user_1,
+            &board,
+            false,
+            false,
+            false,
+            conn.deref(),
+        )
+        .unwrap();
+        db::update_move_list(&session_id, "4", conn.deref()).unwrap();
+
+        // spectators only read the game
+        let session = create_spectator_session(session_id, Uuid::new_v4());
+        let response = game_state(session, req.clone()).await.unwrap();
+        let body = get_body_str(response.body().as_ref().unwrap());
+        let state: models::GameState = serde_json::from_str(&body).unwrap();
+        assert!(state.is_ai_turn());
+        assert_eq!(state.move_list, "4");
+
+        let session = create_user_session(session_id, user_1);
+        let response = game_state(session, req).await.unwrap();
+        let body = get_body_str(response.body().as_ref().unwrap());
+        let state: models::GameState = serde_json::from_str(&body).unwrap();
+        assert!(!state.is_ai_turn());
+        assert_eq!(state.move_list.split_whitespace().count(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn test_make_move_not_a_player() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::POST)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        let (test_session_id, _user_1, _user_2) = create_game(&pool, RuleSet::Standard);
+        let session = create_spectator_session(test_session_id, Uuid::new_v4());
+
+        let error = make_move(web::Path::from(3), no_version(), session, req)
+            .await
+            .unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+    }
+
+    #[actix_rt::test]
+    async fn test_export() {
+        let pool = create_conn_pool();
+        let req = TestRequest::get().app_data(pool).to_http_request();
+        let test_session_id = Uuid::new_v4();
+        game::export_game.mock_safe(move |session_id, _conn| {
+            assert_eq!(session_id, test_session_id);
+            MockResult::Return(Ok("1. 4 4".parse::<GameRecord>().unwrap()))
+        });
+
+        let response = export(web::Path::from(test_session_id), req).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let body = get_body_str(response.body().as_ref().unwrap());
+        assert!(body.ends_with("1. 4 4 *\n"));
+    }
+
+    #[actix_rt::test]
+    async fn test_export_not_finished() {
+        let pool = create_conn_pool();
+        let req = TestRequest::get().app_data(pool).to_http_request();
+        game::export_game
+            .mock_safe(|_session_id, _conn| MockResult::Return(Err(game::GameError::NotFinished)));
+
+        let error = export(web::Path::from(Uuid::new_v4()), req)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.as_response_error().error_response().status(),
+            http::StatusCode::CONFLICT
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_import() {
+        let pool = create_conn_pool();
+        let req = TestRequest::post().app_data(pool).to_http_request();
+        let test_session_id = Uuid::new_v4();
+        game::import_game.mock_safe(move |record, _conn| {
+            assert_eq!(record.notation.moves, vec![4, 4]);
+            MockResult::Return(Ok(test_session_id))
+        });
+
+        let response = import("1. 4 4 *".to_string(), req).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let body = get_body_str(response.body().as_ref().unwrap());
+        assert!(body.contains(&test_session_id.to_string()));
+    }
+
+    #[test]
+    fn test_analysis_params_position() {
+        let params = web::Query::<PositionParams>::from_query("moves=4+4+3").unwrap();
+        let (board, colors) = params.position().unwrap();
+        assert_eq!(colors, DEFAULT_COLORS);
+        assert_eq!(board.cell(5, 3), 'X');
+        assert_eq!(board.cell(4, 3), 'O');
+
+        let params = web::Query::<PositionParams>::from_query("moves=2x3%2F2+2").unwrap();
+        assert_eq!(params.position().unwrap().0.columns(), 3);
+
+        let board = "---".to_string() + "-O-";
+        let query = format!("board={}&rows=2&columns=3&win_length=2", board);
+        let params = web::Query::<PositionParams>::from_query(&query).unwrap();
+        assert_eq!(params.position().unwrap().1, ['O', 'X']);
+        let params =
+            web::Query::<PositionParams>::from_query(&query.replace("-O-", "---")).unwrap();
+        assert_eq!(params.position().unwrap().1, ['X', 'O']);
+        let params =
+            web::Query::<PositionParams>::from_query(&(query.replace("-O-", "---") + "&first=O"))
+                .unwrap();
+        assert_eq!(params.position().unwrap().1, ['O', 'X']);
+
+        let params = web::Query::<PositionParams>::from_query("").unwrap();
+        assert!(params.position().is_err());
+        let params = web::Query::<PositionParams>::from_query("board=---").unwrap();
+        assert!(params.position().is_err());
+        let params = web::Query::<PositionParams>::from_query("moves=4&win_rule=exact").unwrap();
+        assert_eq!(params.position().unwrap().0.win_rule(), WinRule::Exact);
+        let params = web::Query::<PositionParams>::from_query("moves=4&win_rule=renju").unwrap();
+        assert!(params.position().is_err());
+        let params = web::Query::<PositionParams>::from_query("moves=6x9%2F5%2Frenju+4").unwrap();
+        assert!(params.position().is_err());
+
+        let params = web::Query::<PositionParams>::from_query("moves=4+p4&rules=popout").unwrap();
+        assert!(params.position().unwrap_err().contains("popout"));
+        let params = web::Query::<PositionParams>::from_query("moves=4+p4").unwrap();
+        assert!(params.position().unwrap_err().contains("p4 is not a drop"));
+        let params = web::Query::<PositionParams>::from_query("moves=h8+h9").unwrap();
+        assert!(params.position().unwrap_err().contains("h8 is not a drop"));
+        let params = web::Query::<PositionParams>::from_query("moves=4&rules=chess").unwrap();
+        assert!(params.position().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_analysis() {
+        let pool = create_conn_pool();
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::GET)
+            .app_data(pool.clone())
+            .to_http_request();
+
+        // the analysis runs on another thread, mocks don't reach it: play the position in a
+        // rated game instead
+        let conn = pool.get().unwrap();
+        let session_id =
+            db::create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        db::update_rated(&session_id, true, conn.deref()).unwrap();
+        let board = Notation::new(BoardSize::default(), vec![2, 8, 2, 8, 3])
+            .board()
+            .unwrap();
+        let key = position_key(&board, 'X');
+        db::update_position_key(&session_id, &key, conn.deref()).unwrap();
+        let params = web::Query::<PositionParams>::from_query("moves=2+8+2+8+3").unwrap();
+        let error = analysis(params, req.clone()).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        let params = web::Query::<PositionParams>::from_query("moves=4+x").unwrap();
+        let error = analysis(params, req.clone()).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+
+        let params = web::Query::<PositionParams>::from_query("moves=4+p4").unwrap();
+        let error = analysis(params, req).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn test_book() {
+        let req = TestRequest::with_header("content-type", "application/json")
+            .method(Method::GET)
+            .to_http_request();
+
+        game::book_moves.mock_safe(|board, colors| {
+            assert_eq!(board.cell(5, 4), 'X');
+            assert_eq!(colors, DEFAULT_COLORS);
+            MockResult::Return(Ok(vec![BookMove {
+                column: 5,
+                weight: 3,
+            }]))
+        });
+        let params = web::Query::<PositionParams>::from_query("moves=5").unwrap();
+        let response = book(params, req.clone()).await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(
+            get_body_str(&response.body().as_ref().unwrap()),
+            r#"{"moves":[{"column":5,"weight":3}]}"#
+        );
+
+        game::book_moves
+            .mock_safe(|_board, _colors| MockResult::Return(Err(game::GameError::NoOpeningBook)));
+        let params = web::Query::<PositionParams>::from_query("moves=5").unwrap();
+        let error = book(params, req).await.unwrap_err();
+        let response = error.as_response_error().error_response();
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_import_invalid_record() {
+        let pool = create_conn_pool();
+        let req = TestRequest::post().app_data(pool).to_http_request();
+
+        let error = import("1. 4 four".to_string(), req).await.unwrap_err();
+        assert_eq!(
+            error.as_response_error().error_response().status(),
+            http::StatusCode::BAD_REQUEST
+        );
+    }
 }
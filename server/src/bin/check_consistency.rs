@@ -0,0 +1,44 @@
+//! Compares every row of `game_state` with the replay of its moves and prints the games that
+//! disagree. With `--fix` the derived columns of those games are rebuilt from the moves.
+//!
+//! `cargo run --bin check_consistency [-- --fix]`
+use connect5_rust::{db, projection};
+use std::process;
+use uuid::Uuid;
+
+fn main() {
+    let fix = match std::env::args().nth(1).as_deref() {
+        None => false,
+        Some("--fix") => true,
+        Some(arg) => {
+            eprintln!(
+                "Unknown argument: {}. Usage: check_consistency [--fix]",
+                arg
+            );
+            process::exit(2);
+        }
+    };
+    let conn = db::establish_connection();
+    let problems = projection::check_all_games(&conn).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+    for (id, problem) in &problems {
+        println!("{}: {}", id, problem);
+        if fix {
+            let rebuilt = Uuid::parse_str(id)
+                .map_err(|error| error.to_string())
+                .and_then(|ses_id| {
+                    projection::rebuild_game_state(&ses_id, &conn).map_err(|e| e.to_string())
+                });
+            match rebuilt {
+                Ok(_) => println!("{}: rebuilt", id),
+                Err(error) => println!("{}: not rebuilt, {}", id, error),
+            }
+        }
+    }
+    println!("{} inconsistent games", problems.len());
+    if !problems.is_empty() && !fix {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,135 @@
+//! Generates an opening book and writes it to stdout, in the format read by
+//! `engine::book::OpeningBook`. Point the `OPENING_BOOK` environment variable at the file to let
+//! the computer player and `/api/book` use it.
+//!
+//! From engine self-play, on a board of the given size:
+//!
+//! `cargo run --bin generate_book -- self-play [--games 100] [--plies 6] [--time-ms 100]
+//!  [--rows 6 --columns 9 --win-length 5] > opening_book.txt`
+//!
+//! From the finished games stored in the database:
+//!
+//! `cargo run --bin generate_book -- games [--plies 6] > opening_book.txt`
+use connect5_rust::board::{BoardSize, WinRule};
+use connect5_rust::db;
+use connect5_rust::engine::bitboard::BitBoard;
+use connect5_rust::engine::book::OpeningBook;
+use connect5_rust::engine::level::Handicapped;
+use connect5_rust::engine::minimax::Minimax;
+use connect5_rust::engine::AiPlayer;
+use connect5_rust::notation::Notation;
+use connect5_rust::rules::RuleSet;
+use std::collections::HashMap;
+use std::process;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Share of random moves in self-play games, so that they don't all follow the same line.
+const SELF_PLAY_MISTAKE_RATE: f64 = 0.2;
+
+const USAGE: &str = "Usage: generate_book self-play [--games N] [--plies N] [--time-ms N] \
+                     [--rows N] [--columns N] [--win-length N]\n       \
+                     generate_book games [--plies N]";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (mode, options) = match args.split_first() {
+        Some((mode, options)) => (mode.as_str(), parse_options(options).unwrap_or_else(exit)),
+        None => exit(USAGE.to_string()),
+    };
+    let option = |name: &str, default: usize| options.get(name).copied().unwrap_or(default);
+    let plies = option("plies", 6);
+    let book = match mode {
+        "self-play" => {
+            let default = BoardSize::default();
+            let size = BoardSize::new(
+                option("rows", default.rows),
+                option("columns", default.columns),
+                option("win-length", default.win_length),
+            );
+            let time = Duration::from_millis(option("time-ms", 100) as u64);
+            self_play(size, option("games", 100), plies, time)
+        }
+        "games" => stored_games(plies),
+        _ => Err(USAGE.to_string()),
+    }
+    .unwrap_or_else(exit);
+    eprintln!("{} positions", book.len());
+    print!("{}", book);
+}
+
+fn exit<T>(error: String) -> T {
+    eprintln!("{}", error);
+    process::exit(2)
+}
+
+// `--name value` pairs with numeric values
+fn parse_options(args: &[String]) -> Result<HashMap<String, usize>, String> {
+    let mut options = HashMap::new();
+    for pair in args.chunks(2) {
+        match pair {
+            [name, value] if name.starts_with("--") => {
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Invalid value of {}: {}", name, value))?;
+                options.insert(name.trim_start_matches("--").to_string(), value);
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn self_play(
+    size: BoardSize,
+    games: usize,
+    plies: usize,
+    time: Duration,
+) -> Result<OpeningBook, String> {
+    let mut book = OpeningBook::new();
+    let mut player = Handicapped::new(
+        Box::new(Minimax::new(size.cell_count(), time)),
+        SELF_PLAY_MISTAKE_RATE,
+    );
+    for game in 0..games {
+        let mut position = BitBoard::new(size)?;
+        let mut moves = vec![];
+        while position.winner().is_none() && !position.is_full() {
+            let col = player
+                .choose_move(&position)
+                .ok_or("The engine has no move to play")?;
+            position.play(col);
+            moves.push(col);
+        }
+        book.add_game(size, WinRule::Freestyle, &moves, plies)?;
+        eprintln!("game {}: {} moves", game + 1, moves.len());
+    }
+    Ok(book)
+}
+
+fn stored_games(plies: usize) -> Result<OpeningBook, String> {
+    let conn = db::establish_connection();
+    let mut book = OpeningBook::new();
+    for id in db::get_game_ids(&conn).map_err(|error| error.to_string())? {
+        let ses_id = Uuid::parse_str(&id).map_err(|error| error.to_string())?;
+        let game_state = db::get_game_state(&ses_id, &conn).map_err(|error| error.to_string())?;
+        if !game_state.ended || game_state.rule_set() != Ok(RuleSet::Standard) {
+            continue;
+        }
+        let size = game_state.board_size();
+        let win_rule = game_state.win_rule();
+        let moves = Notation::from_move_list(size, win_rule, &game_state.move_list)?
+            .moves
+            .iter()
+            .map(|col| col - 1)
+            .collect::<Vec<_>>();
+        // games played before the moves were recorded, or too big for the engine
+        if moves.is_empty() || BitBoard::new(size).is_err() {
+            continue;
+        }
+        if let Err(error) = book.add_game(size, win_rule, &moves, plies) {
+            eprintln!("Skipped game {}: {}", id, error);
+        }
+    }
+    Ok(book)
+}
@@ -0,0 +1,194 @@
+//! Plays two engine configurations against each other and reports the result of the first one,
+//! with the Elo difference and its 95% error bar. Every random opening is played twice, with
+//! the engines swapping sides.
+//!
+//! `cargo run --release --bin match_runner -- --first minimax:expert --second mcts:expert
+//!  [--games 100] [--opening-plies 2] [--rows 6 --columns 9 --win-length 5]
+//!  [--win-rule exact] [--seed N]
+//!  [--sprt 0,20 [--alpha 0.05] [--beta 0.05]] [--records games.txt]`
+//!
+//! With `--sprt elo0,elo1` the match stops as soon as the sequential probability ratio test
+//! decides whether the first engine is `elo1` rather than `elo0` stronger. `--records` writes
+//! the games in the game record format, separated by blank lines. The `external` engine is the
+//! process started by the `EXTERNAL_ENGINE` command. The engines don't know the forbidden moves
+//! of the renju win rule, so only the freestyle and exact win rules are played.
+use connect5_rust::board::{BoardSize, WinRule};
+use connect5_rust::engine::tournament::{
+    play_game, random_opening, EngineConfig, MatchResult, Sprt, SprtResult,
+};
+use connect5_rust::notation::Notation;
+use connect5_rust::record::{GameRecord, GameResult, UNKNOWN_DATE};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::process;
+use std::str::FromStr;
+
+const USAGE: &str = "Usage: match_runner --first ENGINE[:LEVEL] --second ENGINE[:LEVEL] \
+                     [--games N] [--opening-plies N] [--rows N] [--columns N] [--win-length N] \
+                     [--win-rule freestyle|exact] [--seed N] [--sprt ELO0,ELO1] [--alpha P] [--beta P] [--records FILE]";
+
+const COLORS: [char; 2] = ['X', 'O'];
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let options = parse_options(&args).unwrap_or_else(exit);
+    run(&options).unwrap_or_else(exit);
+}
+
+fn exit<T>(error: String) -> T {
+    eprintln!("{}", error);
+    process::exit(2)
+}
+
+// `--name value` pairs
+fn parse_options(args: &[String]) -> Result<HashMap<String, String>, String> {
+    let mut options = HashMap::new();
+    for pair in args.chunks(2) {
+        match pair {
+            [name, value] if name.starts_with("--") => {
+                options.insert(name.trim_start_matches("--").to_string(), value.clone());
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(options)
+}
+
+fn option<T: FromStr>(options: &HashMap<String, String>, name: &str) -> Result<Option<T>, String> {
+    options
+        .get(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid value of --{}: {}", name, value))
+        })
+        .transpose()
+}
+
+fn parse_sprt(options: &HashMap<String, String>) -> Result<Option<Sprt>, String> {
+    let bounds = match options.get("sprt") {
+        Some(bounds) => bounds,
+        None => return Ok(None),
+    };
+    let invalid = || format!("Invalid value of --sprt: {}, expected ELO0,ELO1", bounds);
+    let (elo0, elo1) = bounds.split_once(',').ok_or_else(invalid)?;
+    let mut sprt = Sprt::new(
+        elo0.trim().parse().map_err(|_| invalid())?,
+        elo1.trim().parse().map_err(|_| invalid())?,
+    );
+    sprt.alpha = option(options, "alpha")?.unwrap_or(sprt.alpha);
+    sprt.beta = option(options, "beta")?.unwrap_or(sprt.beta);
+    Ok(Some(sprt))
+}
+
+fn run(options: &HashMap<String, String>) -> Result<(), String> {
+    let configs: [EngineConfig; 2] = [
+        option(options, "first")?.ok_or(USAGE)?,
+        option(options, "second")?.ok_or(USAGE)?,
+    ];
+    let default = BoardSize::default();
+    let size = BoardSize::new(
+        option(options, "rows")?.unwrap_or(default.rows),
+        option(options, "columns")?.unwrap_or(default.columns),
+        option(options, "win-length")?.unwrap_or(default.win_length),
+    );
+    let win_rule = option(options, "win-rule")?.unwrap_or_default();
+    if win_rule == WinRule::Renju {
+        return Err("The engines don't know the renju restrictions".to_string());
+    }
+    let games: usize = option(options, "games")?.unwrap_or(100);
+    let opening_plies = option(options, "opening-plies")?.unwrap_or(2);
+    let mut rng = match option(options, "seed")? {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let sprt = parse_sprt(options)?;
+    let mut records = match options.get("records") {
+        Some(path) => Some(File::create(path).map_err(|error| error.to_string())?),
+        None => None,
+    };
+
+    let mut players = [configs[0].player(), configs[1].player()];
+    let mut result = MatchResult::default();
+    let mut opening = vec![];
+    for game in 0..games {
+        // the first engine moves first in even games, the second one repeats its opening
+        let swapped = game % 2 == 1;
+        if !swapped {
+            opening = random_opening(size, win_rule, opening_plies, &mut rng)?;
+        }
+        if swapped {
+            players.swap(0, 1);
+        }
+        let played = play_game(&mut players, size, win_rule, &opening);
+        if swapped {
+            players.swap(0, 1);
+        }
+        let (moves, winner) = played?;
+        result.add(winner.map(|player| (player == 0) != swapped));
+
+        let game_result = match winner {
+            Some(0) => GameResult::FirstWins,
+            Some(_) => GameResult::SecondWins,
+            None => GameResult::Draw,
+        };
+        let names = if swapped {
+            [configs[1].to_string(), configs[0].to_string()]
+        } else {
+            [configs[0].to_string(), configs[1].to_string()]
+        };
+        println!(
+            "game {}: {} - {} {}, {}",
+            game + 1,
+            names[0],
+            names[1],
+            game_result,
+            result
+        );
+        if let Some(file) = records.as_mut() {
+            let record = GameRecord {
+                players: names,
+                colors: COLORS,
+                date: UNKNOWN_DATE.to_string(),
+                result: game_result,
+                notation: Notation::new(size, moves.iter().map(|col| col + 1).collect())
+                    .with_win_rule(win_rule),
+            };
+            writeln!(file, "{}", record).map_err(|error| error.to_string())?;
+        }
+
+        if let Some(sprt) = sprt {
+            match sprt.test(&result) {
+                SprtResult::Continue => {}
+                SprtResult::AcceptH0 => {
+                    println!(
+                        "SPRT: H0 accepted, {} is not {} Elo stronger",
+                        configs[0], sprt.elo1
+                    );
+                    break;
+                }
+                SprtResult::AcceptH1 => {
+                    println!(
+                        "SPRT: H1 accepted, {} is {} Elo stronger",
+                        configs[0], sprt.elo1
+                    );
+                    break;
+                }
+            }
+        }
+    }
+    println!("{} vs {}: {}", configs[0], configs[1], result);
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.bounds();
+        println!(
+            "LLR {:.2} ({:.2}, {:.2})",
+            result.llr(sprt.elo0, sprt.elo1),
+            lower,
+            upper
+        );
+    }
+    Ok(())
+}
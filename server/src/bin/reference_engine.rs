@@ -0,0 +1,102 @@
+//! Reference engine of the external engine protocol, see `engine::external`, playing with the
+//! engines of the server.
+//!
+//! `cargo run --release --bin reference_engine [minimax|mcts]`
+//!
+//! To play it as the computer opponent, build it and point the server at it with
+//! `EXTERNAL_ENGINE=target/release/reference_engine`.
+use connect5_rust::engine::analysis::{self, Evaluation};
+use connect5_rust::engine::bitboard::BitBoard;
+use connect5_rust::engine::external::{Command, Reply, COLORS};
+use connect5_rust::engine::mcts::Mcts;
+use connect5_rust::engine::{AiEngine, AiPlayer};
+use std::io::{self, BufRead};
+use std::process;
+use std::time::Duration;
+
+fn main() {
+    let engine = match std::env::args().nth(1) {
+        Some(name) => name.parse::<AiEngine>().unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            process::exit(2)
+        }),
+        None => AiEngine::Minimax,
+    };
+    if engine == AiEngine::External {
+        eprintln!("The reference engine plays with minimax or mcts");
+        process::exit(2)
+    }
+    let mut position = None;
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let command = match line.parse::<Command>() {
+            Ok(command) => command,
+            // unknown commands are ignored
+            Err(_) => continue,
+        };
+        match command {
+            Command::Hello => {
+                reply(Reply::Id {
+                    name: format!("connect5-rust {}", engine),
+                });
+                reply(Reply::HelloOk);
+            }
+            Command::IsReady => reply(Reply::ReadyOk),
+            Command::Position {
+                size,
+                win_rule,
+                cells,
+            } => {
+                position = BitBoard::from_board_str(&cells, size, COLORS)
+                    .map(|position| position.with_win_rule(win_rule))
+                    .map_err(|error| reply(Reply::Info(format!("string {}", error))))
+                    .ok();
+            }
+            Command::Go { movetime } => {
+                let best = match &position {
+                    Some(position) => search(engine, position, movetime),
+                    None => None,
+                };
+                reply(Reply::BestMove(best.map(|col| col + 1)));
+            }
+            Command::Quit => break,
+        }
+    }
+}
+
+fn reply(reply: Reply) {
+    println!("{}", reply);
+}
+
+fn search(engine: AiEngine, position: &BitBoard, movetime: Duration) -> Option<usize> {
+    if position.winner().is_some() {
+        return None;
+    }
+    if engine == AiEngine::Mcts {
+        return Mcts::new(None, Some(movetime)).choose_move(position);
+    }
+    let analysis = analysis::analyse(position, movetime);
+    let best = analysis.best?;
+    if let Some(column) = analysis.columns.iter().find(|c| c.column == best) {
+        let score = match column.evaluation {
+            Evaluation::Win { moves } => format!("win {}", moves),
+            Evaluation::Loss { moves } => format!("loss {}", moves),
+            Evaluation::Draw => "draw".to_string(),
+            Evaluation::Score { score } => score.to_string(),
+        };
+        let pv = analysis
+            .pv
+            .iter()
+            .map(|col| col.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        reply(Reply::Info(format!(
+            "depth {} score {} pv {}",
+            analysis.depth, score, pv
+        )));
+    }
+    Some(best - 1)
+}
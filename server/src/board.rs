@@ -0,0 +1,804 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+pub use crate::utils;
+
+pub const EMPTY_CELL: char = '-';
+pub const MAX_DIMENSION: usize = 20;
+
+/// Which sequences of at least `win_length` equal pieces win. Stored with every game in
+/// `game_state.win_rule`, see `Board::with_win_rule`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WinRule {
+    /// any sequence of `win_length` or more pieces
+    #[default]
+    Freestyle,
+    /// exactly `win_length` pieces, longer sequences (overlines) don't win
+    Exact,
+    /// overlines don't win for the first player, who is also forbidden to play an overline, two
+    /// fours or two open threes with one move
+    Renju,
+}
+
+impl WinRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WinRule::Freestyle => "freestyle",
+            WinRule::Exact => "exact",
+            WinRule::Renju => "renju",
+        }
+    }
+
+    /// True if a sequence of `length` pieces wins, `first_player` being true for the pieces of
+    /// the player who moved first.
+    pub fn wins(&self, length: usize, win_length: usize, first_player: bool) -> bool {
+        match self {
+            WinRule::Exact => length == win_length,
+            WinRule::Renju if first_player => length == win_length,
+            _ => length >= win_length,
+        }
+    }
+}
+
+impl fmt::Display for WinRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for WinRule {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "freestyle" => Ok(WinRule::Freestyle),
+            "exact" => Ok(WinRule::Exact),
+            "renju" => Ok(WinRule::Renju),
+            _ => Err(format!("Unknown win rule: {}", name)),
+        }
+    }
+}
+
+/// Dimensions and winning sequence length of a game board.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoardSize {
+    pub rows: usize,
+    pub columns: usize,
+    pub win_length: usize,
+}
+
+impl Default for BoardSize {
+    // the classic Connect-5 board
+    fn default() -> Self {
+        BoardSize {
+            rows: 6,
+            columns: 9,
+            win_length: 5,
+        }
+    }
+}
+
+impl BoardSize {
+    pub fn new(rows: usize, columns: usize, win_length: usize) -> Self {
+        BoardSize {
+            rows,
+            columns,
+            win_length,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rows < 1 || self.rows > MAX_DIMENSION {
+            return Err(format!("Rows must be between 1 and {}", MAX_DIMENSION));
+        }
+        if self.columns < 1 || self.columns > MAX_DIMENSION {
+            return Err(format!("Columns must be between 1 and {}", MAX_DIMENSION));
+        }
+        if self.win_length < 2 || self.win_length > self.rows.max(self.columns) {
+            return Err(format!(
+                "Win length must be between 2 and {}",
+                self.rows.max(self.columns)
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.rows * self.columns
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    size: BoardSize,
+    cells: Vec<Vec<char>>,
+    win_rule: WinRule,
+    /// color of the player who moved first, set with the win rule
+    first_color: Option<char>,
+}
+
+impl Board {
+    pub fn new(size: BoardSize) -> Self {
+        Board {
+            size,
+            cells: vec![vec![EMPTY_CELL; size.columns]; size.rows],
+            win_rule: WinRule::default(),
+            first_color: None,
+        }
+    }
+
+    /// The board played with the win rule, `first_color` being the color of the player who
+    /// moved first, the one the renju restrictions apply to. Boards are freestyle by default.
+    pub fn with_win_rule(self, win_rule: WinRule, first_color: char) -> Self {
+        Board {
+            win_rule,
+            first_color: Some(first_color),
+            ..self
+        }
+    }
+
+    /// Parses the row-major board string stored in the `game_state` table.
+    pub fn parse(board_str: &str, size: BoardSize) -> Result<Self, String> {
+        if board_str.chars().count() != size.cell_count() {
+            return Err(format!(
+                "Board string length {} does not match a {}x{} board",
+                board_str.chars().count(),
+                size.rows,
+                size.columns
+            ));
+        }
+        Ok(Board {
+            cells: utils::str_to_arr(board_str, size.rows, size.columns),
+            ..Board::new(size)
+        })
+    }
+
+    pub fn from_cells(cells: Vec<Vec<char>>, win_length: usize) -> Self {
+        let size = BoardSize::new(cells.len(), cells[0].len(), win_length);
+        Board {
+            cells,
+            ..Board::new(size)
+        }
+    }
+
+    pub fn size(&self) -> BoardSize {
+        self.size
+    }
+
+    pub fn rows(&self) -> usize {
+        self.size.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.size.columns
+    }
+
+    pub fn win_length(&self) -> usize {
+        self.size.win_length
+    }
+
+    pub fn win_rule(&self) -> WinRule {
+        self.win_rule
+    }
+
+    pub fn first_color(&self) -> Option<char> {
+        self.first_color
+    }
+
+    pub fn cells(&self) -> &[Vec<char>] {
+        &self.cells
+    }
+
+    pub fn cell(&self, row: usize, column: usize) -> char {
+        self.cells[row][column]
+    }
+
+    /// Drops a piece into the (zero based) column and returns the row it landed on.
+    pub fn drop_piece(&mut self, column: usize, color: char) -> Result<usize, String> {
+        let row = (0..self.rows())
+            .rev()
+            .find(|row| self.cells[*row][column] == EMPTY_CELL);
+        match row {
+            Some(row_num) => {
+                self.cells[row_num][column] = color;
+                Ok(row_num)
+            }
+            None => Err("This column is full. Please, try another move".to_owned()),
+        }
+    }
+
+    /// Removes the bottom piece of the (zero based) column, the pieces above it fall one row.
+    /// Returns the removed piece.
+    pub fn pop_piece(&mut self, column: usize) -> Result<char, String> {
+        let bottom = self.rows() - 1;
+        let piece = self.cells[bottom][column];
+        if piece == EMPTY_CELL {
+            return Err("This column is empty".to_owned());
+        }
+        for row in (1..=bottom).rev() {
+            self.cells[row][column] = self.cells[row - 1][column];
+        }
+        self.cells[0][column] = EMPTY_CELL;
+        Ok(piece)
+    }
+
+    /// Places a piece on the empty (zero based) cell, without gravity.
+    pub fn place_piece(&mut self, row: usize, column: usize, color: char) -> Result<(), String> {
+        if self.cells[row][column] != EMPTY_CELL {
+            return Err("This cell is already taken. Please, try another move".to_owned());
+        }
+        self.cells[row][column] = color;
+        Ok(())
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.cells.iter().flatten().all(|cell| *cell != EMPTY_CELL)
+    }
+
+    /// Returns the color of the first winning sequence found on the board.
+    pub fn winner(&self) -> Option<char> {
+        self.winning_line().map(|line| line.color)
+    }
+
+    /// Looks for a winning sequence through the piece at (row, column) only, scanning outward from
+    /// it in each direction. Enough to detect a win right after that piece was placed.
+    pub fn winning_line_at(&self, row: usize, column: usize) -> Option<WinningLine> {
+        let color = self.cells[row][column];
+        if color == EMPTY_CELL {
+            return None;
+        }
+        for (direction, (d_row, d_col)) in DIRECTIONS.iter() {
+            let cells = self.run_at((row, column), *d_row, *d_col);
+            if self.is_winning_run(cells.len(), color) {
+                return Some(WinningLine {
+                    color,
+                    direction: *direction,
+                    cells,
+                });
+            }
+        }
+        None
+    }
+
+    /// Under `WinRule::Renju`, why the first player's piece at (row, column) was a forbidden
+    /// move, `None` if it wasn't. A move completing exactly `win_length` pieces is never
+    /// forbidden. Gravity is ignored when looking for the fours and threes.
+    pub fn forbidden_move(&self, row: usize, column: usize) -> Option<&'static str> {
+        let color = self.cells[row][column];
+        if self.win_rule != WinRule::Renju || self.first_color != Some(color) {
+            return None;
+        }
+        let win_length = self.win_length();
+        let runs = DIRECTIONS
+            .iter()
+            .map(|(_, (d_row, d_col))| self.run_at((row, column), *d_row, *d_col).len())
+            .collect::<Vec<_>>();
+        if runs.contains(&win_length) {
+            return None;
+        }
+        if runs.iter().any(|length| *length > win_length) {
+            return Some("an overline");
+        }
+        let count = |shape: fn(&Board, Cell, isize, isize) -> bool| {
+            DIRECTIONS
+                .iter()
+                .filter(|(_, (d_row, d_col))| shape(self, (row, column), *d_row, *d_col))
+                .count()
+        };
+        if count(Board::makes_four) >= 2 {
+            return Some("two fours");
+        }
+        if count(Board::makes_open_three) >= 2 {
+            return Some("two open threes");
+        }
+        None
+    }
+
+    // the run of equal pieces through `cell` in the given direction, from one end to the other
+    fn run_at(&self, cell: Cell, d_row: isize, d_col: isize) -> Vec<Cell> {
+        let color = self.cells[cell.0][cell.1];
+        let mut start = cell;
+        while let Some(prev) = self.step(start, -d_row, -d_col) {
+            if self.cells[prev.0][prev.1] != color {
+                break;
+            }
+            start = prev;
+        }
+        let mut cells = vec![start];
+        let mut next = self.step(start, d_row, d_col);
+        while let Some(cell) = next {
+            if self.cells[cell.0][cell.1] != color {
+                break;
+            }
+            cells.push(cell);
+            next = self.step(cell, d_row, d_col);
+        }
+        cells
+    }
+
+    // empty cells of the line through `cell` that one more piece could join to its run
+    fn empty_cells_near(&self, cell: Cell, d_row: isize, d_col: isize) -> Vec<Cell> {
+        let mut cells = vec![];
+        for sign in [-1, 1].iter() {
+            let mut next = self.step(cell, sign * d_row, sign * d_col);
+            for _ in 1..self.win_length() {
+                let current = match next {
+                    Some(current) => current,
+                    None => break,
+                };
+                if self.cells[current.0][current.1] == EMPTY_CELL {
+                    cells.push(current);
+                }
+                next = self.step(current, sign * d_row, sign * d_col);
+            }
+        }
+        cells
+    }
+
+    // one more piece in the direction makes exactly `win_length` through `cell`
+    fn makes_four(&self, cell: Cell, d_row: isize, d_col: isize) -> bool {
+        let color = self.cells[cell.0][cell.1];
+        self.empty_cells_near(cell, d_row, d_col)
+            .into_iter()
+            .any(|empty| {
+                let mut board = self.clone();
+                board.cells[empty.0][empty.1] = color;
+                board.run_at(cell, d_row, d_col).len() == self.win_length()
+            })
+    }
+
+    // one more piece in the direction joins a run of `win_length - 1` through `cell` that can be
+    // completed at both ends
+    fn makes_open_three(&self, cell: Cell, d_row: isize, d_col: isize) -> bool {
+        let color = self.cells[cell.0][cell.1];
+        self.empty_cells_near(cell, d_row, d_col)
+            .into_iter()
+            .any(|empty| {
+                let mut board = self.clone();
+                board.cells[empty.0][empty.1] = color;
+                let run = board.run_at(cell, d_row, d_col);
+                if !run.contains(&empty) || run.len() + 1 != self.win_length() {
+                    return false;
+                }
+                let ends = [
+                    board.step(run[0], -d_row, -d_col),
+                    board.step(run[run.len() - 1], d_row, d_col),
+                ];
+                ends.iter().all(|end| match end {
+                    Some(end) if board.cells[end.0][end.1] == EMPTY_CELL => {
+                        let mut four = board.clone();
+                        four.cells[end.0][end.1] = color;
+                        four.run_at(cell, d_row, d_col).len() == self.win_length()
+                    }
+                    _ => false,
+                })
+            })
+    }
+
+    fn is_winning_run(&self, length: usize, color: char) -> bool {
+        let first_player = self.first_color == Some(color);
+        self.win_rule.wins(length, self.win_length(), first_player)
+    }
+
+    // the neighbour of `cell` in the given direction, None at the board edge
+    fn step(&self, cell: Cell, d_row: isize, d_col: isize) -> Option<Cell> {
+        let row = cell.0 as isize + d_row;
+        let col = cell.1 as isize + d_col;
+        if row < 0 || col < 0 || row >= self.rows() as isize || col >= self.columns() as isize {
+            None
+        } else {
+            Some((row as usize, col as usize))
+        }
+    }
+
+    /// Scans every row, column and diagonal for a winning sequence, see `WinRule`.
+    pub fn winning_line(&self) -> Option<WinningLine> {
+        self.find_winning_line(|color| color != EMPTY_CELL)
+    }
+
+    /// Like `winning_line`, for the pieces of `color` only.
+    pub fn winning_line_of(&self, color: char) -> Option<WinningLine> {
+        self.find_winning_line(|piece| piece == color)
+    }
+
+    fn find_winning_line(&self, counts: impl Fn(char) -> bool) -> Option<WinningLine> {
+        let (h, w) = (self.rows(), self.columns());
+        let lines = [
+            (Direction::Horizontal, get_rows(h, w)),
+            (Direction::Vertical, get_columns(h, w)),
+            (Direction::DiagonalDown, get_diagonals_left(h, w)),
+            (Direction::DiagonalUp, get_diagonals_right(h, w)),
+        ];
+        for (direction, direction_lines) in lines.iter() {
+            for line in direction_lines {
+                for (color, group) in &line.iter().group_by(|(row, col)| self.cells[*row][*col]) {
+                    let cells = group.copied().collect::<Vec<Cell>>();
+                    if counts(color) && self.is_winning_run(cells.len(), color) {
+                        return Some(WinningLine {
+                            color,
+                            direction: *direction,
+                            cells,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", utils::arr_to_str(&self.cells))
+    }
+}
+
+/// (row, column) of a board cell, row 0 being the top row
+pub type Cell = (usize, usize);
+
+// the step from a cell to the next one of a line, in every direction
+const DIRECTIONS: [(Direction, (isize, isize)); 4] = [
+    (Direction::Horizontal, (0, 1)),
+    (Direction::Vertical, (1, 0)),
+    (Direction::DiagonalDown, (1, 1)),
+    (Direction::DiagonalUp, (-1, 1)),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+    /// from the top left to the bottom right
+    DiagonalDown,
+    /// from the bottom left to the top right
+    DiagonalUp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinningLine {
+    pub color: char,
+    pub direction: Direction,
+    pub cells: Vec<Cell>,
+}
+
+impl WinningLine {
+    /// Comma separated indices of the winning cells in the board string.
+    pub fn cell_indices(&self, columns: usize) -> String {
+        self.cells
+            .iter()
+            .map(|(row, col)| (row * columns + col).to_string())
+            .join(",")
+    }
+}
+
+fn get_rows(h: usize, w: usize) -> Vec<Vec<Cell>> {
+    (0..h)
+        .map(|row| (0..w).map(|col| (row, col)).collect())
+        .collect()
+}
+
+fn get_columns(h: usize, w: usize) -> Vec<Vec<Cell>> {
+    (0..w)
+        .map(|col| (0..h).map(|row| (row, col)).collect())
+        .collect()
+}
+
+fn get_diagonals_left(h: usize, w: usize) -> Vec<Vec<Cell>> {
+    let (h, w) = (h as isize, w as isize);
+    let mut diags: Vec<Vec<Cell>> = Vec::new();
+    for p in 0..(h + w - 1) {
+        let mut d: Vec<Cell> = Vec::new();
+        let lower_bound = (p - h + 1).max(0);
+        let higher_bound = (p + 1).min(w);
+        for col in lower_bound..higher_bound {
+            let row = h - p + col - 1;
+            d.push((row as usize, col as usize))
+        }
+        diags.push(d);
+    }
+    diags
+}
+
+fn get_diagonals_right(h: usize, w: usize) -> Vec<Vec<Cell>> {
+    let (h, w) = (h as isize, w as isize);
+    let mut diags: Vec<Vec<Cell>> = Vec::new();
+    for p in 0..(h + w - 1) {
+        let mut d: Vec<Cell> = Vec::new();
+        let lower_bound = (p - h + 1).max(0);
+        let higher_bound = (p + 1).min(w);
+        for col in lower_bound..higher_bound {
+            let row = p - col;
+            d.push((row as usize, col as usize))
+        }
+        diags.push(d);
+    }
+    diags
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{
+        get_diagonals_left, get_diagonals_right, Board, BoardSize, Cell, Direction, WinRule,
+    };
+
+    fn cell_values(board: &[Vec<char>], lines: Vec<Vec<Cell>>) -> Vec<Vec<char>> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|(row, col)| board[*row][*col]).collect())
+            .collect()
+    }
+
+    #[test]
+    pub fn test_get_diagonals_left() {
+        let board = vec![
+            vec!['1', '2', '3', '4'],
+            vec!['A', 'B', 'C', '4'],
+            vec!['W', 'X', 'Y', 'Z'],
+            vec!['9', '8', '7', '6'],
+        ];
+
+        let target = vec![
+            vec!['9'],
+            vec!['W', '8'],
+            vec!['A', 'X', '7'],
+            vec!['1', 'B', 'Y', '6'],
+            vec!['2', 'C', 'Z'],
+            vec!['3', '4'],
+            vec!['4'],
+        ];
+
+        let res = cell_values(&board, get_diagonals_left(4, 4));
+
+        assert_eq!(res, target)
+    }
+
+    #[test]
+    pub fn test_get_diagonals_right() {
+        let board = vec![
+            vec!['1', '2', '3', '4'],
+            vec!['A', 'B', 'C', '4'],
+            vec!['W', 'X', 'Y', 'Z'],
+            vec!['9', '8', '7', '6'],
+        ];
+
+        let target = vec![
+            vec!['1'],
+            vec!['A', '2'],
+            vec!['W', 'B', '3'],
+            vec!['9', 'X', 'C', '4'],
+            vec!['8', 'Y', '4'],
+            vec!['7', 'Z'],
+            vec!['6'],
+        ];
+
+        let res = cell_values(&board, get_diagonals_right(4, 4));
+
+        assert_eq!(res, target)
+    }
+
+    #[test]
+    pub fn test_parse_and_display() {
+        let s = "-".repeat(41) + "X";
+        let board = Board::parse(&s, BoardSize::new(6, 7, 4)).unwrap();
+        assert_eq!(board.rows(), 6);
+        assert_eq!(board.columns(), 7);
+        assert_eq!(board.cell(5, 6), 'X');
+        assert_eq!(board.to_string(), s);
+
+        assert!(Board::parse(&s, BoardSize::default()).is_err());
+    }
+
+    #[test]
+    pub fn test_drop_piece() {
+        let mut board = Board::new(BoardSize::new(2, 3, 2));
+        assert_eq!(board.drop_piece(1, 'X'), Ok(1));
+        assert_eq!(board.drop_piece(1, 'O'), Ok(0));
+        assert_eq!(
+            board.drop_piece(1, 'X'),
+            Err("This column is full. Please, try another move".to_string())
+        );
+        assert_eq!(board.to_string(), "-O--X-");
+    }
+
+    #[test]
+    pub fn test_pop_piece() {
+        let mut board = Board::parse("-O--X-", BoardSize::new(2, 3, 2)).unwrap();
+        assert_eq!(board.pop_piece(1), Ok('X'));
+        assert_eq!(board.to_string(), "----O-");
+        assert_eq!(board.pop_piece(0), Err("This column is empty".to_string()));
+    }
+
+    #[test]
+    pub fn test_place_piece() {
+        let mut board = Board::new(BoardSize::new(2, 3, 2));
+        board.place_piece(0, 1, 'X').unwrap();
+        assert_eq!(board.to_string(), "-X----");
+        assert!(board.place_piece(0, 1, 'O').is_err());
+        // a full top row is not a full board without gravity
+        board.place_piece(0, 0, 'O').unwrap();
+        board.place_piece(0, 2, 'O').unwrap();
+        assert!(!board.is_full());
+    }
+
+    #[test]
+    pub fn test_winner_connect_4() {
+        let mut board = Board::new(BoardSize::new(6, 7, 4));
+        for column in 0..3 {
+            board.drop_piece(column, 'X').unwrap();
+            assert_eq!(board.winner(), None);
+        }
+        board.drop_piece(3, 'X').unwrap();
+        assert_eq!(board.winner(), Some('X'));
+    }
+
+    #[test]
+    pub fn test_winning_line() {
+        let board = Board::from_cells(
+            vec![
+                vec!['-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', 'O', '-', '-', '-'],
+                vec!['-', '-', 'O', 'X', '-', '-', '-'],
+                vec!['-', 'O', 'X', 'X', '-', '-', '-'],
+                vec!['O', 'X', 'X', 'X', '-', '-', '-'],
+            ],
+            4,
+        );
+        let line = board.winning_line().unwrap();
+        assert_eq!(line.color, 'O');
+        assert_eq!(line.direction, Direction::DiagonalUp);
+        assert_eq!(line.cells, vec![(5, 0), (4, 1), (3, 2), (2, 3)]);
+        assert_eq!(line.cell_indices(board.columns()), "35,29,23,17");
+
+        let board = Board::parse("-X---X---X---X--", BoardSize::new(4, 4, 4)).unwrap();
+        let line = board.winning_line().unwrap();
+        assert_eq!(line.color, 'X');
+        assert_eq!(line.direction, Direction::Vertical);
+        assert_eq!(line.cells, vec![(0, 1), (1, 1), (2, 1), (3, 1)]);
+
+        assert_eq!(Board::new(BoardSize::default()).winning_line(), None);
+        assert_eq!(board.winning_line_of('O'), None);
+        assert_eq!(board.winning_line_of('X').unwrap().cells.len(), 4);
+    }
+
+    #[test]
+    pub fn test_winning_line_at() {
+        let board = Board::from_cells(
+            vec![
+                vec!['-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', 'O', '-', '-', '-'],
+                vec!['-', '-', 'O', 'X', '-', '-', '-'],
+                vec!['-', 'O', 'X', 'X', '-', '-', '-'],
+                vec!['O', 'X', 'X', 'X', 'X', '-', '-'],
+            ],
+            4,
+        );
+        // the same line is found from any of its cells
+        for (row, col) in [(5, 0), (4, 1), (3, 2), (2, 3)].iter() {
+            let line = board.winning_line_at(*row, *col).unwrap();
+            assert_eq!(line.color, 'O');
+            assert_eq!(line.direction, Direction::DiagonalUp);
+            assert_eq!(line.cells, vec![(5, 0), (4, 1), (3, 2), (2, 3)]);
+        }
+        let line = board.winning_line_at(5, 3).unwrap();
+        assert_eq!(line.direction, Direction::Horizontal);
+        assert_eq!(line.cells, vec![(5, 1), (5, 2), (5, 3), (5, 4)]);
+
+        assert_eq!(board.winning_line_at(3, 3), None);
+        assert_eq!(board.winning_line_at(0, 0), None);
+    }
+
+    #[test]
+    pub fn test_win_rules() {
+        let size = BoardSize::new(1, 9, 3);
+        // board, color of the first player, winners of the freestyle, exact and renju rules
+        let cases = [
+            ("XXXXOOO--", 'X', [Some('X'), Some('O'), Some('O')]),
+            ("OOOOXXX--", 'O', [Some('O'), Some('X'), Some('X')]),
+            ("XXXX-OO--", 'X', [Some('X'), None, None]),
+            // as many pieces of both colors: only the first color tells whose overline wins
+            ("XXXX-OOOO", 'X', [Some('X'), None, Some('O')]),
+            ("XXXX-OOOO", 'O', [Some('X'), None, Some('X')]),
+        ];
+        let win_rules = [WinRule::Freestyle, WinRule::Exact, WinRule::Renju];
+        for (board_str, first_color, winners) in cases.iter() {
+            for (win_rule, winner) in win_rules.iter().zip(winners) {
+                let board = Board::parse(board_str, size)
+                    .unwrap()
+                    .with_win_rule(*win_rule, *first_color);
+                assert_eq!(board.winner(), *winner, "{} {}", board_str, win_rule);
+                let line_at = board.winning_line_at(0, 0).map(|line| line.color);
+                assert_eq!(line_at, winner.filter(|color| *color == board.cell(0, 0)));
+            }
+        }
+        assert_eq!("exact".parse::<WinRule>(), Ok(WinRule::Exact));
+        assert_eq!(WinRule::default().to_string(), "freestyle");
+        assert!("sudden".parse::<WinRule>().is_err());
+    }
+
+    #[test]
+    pub fn test_forbidden_move() {
+        // the first player X plays at (4, 4), O has one piece less, away from the lines
+        let forbidden = |x: &[Cell], win_rule: WinRule| {
+            let mut board = Board::new(BoardSize::new(9, 9, 5)).with_win_rule(win_rule, 'X');
+            let o = [(8, 0), (8, 2), (8, 4), (8, 6), (8, 8), (0, 8), (0, 6)];
+            for (row, col) in x.iter().chain(&o[..x.len()]) {
+                let color = if x.contains(&(*row, *col)) { 'X' } else { 'O' };
+                board.place_piece(*row, *col, color).unwrap();
+            }
+            board.place_piece(4, 4, 'X').unwrap();
+            board.forbidden_move(4, 4)
+        };
+        let overline = [(4, 0), (4, 1), (4, 2), (4, 3), (4, 5)];
+        let five = [(4, 0), (4, 1), (4, 2), (4, 3), (2, 2)];
+        let two_fours = [(4, 1), (4, 2), (4, 3), (1, 4), (2, 4), (3, 4)];
+        let two_threes = [(4, 2), (4, 3), (2, 4), (3, 4)];
+        let four_and_three = [(4, 1), (4, 2), (4, 3), (2, 4), (3, 4)];
+        assert_eq!(forbidden(&overline, WinRule::Renju), Some("an overline"));
+        assert_eq!(forbidden(&five, WinRule::Renju), None);
+        assert_eq!(forbidden(&two_fours, WinRule::Renju), Some("two fours"));
+        assert_eq!(
+            forbidden(&two_threes, WinRule::Renju),
+            Some("two open threes")
+        );
+        assert_eq!(forbidden(&four_and_three, WinRule::Renju), None);
+        assert_eq!(forbidden(&two_fours, WinRule::Exact), None);
+
+        // a three blocked at one end is not open
+        let board = Board::parse("-OXX-----", BoardSize::new(1, 9, 4)).unwrap();
+        assert!(!board.makes_open_three((0, 2), 0, 1));
+        // the second player has no restrictions
+        let board = Board::parse("XX-XX-OOOO", BoardSize::new(1, 10, 3)).unwrap();
+        let board = board.with_win_rule(WinRule::Renju, 'X');
+        assert_eq!(board.forbidden_move(0, 9), None);
+        assert_eq!(board.winner(), Some('O'));
+        let board = board.with_win_rule(WinRule::Renju, 'O');
+        assert_eq!(board.forbidden_move(0, 9), Some("an overline"));
+        assert_eq!(board.winner(), None);
+    }
+
+    #[test]
+    pub fn test_winning_line_at_matches_full_scan() {
+        // a few hundred pseudo random games on a Connect-4 board
+        let mut seed: u64 = 42;
+        for _ in 0..300 {
+            let mut board = Board::new(BoardSize::new(6, 7, 4));
+            for ply in 0..42 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let column = (seed >> 33) as usize % 7;
+                let color = if ply % 2 == 0 { 'X' } else { 'O' };
+                if let Ok(row) = board.drop_piece(column, color) {
+                    let incremental = board.winning_line_at(row, column);
+                    assert_eq!(incremental.is_some(), board.winning_line().is_some());
+                    if incremental.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_is_full() {
+        let mut board = Board::new(BoardSize::new(1, 2, 2));
+        board.drop_piece(0, 'X').unwrap();
+        assert!(!board.is_full());
+        board.drop_piece(1, 'O').unwrap();
+        assert!(board.is_full());
+    }
+
+    #[test]
+    pub fn test_validate_size() {
+        assert!(BoardSize::default().validate().is_ok());
+        assert!(BoardSize::new(6, 7, 4).validate().is_ok());
+        assert!(BoardSize::new(0, 7, 4).validate().is_err());
+        assert!(BoardSize::new(6, 7, 8).validate().is_err());
+        assert!(BoardSize::new(6, 7, 1).validate().is_err());
+    }
+}
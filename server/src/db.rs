@@ -9,8 +9,9 @@ use std::result::Result;
 use std::time::Duration;
 use uuid::Uuid;
 
+use crate::board::{Board, BoardSize, WinRule};
 pub use crate::models;
-use crate::models::{GameState, NewGameState, User};
+use crate::models::{GameState, Move, NewGameState, NewMove, User};
 pub use crate::schema;
 pub use crate::utils;
 
@@ -87,15 +88,22 @@ pub fn create_new_user(name: &str, color: &str, conn: &SqliteConnection) -> Resu
 }
 
 #[cfg_attr(test, mockable)]
-pub fn create_new_session(user: &Uuid, conn: &SqliteConnection) -> Result<Uuid, String> {
+pub fn create_new_session(
+    user: &Uuid,
+    board_size: &BoardSize,
+    conn: &SqliteConnection,
+) -> Result<Uuid, String> {
     use super::schema::game_state::dsl::*;
 
     let new_session_id = Uuid::new_v4();
 
     let new_game = NewGameState {
         id: new_session_id.to_string(),
-        board: Some("------------------------------------------------------".to_string()), //TODO generate the string given board h&w
+        board: Some(Board::new(*board_size).to_string()),
         user_1: Some(user.to_string()),
+        board_rows: board_size.rows as i32,
+        board_columns: board_size.columns as i32,
+        win_length: board_size.win_length as i32,
     };
 
     let result = diesel::insert_into(game_state)
@@ -125,6 +133,8 @@ pub fn find_existing_game_session(conn: &SqliteConnection) -> Option<models::Gam
     }
 }
 
+/// Seats `user2_id` as `user_2`. Nothing is updated if the seat is taken or if `user2_id` is
+/// already `user_1` of the game.
 #[cfg_attr(test, mockable)]
 pub fn join_game_session(
     session_id: &Uuid,
@@ -135,6 +145,28 @@ pub fn join_game_session(
     diesel::update(game_state)
         .set(user_2.eq(user2_id.to_string()))
         .filter(id.eq(session_id.to_string()))
+        .filter(user_2.is_null())
+        .filter(user_1.ne(user2_id.to_string()))
+        .execute(conn)
+}
+
+/// Seats the computer player as `user_2`.
+#[cfg_attr(test, mockable)]
+pub fn seat_ai_player(
+    session_id: &Uuid,
+    ai_user_id: &Uuid,
+    engine: &str,
+    level: &str,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .set((
+            user_2.eq(ai_user_id.to_string()),
+            ai_engine.eq(engine),
+            ai_level.eq(level),
+        ))
+        .filter(id.eq(session_id.to_string()))
         .execute(conn)
 }
 
@@ -158,6 +190,7 @@ pub fn update_game_state(
     user_id: &Uuid,
     board_str: &str,
     is_winner: bool,
+    is_draw: bool,
     game_over: bool,
     conn: &SqliteConnection,
 ) -> QueryResult<usize> {
@@ -168,11 +201,169 @@ pub fn update_game_state(
             last_user_id.eq(user_id.to_string()),
             board.eq(board_str),
             winner.eq(is_winner),
+            draw.eq(is_draw),
             ended.eq(game_over),
         ))
         .execute(conn)
 }
 
+/// Increments the version of the game if it is still `expected_version`. Returns the number of
+/// updated rows, 0 if the game has changed in the meantime.
+#[cfg_attr(test, mockable)]
+pub fn bump_version(
+    session_id: &Uuid,
+    expected_version: i32,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .filter(version.eq(expected_version))
+        .set(version.eq(version + 1))
+        .execute(conn)
+}
+
+#[cfg_attr(test, mockable)]
+pub fn update_rated(
+    session_id: &Uuid,
+    is_rated: bool,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set(rated.eq(is_rated))
+        .execute(conn)
+}
+
+/// Sets the rule set of a new game and the board it starts with.
+#[cfg_attr(test, mockable)]
+pub fn update_rules(
+    session_id: &Uuid,
+    rule_set: &str,
+    initial_board: &str,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set((rules.eq(rule_set), board.eq(initial_board)))
+        .execute(conn)
+}
+
+/// Sets the win rule of a new game, see `board::WinRule`.
+#[cfg_attr(test, mockable)]
+pub fn update_win_rule(
+    session_id: &Uuid,
+    rule: WinRule,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set(win_rule.eq(rule.name()))
+        .execute(conn)
+}
+
+/// Sets or, with `None`, clears the pending takeback request of the game.
+#[cfg_attr(test, mockable)]
+pub fn update_takeback_request(
+    session_id: &Uuid,
+    user_id: Option<&Uuid>,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set(takeback_requested_by.eq(user_id.map(|user_id| user_id.to_string())))
+        .execute(conn)
+}
+
+#[cfg_attr(test, mockable)]
+pub fn update_winning_line(
+    session_id: &Uuid,
+    color: char,
+    cells: &str,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set((winner_color.eq(color.to_string()), winning_cells.eq(cells)))
+        .execute(conn)
+}
+
+#[cfg_attr(test, mockable)]
+pub fn update_position_key(
+    session_id: &Uuid,
+    key: &str,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set(position_key.eq(key))
+        .execute(conn)
+}
+
+#[cfg_attr(test, mockable)]
+pub fn update_move_list(
+    session_id: &Uuid,
+    moves: &str,
+    conn: &SqliteConnection,
+) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(session_id.to_string()))
+        .set(move_list.eq(moves))
+        .execute(conn)
+}
+
+/// Overwrites the columns of the game that are derived from its moves, see `projection`, and
+/// increments its version.
+#[cfg_attr(test, mockable)]
+pub fn store_projection(projection: &GameState, conn: &SqliteConnection) -> QueryResult<usize> {
+    use super::schema::game_state::dsl::*;
+    diesel::update(game_state)
+        .filter(id.eq(&projection.id))
+        .set((
+            board.eq(&projection.board),
+            last_user_id.eq(&projection.last_user_id),
+            winner.eq(projection.winner),
+            draw.eq(projection.draw),
+            ended.eq(projection.ended),
+            winner_color.eq(&projection.winner_color),
+            winning_cells.eq(&projection.winning_cells),
+            position_key.eq(&projection.position_key),
+            move_list.eq(&projection.move_list),
+            version.eq(version + 1),
+        ))
+        .execute(conn)
+}
+
+pub fn get_game_ids(conn: &SqliteConnection) -> QueryResult<Vec<String>> {
+    use super::schema::game_state::dsl::*;
+    game_state.select(id).order(id.asc()).load::<String>(conn)
+}
+
+/// Games currently in the position with the given key.
+#[cfg_attr(test, mockable)]
+pub fn find_games_by_position_key(
+    key: &str,
+    conn: &SqliteConnection,
+) -> QueryResult<Vec<models::GameState>> {
+    use super::schema::game_state::dsl::*;
+    game_state
+        .filter(position_key.eq(key))
+        .load::<GameState>(conn)
+}
+
+#[cfg_attr(test, mockable)]
+pub fn get_user(user_id: &Uuid, conn: &SqliteConnection) -> QueryResult<User> {
+    use super::schema::user::dsl::*;
+    user.filter(id.eq(user_id.to_string())).first::<User>(conn)
+}
+
 #[cfg_attr(test, mockable)]
 pub fn get_user_color(user_id: &Uuid, conn: &SqliteConnection) -> QueryResult<char> {
     use super::schema::user::dsl::*;
@@ -194,17 +385,54 @@ pub fn get_game_state(
 ) -> QueryResult<models::GameState> {
     use super::schema::game_state::dsl::*;
 
-    let res = game_state
+    game_state
         .filter(id.eq(session_id.to_string()))
-        .limit(1)
-        .load::<GameState>(conn)
-        .expect("Error loading game state");
-    Ok(res[0].clone())
+        .first::<GameState>(conn)
+}
+
+#[cfg_attr(test, mockable)]
+pub fn insert_move(new_move: &NewMove, conn: &SqliteConnection) -> QueryResult<usize> {
+    use super::schema::moves::dsl::*;
+    diesel::insert_into(moves).values(new_move).execute(conn)
+}
+
+/// Moves of the game, in the order they were played.
+#[cfg_attr(test, mockable)]
+pub fn get_moves(session_id: &Uuid, conn: &SqliteConnection) -> QueryResult<Vec<Move>> {
+    use super::schema::moves::dsl::*;
+    moves
+        .filter(game_id.eq(session_id.to_string()))
+        .order(ply.asc())
+        .load::<Move>(conn)
+}
+
+/// Deletes the last move of the game. The game state has to be rebuilt afterwards, see
+/// `projection::rebuild_game_state`.
+#[cfg_attr(test, mockable)]
+pub fn delete_last_move(session_id: &Uuid, conn: &SqliteConnection) -> QueryResult<usize> {
+    use super::schema::moves::dsl::*;
+    let last_ply = moves
+        .select(diesel::dsl::max(ply))
+        .filter(game_id.eq(session_id.to_string()))
+        .first::<Option<i32>>(conn)?;
+    match last_ply {
+        Some(last_ply) => diesel::delete(
+            moves
+                .filter(game_id.eq(session_id.to_string()))
+                .filter(ply.eq(last_ply)),
+        )
+        .execute(conn),
+        None => Ok(0),
+    }
 }
 
 pub fn clean_db(conn: &SqliteConnection) {
     use super::schema::game_state::dsl::*;
     // use super::schema::user::dsl::*;
+    // moves reference game_state
+    diesel::delete(super::schema::moves::table)
+        .execute(conn)
+        .unwrap();
     diesel::delete(game_state).execute(conn).unwrap();
     // diesel::delete(user)
     //     .execute(&conn)
@@ -213,10 +441,15 @@ pub fn clean_db(conn: &SqliteConnection) {
 
 #[cfg(test)]
 pub mod tests {
+    use crate::board::{BoardSize, WinRule};
     use crate::db::{
-        create_conn_pool, create_new_session, create_new_user, find_existing_game_session,
-        get_board, get_game_state, get_user_color, join_game_session, update_game_state,
+        bump_version, create_conn_pool, create_new_session, create_new_user, delete_last_move,
+        find_existing_game_session, find_games_by_position_key, get_board, get_game_ids,
+        get_game_state, get_moves, get_user, get_user_color, insert_move, join_game_session,
+        seat_ai_player, store_projection, update_game_state, update_move_list, update_position_key,
+        update_rated, update_rules, update_takeback_request, update_win_rule, update_winning_line,
     };
+    use crate::models::NewMove;
     use std::ops::Deref;
     use uuid::Uuid;
 
@@ -224,7 +457,8 @@ pub mod tests {
     pub fn test_get_board() {
         let target_board = "------------------------------------------------------".to_owned();
         let conn = create_conn_pool().get().unwrap();
-        let session_id = create_new_session(&Uuid::new_v4(), conn.deref()).unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
         let board = get_board(&session_id, conn.deref()).unwrap();
         // println!("{:?}", board.unwrap());
         assert_eq!(board, target_board);
@@ -234,7 +468,8 @@ pub mod tests {
     pub fn test_find_existing_game_session() {
         //clean_db();
         let conn = create_conn_pool().get().unwrap();
-        let session_id = create_new_session(&Uuid::new_v4(), conn.deref()).unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
         let gs = find_existing_game_session(conn.deref()).unwrap();
         assert_eq!(session_id.to_string(), gs.id)
     }
@@ -243,7 +478,7 @@ pub mod tests {
     pub fn test_create_new_session() {
         let user = Uuid::new_v4();
         let conn = create_conn_pool().get().unwrap();
-        let result = create_new_session(&user, conn.deref());
+        let result = create_new_session(&user, &BoardSize::default(), conn.deref());
         match result {
             Ok(session_id) => println!("created new session with id: {}", session_id),
             Err(error) => {
@@ -252,21 +487,65 @@ pub mod tests {
         }
     }
 
+    #[test]
+    pub fn test_create_new_session_board_size() {
+        let conn = create_conn_pool().get().unwrap();
+        let board_size = BoardSize::new(6, 7, 4);
+        let session_id = create_new_session(&Uuid::new_v4(), &board_size, conn.deref()).unwrap();
+        let gs = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(gs.board_size(), board_size);
+        assert_eq!(gs.board.as_deref().unwrap(), "-".repeat(42));
+
+        assert_eq!(gs.win_rule(), WinRule::Freestyle);
+        assert_eq!(
+            update_win_rule(&session_id, WinRule::Renju, conn.deref()).unwrap(),
+            1
+        );
+        let gs = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(gs.win_rule, "renju");
+        assert_eq!(gs.win_rule(), WinRule::Renju);
+        assert_eq!(gs.board_size(), board_size);
+    }
+
     #[test]
     pub fn test_join_game_session() {
         let user = Uuid::new_v4();
         let conn = create_conn_pool().get().unwrap();
-        let session_id = create_new_session(&Uuid::new_v4(), conn.deref()).unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
         let updated_records = join_game_session(&session_id, &user, conn.deref());
         assert_eq!(updated_records.unwrap(), 1);
         // let _board = get_board(&session_id, conn.deref());
     }
 
+    #[test]
+    pub fn test_join_game_session_taken() {
+        let user_1 = Uuid::new_v4();
+        let user_2 = Uuid::new_v4();
+        let conn = create_conn_pool().get().unwrap();
+        let session_id = create_new_session(&user_1, &BoardSize::default(), conn.deref()).unwrap();
+        assert_eq!(
+            join_game_session(&session_id, &user_1, conn.deref()).unwrap(),
+            0
+        );
+        assert_eq!(
+            join_game_session(&session_id, &user_2, conn.deref()).unwrap(),
+            1
+        );
+        let third = Uuid::new_v4();
+        assert_eq!(
+            join_game_session(&session_id, &third, conn.deref()).unwrap(),
+            0
+        );
+        let gs = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(gs.user_2, Some(user_2.to_string()));
+    }
+
     #[test]
     pub fn test_update_game_state() {
         let user_id = Uuid::new_v4();
         let conn = create_conn_pool().get().unwrap();
-        let session_id = create_new_session(&user_id, conn.deref()).unwrap();
+        let session_id = create_new_session(&user_id, &BoardSize::default(), conn.deref()).unwrap();
         let new_board = "-X----------------------------------------------------".to_owned();
         let updated_records = update_game_state(
             &session_id,
@@ -274,6 +553,7 @@ pub mod tests {
             &new_board,
             false,
             false,
+            false,
             conn.deref(),
         );
         assert_eq!(updated_records.unwrap(), 1);
@@ -288,6 +568,7 @@ pub mod tests {
             &new_board,
             false,
             false,
+            false,
             conn.deref(),
         );
         assert_eq!(updated_records.unwrap(), 1);
@@ -295,6 +576,221 @@ pub mod tests {
         assert_eq!(board.unwrap(), new_board);
     }
 
+    #[test]
+    pub fn test_update_winning_line() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        let updated_records = update_winning_line(&session_id, 'X', "1,2,3,4,5", conn.deref());
+        assert_eq!(updated_records.unwrap(), 1);
+        let gs = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(gs.winner_color.unwrap(), "X");
+        assert_eq!(gs.winning_cells.unwrap(), "1,2,3,4,5");
+    }
+
+    #[test]
+    pub fn test_seat_ai_player() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        let ai_user_id = Uuid::new_v4();
+        let updated_records =
+            seat_ai_player(&session_id, &ai_user_id, "minimax", "expert", conn.deref());
+        assert_eq!(updated_records.unwrap(), 1);
+        let gs = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(gs.user_2.unwrap(), ai_user_id.to_string());
+        assert_eq!(gs.ai_engine.unwrap(), "minimax");
+        assert_eq!(gs.ai_level.unwrap(), "expert");
+    }
+
+    #[test]
+    pub fn test_find_games_by_position_key() {
+        let conn = create_conn_pool().get().unwrap();
+        let key = Uuid::new_v4().to_string();
+        let session_1 =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        let session_2 =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        assert_eq!(
+            update_position_key(&session_1, &key, conn.deref()).unwrap(),
+            1
+        );
+        assert_eq!(
+            update_position_key(&session_2, &key, conn.deref()).unwrap(),
+            1
+        );
+
+        let mut ids = find_games_by_position_key(&key, conn.deref())
+            .unwrap()
+            .into_iter()
+            .map(|gs| gs.id)
+            .collect::<Vec<_>>();
+        ids.sort();
+        let mut expected = vec![session_1.to_string(), session_2.to_string()];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    pub fn test_bump_version() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        assert_eq!(
+            get_game_state(&session_id, conn.deref()).unwrap().version,
+            0
+        );
+        assert_eq!(bump_version(&session_id, 0, conn.deref()).unwrap(), 1);
+        assert_eq!(bump_version(&session_id, 0, conn.deref()).unwrap(), 0);
+        assert_eq!(bump_version(&session_id, 1, conn.deref()).unwrap(), 1);
+        assert_eq!(
+            get_game_state(&session_id, conn.deref()).unwrap().version,
+            2
+        );
+    }
+
+    #[test]
+    pub fn test_takeback_request_and_delete_last_move() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = Uuid::new_v4();
+        let session_id = create_new_session(&user_id, &BoardSize::default(), conn.deref()).unwrap();
+        update_takeback_request(&session_id, Some(&user_id), conn.deref()).unwrap();
+        let game_state = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(game_state.takeback_requested_by, Some(user_id.to_string()));
+        update_takeback_request(&session_id, None, conn.deref()).unwrap();
+        let game_state = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(game_state.takeback_requested_by, None);
+
+        assert_eq!(delete_last_move(&session_id, conn.deref()).unwrap(), 0);
+        for (ply, col) in [(1, 4), (2, 5)].iter() {
+            let new_move = NewMove {
+                game_id: session_id.to_string(),
+                ply: *ply,
+                user_id: user_id.to_string(),
+                col_num: *col,
+                row_num: 1,
+                created_at: None,
+                kind: "drop".to_string(),
+            };
+            insert_move(&new_move, conn.deref()).unwrap();
+        }
+        assert_eq!(delete_last_move(&session_id, conn.deref()).unwrap(), 1);
+        let moves = get_moves(&session_id, conn.deref()).unwrap();
+        assert_eq!(moves.iter().map(|m| m.col_num).collect::<Vec<_>>(), vec![4]);
+    }
+
+    #[test]
+    pub fn test_update_rated() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        assert!(!get_game_state(&session_id, conn.deref()).unwrap().rated);
+        assert_eq!(update_rated(&session_id, true, conn.deref()).unwrap(), 1);
+        assert!(get_game_state(&session_id, conn.deref()).unwrap().rated);
+    }
+
+    #[test]
+    pub fn test_update_rules() {
+        let conn = create_conn_pool().get().unwrap();
+        let size = BoardSize::new(2, 3, 2);
+        let session_id = create_new_session(&Uuid::new_v4(), &size, conn.deref()).unwrap();
+        assert_eq!(
+            get_game_state(&session_id, conn.deref()).unwrap().rules,
+            "standard"
+        );
+        assert_eq!(
+            update_rules(&session_id, "other", "X-----", conn.deref()).unwrap(),
+            1
+        );
+        let game_state = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(game_state.rules, "other");
+        assert_eq!(game_state.board.unwrap(), "X-----");
+    }
+
+    #[test]
+    pub fn test_update_move_list() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        assert_eq!(
+            get_game_state(&session_id, conn.deref()).unwrap().move_list,
+            ""
+        );
+        assert_eq!(
+            update_move_list(&session_id, "5 5 4", conn.deref()).unwrap(),
+            1
+        );
+        let gs = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(gs.move_list, "5 5 4");
+    }
+
+    fn new_move(session_id: &Uuid, ply: i32, created_at: Option<&str>) -> NewMove {
+        NewMove {
+            game_id: session_id.to_string(),
+            ply,
+            user_id: Uuid::new_v4().to_string(),
+            col_num: 4,
+            row_num: ply,
+            created_at: created_at.map(str::to_string),
+            kind: "drop".to_string(),
+        }
+    }
+
+    #[test]
+    pub fn test_insert_and_get_moves() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        let first = new_move(&session_id, 1, Some("2020-06-19 11:42:29"));
+        let second = new_move(&session_id, 2, None);
+        assert_eq!(insert_move(&second, conn.deref()).unwrap(), 1);
+        assert_eq!(insert_move(&first, conn.deref()).unwrap(), 1);
+        // a ply is played only once
+        assert!(insert_move(&first, conn.deref()).is_err());
+
+        let moves = get_moves(&session_id, conn.deref()).unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].ply, 1);
+        assert_eq!(moves[0].user_id, first.user_id);
+        assert_eq!(moves[0].created_at, "2020-06-19 11:42:29");
+        assert_eq!(moves[1].row_num, 2);
+        assert_eq!(moves[1].created_at.len(), "2020-06-19 11:42:29".len());
+    }
+
+    #[test]
+    pub fn test_store_projection() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id =
+            create_new_session(&Uuid::new_v4(), &BoardSize::default(), conn.deref()).unwrap();
+        update_winning_line(&session_id, 'X', "1,2,3,4,5", conn.deref()).unwrap();
+        let mut projection = get_game_state(&session_id, conn.deref()).unwrap();
+        projection.board = Some("-".repeat(53) + "X");
+        projection.winner_color = None;
+        projection.winning_cells = None;
+        projection.move_list = "9".to_string();
+        assert_eq!(store_projection(&projection, conn.deref()).unwrap(), 1);
+
+        let stored = get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(stored.board, projection.board);
+        assert_eq!(stored.winner_color, None);
+        assert_eq!(stored.winning_cells, None);
+        assert_eq!(stored.move_list, "9");
+        assert!(get_game_ids(conn.deref())
+            .unwrap()
+            .contains(&session_id.to_string()));
+    }
+
+    #[test]
+    pub fn test_get_user() {
+        let conn = create_conn_pool().get().unwrap();
+        let new_user_id = create_new_user("test-user", "X", conn.deref()).unwrap();
+        let user = get_user(&new_user_id, conn.deref()).unwrap();
+        assert_eq!(user.user_name, "test-user");
+        assert_eq!(user.user_color, "X");
+        assert!(get_user(&Uuid::new_v4(), conn.deref()).is_err());
+    }
+
     #[test]
     pub fn test_get_user_color() {
         let conn = create_conn_pool().get().unwrap();
@@ -308,7 +804,7 @@ pub mod tests {
     pub fn test_get_game_state() {
         let user = Uuid::new_v4();
         let conn = create_conn_pool().get().unwrap();
-        let result = create_new_session(&user, conn.deref());
+        let result = create_new_session(&user, &BoardSize::default(), conn.deref());
         match result {
             Ok(session_id) => {
                 let gs = get_game_state(&session_id, conn.deref()).unwrap();
@@ -0,0 +1,162 @@
+//! Evaluation of every legal move of a position, for post-game review and hints.
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::engine::bitboard::BitBoard;
+use crate::engine::book::BookMove;
+use crate::engine::minimax::{Minimax, WIN_SCORE};
+
+/// Longest principal variation reported for a move.
+const MAX_PV_LENGTH: usize = 12;
+
+/// Value of a move for the player to move.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum Evaluation {
+    /// forced win, `moves` counts the moves of both players up to and including the winning one
+    Win { moves: usize },
+    /// forced loss, `moves` counts the moves up to and including the winning move of the other
+    /// player
+    Loss { moves: usize },
+    /// the game ends in a draw with best play from both sides
+    Draw,
+    /// heuristic score of the search, positive if the position favours the player to move
+    Score { score: i32 },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ColumnEvaluation {
+    /// 1-based
+    pub column: usize,
+    #[serde(flatten)]
+    pub evaluation: Evaluation,
+    /// best line starting with `column`, 1-based columns
+    pub pv: Vec<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// depth of the search, in moves
+    pub depth: usize,
+    /// legal columns, left to right
+    pub columns: Vec<ColumnEvaluation>,
+    /// 1-based column of the best move, `None` if the board is full
+    pub best: Option<usize>,
+    /// principal variation, the line of `best`
+    pub pv: Vec<usize>,
+    /// moves of the opening book for the position
+    pub book: Vec<BookMove>,
+}
+
+/// Searches every legal move of `position` for at most `time_budget`.
+pub fn analyse(position: &BitBoard, time_budget: Duration) -> Analysis {
+    let mut minimax = Minimax::new(position.size().cell_count(), time_budget);
+    let (scores, depth) = minimax.analyse(position);
+    let remaining = position.size().cell_count() - position.moves();
+    let best = scores
+        .iter()
+        .max_by_key(|(_, score)| *score)
+        .map(|(col, _)| *col);
+
+    let mut columns = scores
+        .iter()
+        .map(|(col, score)| ColumnEvaluation {
+            column: col + 1,
+            evaluation: evaluation(position, *score, depth >= remaining),
+            pv: minimax
+                .principal_variation(position, *col, MAX_PV_LENGTH)
+                .iter()
+                .map(|col| col + 1)
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    columns.sort_by_key(|column| column.column);
+    let pv = best
+        .and_then(|best| columns.iter().find(|column| column.column == best + 1))
+        .map(|column| column.pv.clone())
+        .unwrap_or_default();
+    Analysis {
+        depth,
+        columns,
+        best: best.map(|col| col + 1),
+        pv,
+        book: vec![],
+    }
+}
+
+// `complete` is true if the search reached the end of the game on every line
+fn evaluation(position: &BitBoard, score: i32, complete: bool) -> Evaluation {
+    // a win found with `moves` pieces on the board scores `WIN_SCORE - moves`
+    let moves_to_win = |score: i32| (WIN_SCORE - score) as usize + 1 - position.moves();
+    if score > WIN_SCORE / 2 {
+        Evaluation::Win {
+            moves: moves_to_win(score),
+        }
+    } else if score < -WIN_SCORE / 2 {
+        Evaluation::Loss {
+            moves: moves_to_win(-score),
+        }
+    } else if complete && score == 0 {
+        Evaluation::Draw
+    } else {
+        Evaluation::Score { score }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::BoardSize;
+    use crate::engine::analysis::{analyse, Evaluation};
+    use crate::engine::bitboard::BitBoard;
+    use std::time::Duration;
+
+    fn position(size: BoardSize, moves: &[usize]) -> BitBoard {
+        let mut position = BitBoard::new(size).unwrap();
+        position.play_sequence(moves).unwrap();
+        position
+    }
+
+    #[test]
+    pub fn test_win_and_loss() {
+        let position = position(BoardSize::new(6, 7, 4), &[0, 6, 1, 6, 2]);
+        let analysis = analyse(&position, Duration::from_secs(1));
+        assert_eq!(analysis.best, Some(4));
+        assert_eq!(analysis.columns.len(), 7);
+        // the other player wins on the next move unless column 4 is blocked
+        let column = |column: usize| analysis.columns[column - 1].evaluation.clone();
+        assert_eq!(column(1), Evaluation::Loss { moves: 2 });
+        assert!(matches!(column(4), Evaluation::Score { .. }));
+        assert_eq!(analysis.columns[0].pv, vec![1, 4]);
+        assert_eq!(analysis.pv.first(), Some(&4));
+    }
+
+    #[test]
+    pub fn test_immediate_win() {
+        let position = position(BoardSize::new(6, 7, 4), &[0, 6, 1, 6, 2, 5]);
+        let analysis = analyse(&position, Duration::from_secs(1));
+        assert_eq!(analysis.best, Some(4));
+        assert_eq!(analysis.columns[3].evaluation, Evaluation::Win { moves: 1 });
+        assert_eq!(analysis.pv, vec![4]);
+    }
+
+    #[test]
+    pub fn test_draw() {
+        // nobody can connect 3 on a 2x3 board
+        let position = position(BoardSize::new(2, 3, 3), &[1]);
+        let analysis = analyse(&position, Duration::from_secs(1));
+        assert_eq!(analysis.depth, 5);
+        assert!(analysis
+            .columns
+            .iter()
+            .all(|column| column.evaluation == Evaluation::Draw));
+    }
+
+    #[test]
+    pub fn test_full_board() {
+        let position = position(BoardSize::new(1, 2, 2), &[0, 1]);
+        let analysis = analyse(&position, Duration::from_secs(1));
+        assert_eq!(analysis.best, None);
+        assert!(analysis.columns.is_empty());
+    }
+}
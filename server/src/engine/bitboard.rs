@@ -0,0 +1,416 @@
+use crate::board::{Board, BoardSize, WinRule, EMPTY_CELL};
+
+/// Number of bits available to lay out the board.
+const MAX_BITS: usize = 128;
+
+/// Connect-N position stored as bitmasks.
+///
+/// Every column takes `rows + 1` bits, bottom cell in the lowest bit. The extra bit on top of each
+/// column always stays empty, so that shifting a line never wraps into the next column. Player 0
+/// is the one who moves first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitBoard {
+    size: BoardSize,
+    win_rule: WinRule,
+    height: usize,
+    pieces: [u128; 2],
+    mask: u128,
+    moves: usize,
+    bottom_mask: u128,
+    board_mask: u128,
+}
+
+impl BitBoard {
+    pub fn new(size: BoardSize) -> Result<Self, String> {
+        let height = size.rows + 1;
+        if height * size.columns > MAX_BITS {
+            return Err(format!(
+                "A {}x{} board is too big for the engine",
+                size.rows, size.columns
+            ));
+        }
+        let mut bottom_mask = 0;
+        for col in 0..size.columns {
+            bottom_mask |= 1u128 << (col * height);
+        }
+        let board_mask = bottom_mask * ((1u128 << size.rows) - 1);
+        Ok(BitBoard {
+            size,
+            win_rule: WinRule::default(),
+            height,
+            pieces: [0, 0],
+            mask: 0,
+            moves: 0,
+            bottom_mask,
+            board_mask,
+        })
+    }
+
+    /// The position played with the win rule, freestyle by default.
+    pub fn with_win_rule(self, win_rule: WinRule) -> Self {
+        BitBoard { win_rule, ..self }
+    }
+
+    /// Converts a `Board` and its win rule, `colors` being the colors of the first and the
+    /// second player.
+    pub fn from_board(board: &Board, colors: [char; 2]) -> Result<Self, String> {
+        let mut bitboard = BitBoard::new(board.size())?.with_win_rule(board.win_rule());
+        for col in 0..board.columns() {
+            let mut below_empty = false;
+            for row in (0..board.rows()).rev() {
+                let cell = board.cell(row, col);
+                if cell == EMPTY_CELL {
+                    below_empty = true;
+                    continue;
+                }
+                if below_empty {
+                    return Err(format!("Floating piece in column {}", col + 1));
+                }
+                let player = colors
+                    .iter()
+                    .position(|color| *color == cell)
+                    .ok_or_else(|| format!("Unknown color '{}' on the board", cell))?;
+                let bit = bitboard.cell_bit(board.rows() - 1 - row, col);
+                bitboard.pieces[player] |= bit;
+                bitboard.mask |= bit;
+                bitboard.moves += 1;
+            }
+        }
+        let first = bitboard.pieces[0].count_ones();
+        let second = bitboard.pieces[1].count_ones();
+        if first != second && first != second + 1 {
+            return Err(format!(
+                "Unbalanced position: {} pieces of '{}' and {} of '{}'",
+                first, colors[0], second, colors[1]
+            ));
+        }
+        Ok(bitboard)
+    }
+
+    pub fn from_board_str(
+        board_str: &str,
+        size: BoardSize,
+        colors: [char; 2],
+    ) -> Result<Self, String> {
+        BitBoard::from_board(&Board::parse(board_str, size)?, colors)
+    }
+
+    pub fn to_board(&self, colors: [char; 2]) -> Board {
+        let mut board = Board::new(self.size).with_win_rule(self.win_rule, colors[0]);
+        // dropping the pieces bottom up rebuilds every column
+        for row in 0..self.size.rows {
+            for col in 0..self.size.columns {
+                let bit = self.cell_bit(row, col);
+                for (player, color) in colors.iter().enumerate() {
+                    if self.pieces[player] & bit != 0 {
+                        board.drop_piece(col, *color).unwrap();
+                    }
+                }
+            }
+        }
+        board
+    }
+
+    pub fn size(&self) -> BoardSize {
+        self.size
+    }
+
+    pub fn win_rule(&self) -> WinRule {
+        self.win_rule
+    }
+
+    /// Number of pieces on the board.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    /// 0 for the player who moved first, 1 for the second one
+    pub fn current_player(&self) -> usize {
+        self.moves % 2
+    }
+
+    pub fn pieces(&self, player: usize) -> u128 {
+        self.pieces[player]
+    }
+
+    pub fn mask(&self) -> u128 {
+        self.mask
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.moves == self.size.cell_count()
+    }
+
+    pub fn can_play(&self, col: usize) -> bool {
+        col < self.size.columns && self.mask & self.top_mask(col) == 0
+    }
+
+    pub fn legal_moves(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.size.columns).filter(move |col| self.can_play(*col))
+    }
+
+    /// Bits of the cells the next piece can land on, one per non full column.
+    pub fn possible(&self) -> u128 {
+        (self.mask + self.bottom_mask) & self.board_mask
+    }
+
+    /// Drops a piece of the current player into the (zero based) column. The column must not be
+    /// full, see `can_play`.
+    pub fn play(&mut self, col: usize) {
+        let bit = self.move_bit(col);
+        self.pieces[self.current_player()] |= bit;
+        self.mask |= bit;
+        self.moves += 1;
+    }
+
+    /// Takes back the last piece played into the column.
+    pub fn unplay(&mut self, col: usize) {
+        let column = self.mask & self.column_mask(col);
+        let top = 1u128 << (127 - column.leading_zeros());
+        self.moves -= 1;
+        self.pieces[self.current_player()] &= !top;
+        self.mask &= !top;
+    }
+
+    /// Plays a sequence of zero based columns, e.g. to set up a position.
+    pub fn play_sequence(&mut self, cols: &[usize]) -> Result<(), String> {
+        for col in cols {
+            if !self.can_play(*col) {
+                return Err(format!("Can't play column {}", col + 1));
+            }
+            self.play(*col);
+        }
+        Ok(())
+    }
+
+    /// True if the current player wins by playing the column.
+    pub fn is_winning_move(&self, col: usize) -> bool {
+        let player = self.current_player();
+        self.has_line(self.pieces[player] | self.move_bit(col), player)
+    }
+
+    /// Bit of the cell a piece played into the column lands on, 0 if the column is full.
+    pub fn move_bit(&self, col: usize) -> u128 {
+        (self.mask + self.bottom_mask_col(col)) & self.column_mask(col)
+    }
+
+    /// Row, counted from the bottom, and column of a single cell bit.
+    pub fn bit_cell(&self, bit: u128) -> (usize, usize) {
+        let index = bit.trailing_zeros() as usize;
+        (index % self.height, index / self.height)
+    }
+
+    /// The player owning a winning line, if any.
+    pub fn winner(&self) -> Option<usize> {
+        (0..2).find(|player| self.has_line(self.pieces[*player], *player))
+    }
+
+    /// True if `bits`, the pieces of `player`, contain a winning line in any direction. Under the
+    /// exact win rules a line only wins without pieces of `player` before and after it, see
+    /// `WinRule`.
+    pub fn has_line(&self, bits: u128, player: usize) -> bool {
+        let h = self.height;
+        let length = self.size.win_length;
+        let exact = !self.win_rule.wins(length + 1, length, player == 0);
+        [1, h, h + 1, h - 1].iter().any(|shift| {
+            let mut starts = self.line_starts(bits, *shift);
+            if exact {
+                let after = bits.checked_shr((length * shift) as u32).unwrap_or(0);
+                starts &= !(bits << shift) & !after;
+            }
+            starts != 0
+        })
+    }
+
+    /// Masks of every line of `win_length` cells on the board, in all directions.
+    pub fn line_masks(&self) -> Vec<u128> {
+        let (rows, columns) = (self.size.rows as isize, self.size.columns as isize);
+        let length = self.size.win_length as isize;
+        let mut masks = vec![];
+        for col in 0..columns {
+            for row in 0..rows {
+                for (d_col, d_row) in &[(0, 1), (1, 0), (1, 1), (1, -1)] {
+                    let end_col = col + d_col * (length - 1);
+                    let end_row = row + d_row * (length - 1);
+                    if end_col >= columns || end_row < 0 || end_row >= rows {
+                        continue;
+                    }
+                    masks.push((0..length).fold(0, |mask, i| {
+                        mask | self.cell_bit((row + d_row * i) as usize, (col + d_col * i) as usize)
+                    }));
+                }
+            }
+        }
+        masks
+    }
+
+    // bits starting a run of `win_length` pieces in the direction of `shift`
+    fn line_starts(&self, bits: u128, shift: usize) -> u128 {
+        let mut run = bits;
+        for _ in 1..self.size.win_length {
+            run &= run >> shift;
+        }
+        run
+    }
+
+    // `row` counts from the bottom
+    fn cell_bit(&self, row: usize, col: usize) -> u128 {
+        1u128 << (col * self.height + row)
+    }
+
+    fn top_mask(&self, col: usize) -> u128 {
+        1u128 << (self.size.rows - 1 + col * self.height)
+    }
+
+    fn bottom_mask_col(&self, col: usize) -> u128 {
+        1u128 << (col * self.height)
+    }
+
+    fn column_mask(&self, col: usize) -> u128 {
+        ((1u128 << self.size.rows) - 1) << (col * self.height)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{Board, BoardSize, WinRule};
+    use crate::engine::bitboard::BitBoard;
+
+    const COLORS: [char; 2] = ['X', 'O'];
+
+    #[test]
+    pub fn test_board_str_round_trip() {
+        let size = BoardSize::default();
+        let board_str = "-".repeat(36) + "----X----" + "---OX----";
+        let bitboard = BitBoard::from_board_str(&board_str, size, COLORS).unwrap();
+        assert_eq!(bitboard.moves(), 3);
+        assert_eq!(bitboard.current_player(), 1);
+        assert_eq!(bitboard.to_board(COLORS).to_string(), board_str);
+    }
+
+    #[test]
+    pub fn test_from_board_errors() {
+        let size = BoardSize::new(2, 2, 2);
+        assert!(BitBoard::from_board_str("X---", size, COLORS).is_err());
+        assert!(BitBoard::from_board_str("--XX", size, COLORS).is_err());
+        assert!(BitBoard::from_board_str("--XZ", size, COLORS).is_err());
+        assert!(BitBoard::new(BoardSize::new(15, 15, 5)).is_err());
+    }
+
+    #[test]
+    pub fn test_play_and_unplay() {
+        let mut bitboard = BitBoard::new(BoardSize::new(2, 3, 2)).unwrap();
+        bitboard.play_sequence(&[1, 1]).unwrap();
+        assert!(!bitboard.can_play(1));
+        assert_eq!(bitboard.legal_moves().collect::<Vec<_>>(), vec![0, 2]);
+        assert!(bitboard.play_sequence(&[1]).is_err());
+
+        let before = bitboard.clone();
+        bitboard.play(2);
+        assert_eq!(bitboard.to_board(COLORS).to_string(), "-O--XX");
+        bitboard.unplay(2);
+        assert_eq!(bitboard, before);
+    }
+
+    #[test]
+    pub fn test_wins_in_every_direction() {
+        let size = BoardSize::new(6, 7, 4);
+        // (moves, winning column) with the first player to win
+        let games = vec![
+            (vec![0, 0, 1, 1, 2, 2], 3),             // horizontal
+            (vec![0, 1, 0, 1, 0, 1], 0),             // vertical
+            (vec![0, 1, 1, 2, 2, 3, 2, 3, 3, 6], 3), // diagonal up
+            (vec![6, 5, 5, 4, 4, 3, 4, 3, 3, 0], 3), // diagonal down
+        ];
+        for (moves, winning_col) in games {
+            let mut bitboard = BitBoard::new(size).unwrap();
+            bitboard.play_sequence(&moves).unwrap();
+            assert_eq!(bitboard.winner(), None);
+            assert!(bitboard.is_winning_move(winning_col));
+            bitboard.play(winning_col);
+            assert_eq!(bitboard.winner(), Some(0));
+        }
+    }
+
+    #[test]
+    pub fn test_exact_win_rules() {
+        let size = BoardSize::new(4, 8, 3);
+        let cases = [
+            (WinRule::Freestyle, [true, true]),
+            (WinRule::Exact, [false, false]),
+            (WinRule::Renju, [false, true]),
+        ];
+        for (win_rule, overline_wins) in cases.iter() {
+            let bitboard = BitBoard::new(size).unwrap().with_win_rule(*win_rule);
+            let bits = |cells: &[(usize, usize)]| {
+                cells
+                    .iter()
+                    .fold(0, |bits, (row, col)| bits | bitboard.cell_bit(*row, *col))
+            };
+            for player in 0..2 {
+                // exactly three, in a row and in a column
+                assert!(bitboard.has_line(bits(&[(0, 5), (0, 6), (0, 7)]), player));
+                assert!(bitboard.has_line(bits(&[(1, 0), (2, 0), (3, 0), (0, 1)]), player));
+                // four in a row and four in a column
+                let overlines = [
+                    bits(&[(0, 0), (0, 1), (0, 2), (0, 3)]),
+                    bits(&[(0, 0), (1, 0), (2, 0), (3, 0), (0, 1)]),
+                ];
+                for overline in overlines.iter() {
+                    assert_eq!(bitboard.has_line(*overline, player), overline_wins[player]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_line_masks() {
+        let bitboard = BitBoard::new(BoardSize::new(6, 7, 4)).unwrap();
+        let masks = bitboard.line_masks();
+        assert_eq!(masks.len(), 69);
+        assert!(masks.iter().all(|mask| mask.count_ones() == 4));
+        assert!(masks.iter().all(|mask| bitboard.has_line(*mask, 0)));
+    }
+
+    #[test]
+    pub fn test_no_wrap_between_columns() {
+        // three on top of column 1 and one at the bottom of column 2 is not a vertical line
+        let size = BoardSize::new(4, 3, 4);
+        let board = Board::parse("X--X--X-OOXO", size).unwrap();
+        let bitboard = BitBoard::from_board(&board, ['X', 'O']).unwrap();
+        assert_eq!(bitboard.winner(), None);
+    }
+
+    #[test]
+    pub fn test_matches_board_winner() {
+        let mut seed: u64 = 7;
+        for game in 0..300 {
+            // every other game with exact wins, short lines making overlines frequent
+            let (size, win_rule) = if game % 2 == 0 {
+                (BoardSize::default(), WinRule::Freestyle)
+            } else {
+                (BoardSize::new(6, 7, 3), WinRule::Exact)
+            };
+            let mut board = Board::new(size).with_win_rule(win_rule, COLORS[0]);
+            let mut bitboard = BitBoard::new(size).unwrap().with_win_rule(win_rule);
+            while !bitboard.is_full() && bitboard.winner().is_none() {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let col = (seed >> 33) as usize % size.columns;
+                if !bitboard.can_play(col) {
+                    continue;
+                }
+                board
+                    .drop_piece(col, COLORS[bitboard.current_player()])
+                    .unwrap();
+                bitboard.play(col);
+                assert_eq!(
+                    bitboard.winner().map(|player| COLORS[player]),
+                    board.winner()
+                );
+            }
+            assert_eq!(bitboard.to_board(COLORS), board);
+        }
+    }
+}
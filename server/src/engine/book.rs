@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::board::{BoardSize, WinRule};
+use crate::engine::bitboard::BitBoard;
+use crate::engine::zobrist::Zobrist;
+use crate::engine::AiPlayer;
+use crate::notation::Notation;
+
+/// Environment variable with the path of the opening book file, see `global`.
+pub const BOOK_PATH_VAR: &str = "OPENING_BOOK";
+
+/// A recommended move of the book. `weight` is relative to the other moves of the position.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct BookMove {
+    /// 1-based, as in `notation`
+    pub column: usize,
+    pub weight: u32,
+}
+
+/// Recommended moves for early positions, for any number of board sizes.
+///
+/// Positions are looked up by their Zobrist hash, so a position reached through a different
+/// move order finds the same moves. The text format has one position per line: the moves
+/// leading to it, a colon and the book moves as `column:weight`, all columns 1-based. A header
+/// line as in `notation`, e.g. `6x9/5`, sets the board size and the win rule of the lines that
+/// follow:
+///
+/// ```text
+/// # comment
+/// 6x9/5
+/// : 5:10 4:2
+/// 5 : 5:6 6:3
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpeningBook {
+    positions: HashMap<(BoardSize, WinRule, u64), BookPosition>,
+}
+
+#[derive(Debug, Clone)]
+struct BookPosition {
+    size: BoardSize,
+    win_rule: WinRule,
+    /// first line found to the position, 0-based columns
+    line: Vec<usize>,
+    moves: Vec<BookMove>,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        OpeningBook::default()
+    }
+
+    /// Reads a book in the text format.
+    pub fn load(path: &str) -> Result<Self, String> {
+        fs::read_to_string(path)
+            .map_err(|error| format!("Can't read the opening book {}: {}", path, error))?
+            .parse()
+    }
+
+    /// Number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Adds `weight` to the zero based column `col` in the position after `line`.
+    pub fn add(
+        &mut self,
+        size: BoardSize,
+        win_rule: WinRule,
+        line: &[usize],
+        col: usize,
+        weight: u32,
+    ) -> Result<(), String> {
+        let mut position = BitBoard::new(size)?.with_win_rule(win_rule);
+        position.play_sequence(line)?;
+        if position.winner().is_some() || !position.can_play(col) {
+            return Err(format!("Can't play column {} after {:?}", col + 1, line));
+        }
+        let entry = self
+            .positions
+            .entry(key(&position))
+            .or_insert_with(|| BookPosition {
+                size,
+                win_rule,
+                line: line.to_vec(),
+                moves: vec![],
+            });
+        match entry.moves.iter_mut().find(|m| m.column == col + 1) {
+            Some(book_move) => book_move.weight += weight,
+            None => entry.moves.push(BookMove {
+                column: col + 1,
+                weight,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Adds the first `plies` moves of a game, weighted by the result for the player who made
+    /// them: 2 for a win, 1 for a draw or an unfinished game. Moves of the loser are left out.
+    pub fn add_game(
+        &mut self,
+        size: BoardSize,
+        win_rule: WinRule,
+        moves: &[usize],
+        plies: usize,
+    ) -> Result<(), String> {
+        let mut position = BitBoard::new(size)?.with_win_rule(win_rule);
+        position.play_sequence(moves)?;
+        let winner = position.winner();
+        for (ply, col) in moves.iter().enumerate().take(plies) {
+            let weight = match winner {
+                Some(winner) if winner == ply % 2 => 2,
+                Some(_) => continue,
+                None => 1,
+            };
+            self.add(size, win_rule, &moves[..ply], *col, weight)?;
+        }
+        Ok(())
+    }
+
+    /// Book moves of the position, the heaviest first. Empty if the position is not in the book.
+    pub fn moves(&self, position: &BitBoard) -> Vec<BookMove> {
+        let mut moves = self
+            .positions
+            .get(&key(position))
+            .map(|entry| entry.moves.clone())
+            .unwrap_or_default();
+        moves.retain(|m| m.weight > 0 && position.can_play(m.column - 1));
+        moves.sort_by_key(|m| (std::cmp::Reverse(m.weight), m.column));
+        moves
+    }
+
+    /// Picks a zero based column among the book moves, with a probability proportional to its
+    /// weight.
+    pub fn choose(&self, position: &BitBoard, rng: &mut impl Rng) -> Option<usize> {
+        let moves = self.moves(position);
+        let total = moves.iter().map(|m| m.weight).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rng.gen_range(0..total);
+        for book_move in moves {
+            if pick < book_move.weight {
+                return Some(book_move.column - 1);
+            }
+            pick -= book_move.weight;
+        }
+        None
+    }
+}
+
+/// The book at the path of the `OPENING_BOOK` environment variable, loaded on first use. `None`
+/// if the variable is not set or the book can't be read.
+pub fn global() -> Option<Arc<OpeningBook>> {
+    static BOOK: OnceLock<Option<Arc<OpeningBook>>> = OnceLock::new();
+    BOOK.get_or_init(|| {
+        let path = std::env::var(BOOK_PATH_VAR).ok()?;
+        match OpeningBook::load(&path) {
+            Ok(book) => Some(Arc::new(book)),
+            Err(error) => {
+                println!("{}", error);
+                None
+            }
+        }
+    })
+    .clone()
+}
+
+fn key(position: &BitBoard) -> (BoardSize, WinRule, u64) {
+    let size = position.size();
+    (size, position.win_rule(), Zobrist::new(size).hash(position))
+}
+
+impl fmt::Display for OpeningBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut positions = self.positions.values().collect::<Vec<_>>();
+        positions.sort_by_key(|p| {
+            let size = (p.size.rows, p.size.columns, p.size.win_length);
+            (size, p.win_rule.name(), p.line.len(), p.line.clone())
+        });
+        let mut header = None;
+        for position in positions {
+            if header != Some((position.size, position.win_rule)) {
+                let notation =
+                    Notation::new(position.size, vec![]).with_win_rule(position.win_rule);
+                writeln!(f, "{}", notation)?;
+                header = Some((position.size, position.win_rule));
+            }
+            let line = position.line.iter().map(|col| col + 1).collect::<Vec<_>>();
+            write!(f, "{}", Notation::new(position.size, line).move_list())?;
+            write!(f, "{}:", if position.line.is_empty() { "" } else { " " })?;
+            for book_move in &position.moves {
+                write!(f, " {}:{}", book_move.column, book_move.weight)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for OpeningBook {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut book = OpeningBook::new();
+        let mut size = BoardSize::default();
+        let mut win_rule = WinRule::default();
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |error: String| format!("Line {}: {}", index + 1, error);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (moves, book_moves) = match line.split_once(':') {
+                Some(parts) => parts,
+                None => {
+                    let header = line.parse::<Notation>().map_err(invalid)?;
+                    size = header.size;
+                    win_rule = header.win_rule;
+                    continue;
+                }
+            };
+            let moves = Notation::from_move_list(size, win_rule, moves)
+                .map_err(invalid)?
+                .moves;
+            let moves = moves.iter().map(|col| col - 1).collect::<Vec<_>>();
+            for book_move in book_moves.split_whitespace() {
+                let parse = || -> Option<(usize, u32)> {
+                    let (column, weight) = book_move.split_once(':')?;
+                    Some((column.parse().ok()?, weight.parse().ok()?))
+                };
+                let (column, weight) = parse()
+                    .filter(|(column, _)| *column >= 1)
+                    .ok_or_else(|| invalid(format!("Invalid book move '{}'", book_move)))?;
+                book.add(size, win_rule, &moves, column - 1, weight)
+                    .map_err(invalid)?;
+            }
+        }
+        Ok(book)
+    }
+}
+
+/// Plays from the book while the position is in it, then lets `player` choose.
+pub struct BookPlayer {
+    book: Arc<OpeningBook>,
+    player: Box<dyn AiPlayer>,
+    rng: StdRng,
+}
+
+impl BookPlayer {
+    pub fn new(book: Arc<OpeningBook>, player: Box<dyn AiPlayer>) -> Self {
+        BookPlayer {
+            book,
+            player,
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl AiPlayer for BookPlayer {
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+        self.book
+            .choose(position, &mut self.rng)
+            .or_else(|| self.player.choose_move(position))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{BoardSize, WinRule};
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::book::{BookMove, BookPlayer, OpeningBook};
+    use crate::engine::AiPlayer;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::sync::Arc;
+
+    const BOOK: &str = "# test book
+6x9/5
+: 5:10 4:2
+5 : 5:6 6:3
+6x7/4
+: 4:1
+";
+
+    fn position(size: BoardSize, moves: &[usize]) -> BitBoard {
+        let mut position = BitBoard::new(size).unwrap();
+        position.play_sequence(moves).unwrap();
+        position
+    }
+
+    /// Always plays the first column.
+    struct FirstColumn;
+
+    impl AiPlayer for FirstColumn {
+        fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+            position.legal_moves().next()
+        }
+    }
+
+    #[test]
+    pub fn test_parse_and_display() {
+        let book = BOOK.parse::<OpeningBook>().unwrap();
+        assert_eq!(book.len(), 3);
+        let text = book.to_string();
+        assert_eq!(text, "6x7/4\n: 4:1\n6x9/5\n: 5:10 4:2\n5 : 5:6 6:3\n");
+        assert_eq!(text.parse::<OpeningBook>().unwrap().to_string(), text);
+    }
+
+    #[test]
+    pub fn test_parse_errors() {
+        assert!("6x9/5\n: 10:1".parse::<OpeningBook>().is_err());
+        assert!("6x9/5\n: 5".parse::<OpeningBook>().is_err());
+        assert!("6x9/5\nx : 5:1".parse::<OpeningBook>().is_err());
+        assert!("9x".parse::<OpeningBook>().is_err());
+    }
+
+    #[test]
+    pub fn test_moves() {
+        let book = BOOK.parse::<OpeningBook>().unwrap();
+        let start = position(BoardSize::default(), &[]);
+        assert_eq!(
+            book.moves(&start),
+            vec![
+                BookMove {
+                    column: 5,
+                    weight: 10
+                },
+                BookMove {
+                    column: 4,
+                    weight: 2
+                }
+            ]
+        );
+        assert_eq!(book.moves(&position(BoardSize::default(), &[4])).len(), 2);
+        assert!(book.moves(&position(BoardSize::default(), &[3])).is_empty());
+        assert_eq!(
+            book.moves(&position(BoardSize::new(6, 7, 4), &[]))[0].column,
+            4
+        );
+    }
+
+    #[test]
+    pub fn test_transpositions_share_moves() {
+        let mut book = OpeningBook::new();
+        let size = BoardSize::default();
+        book.add(size, WinRule::Freestyle, &[0, 1, 2], 4, 1)
+            .unwrap();
+        book.add(size, WinRule::Freestyle, &[2, 1, 0], 4, 2)
+            .unwrap();
+        assert_eq!(book.len(), 1);
+        assert_eq!(book.moves(&position(size, &[2, 1, 0]))[0].weight, 3);
+
+        // the same position with another win rule is another book position
+        book.add(size, WinRule::Exact, &[2, 1, 0], 5, 1).unwrap();
+        assert_eq!(book.len(), 2);
+        let exact = position(size, &[2, 1, 0]).with_win_rule(WinRule::Exact);
+        assert_eq!(book.moves(&exact)[0].column, 6);
+        let text = book.to_string();
+        assert!(text.contains("6x9/5/exact\n3 2 1 : 6:1\n"));
+        assert_eq!(text.parse::<OpeningBook>().unwrap().to_string(), text);
+    }
+
+    #[test]
+    pub fn test_add_game() {
+        let mut book = OpeningBook::new();
+        let size = BoardSize::new(6, 7, 4);
+        // the first player wins in column 4
+        book.add_game(size, WinRule::Freestyle, &[0, 6, 1, 6, 2, 6, 3], 3)
+            .unwrap();
+        assert_eq!(book.len(), 2);
+        assert_eq!(book.moves(&position(size, &[]))[0].weight, 2);
+        assert!(book.moves(&position(size, &[0])).is_empty());
+        assert_eq!(book.moves(&position(size, &[0, 6]))[0].column, 2);
+        assert!(book.add_game(size, WinRule::Freestyle, &[7], 3).is_err());
+    }
+
+    #[test]
+    pub fn test_choose() {
+        let book = BOOK.parse::<OpeningBook>().unwrap();
+        let start = position(BoardSize::default(), &[]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = (0..120)
+            .map(|_| book.choose(&start, &mut rng).unwrap())
+            .collect::<Vec<_>>();
+        assert!(picks.iter().all(|col| *col == 4 || *col == 3));
+        assert!(picks.iter().filter(|col| **col == 4).count() > 80);
+        assert_eq!(
+            book.choose(&position(BoardSize::default(), &[3]), &mut rng),
+            None
+        );
+    }
+
+    #[test]
+    pub fn test_book_player() {
+        let book = Arc::new(BOOK.parse::<OpeningBook>().unwrap());
+        let mut player = BookPlayer::new(book, Box::new(FirstColumn));
+        let in_book = player.choose_move(&position(BoardSize::new(6, 7, 4), &[]));
+        assert_eq!(in_book, Some(3));
+        let out_of_book = player.choose_move(&position(BoardSize::default(), &[3]));
+        assert_eq!(out_of_book, Some(0));
+    }
+}
@@ -0,0 +1,485 @@
+//! Engines running in their own process, talking a line based protocol in the spirit of UCI
+//! over stdin and stdout. Lets engines written in any language play or analyse without being
+//! linked into the server.
+//!
+//! ```text
+//! > c5i
+//! < id name connect5-rust minimax
+//! < c5iok
+//! > isready
+//! < readyok
+//! > position 6 9 5 ----------------------------------------------x--------
+//! > go movetime 1000
+//! < info depth 12 score 35 pv 5 4 5
+//! < bestmove 5
+//! > quit
+//! ```
+//!
+//! `position` gives the rows, the columns, the winning length and the cells row by row from the
+//! top, `x` for the player who moved first, `o` for the other one and `-` for an empty cell. The
+//! engine plays for the player to move. A last word `exact` or `renju` gives the win rule of
+//! games that are not freestyle, see `board::WinRule`. `go movetime` is the time to think in
+//! milliseconds, the engine answers with the 1-based column of its move, or `bestmove none` if
+//! it has no move. `info` lines are optional free text. Both sides ignore lines they don't know.
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command as Process, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::{BoardSize, WinRule};
+use crate::engine::bitboard::BitBoard;
+use crate::engine::AiPlayer;
+
+/// Environment variable with the command line of the engine played by `AiEngine::External`.
+pub const ENGINE_COMMAND_VAR: &str = "EXTERNAL_ENGINE";
+
+/// Colors of the first and the second player in `position`.
+pub const COLORS: [char; 2] = ['x', 'o'];
+
+/// Time for the engine to start and to answer `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time the engine may take over `movetime` before it is considered hanging.
+const MOVE_GRACE: Duration = Duration::from_secs(2);
+
+/// Lines sent to the engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Hello,
+    IsReady,
+    Position {
+        size: BoardSize,
+        win_rule: WinRule,
+        cells: String,
+    },
+    Go {
+        movetime: Duration,
+    },
+    Quit,
+}
+
+impl Command {
+    pub fn position(position: &BitBoard) -> Self {
+        Command::Position {
+            size: position.size(),
+            win_rule: position.win_rule(),
+            cells: position.to_board(COLORS).to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Hello => write!(f, "c5i"),
+            Command::IsReady => write!(f, "isready"),
+            Command::Position {
+                size,
+                win_rule,
+                cells,
+            } => {
+                write!(
+                    f,
+                    "position {} {} {} {}",
+                    size.rows, size.columns, size.win_length, cells
+                )?;
+                match win_rule {
+                    WinRule::Freestyle => Ok(()),
+                    win_rule => write!(f, " {}", win_rule),
+                }
+            }
+            Command::Go { movetime } => write!(f, "go movetime {}", movetime.as_millis()),
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let number = |word: &str| {
+            word.parse::<usize>()
+                .map_err(|_| format!("Invalid number '{}' in '{}'", word, line))
+        };
+        match words.as_slice() {
+            ["c5i"] => Ok(Command::Hello),
+            ["isready"] => Ok(Command::IsReady),
+            // the win rule is only given when it is not freestyle
+            ["position", rows, columns, win_length, cells, rest @ ..] if rest.len() <= 1 => {
+                let win_rule = rest
+                    .first()
+                    .map_or(Ok(WinRule::default()), |name| name.parse())?;
+                Ok(Command::Position {
+                    size: BoardSize::new(number(rows)?, number(columns)?, number(win_length)?),
+                    win_rule,
+                    cells: cells.to_string(),
+                })
+            }
+            ["go", "movetime", millis] => Ok(Command::Go {
+                movetime: Duration::from_millis(number(millis)? as u64),
+            }),
+            ["quit"] => Ok(Command::Quit),
+            _ => Err(format!("Unknown command '{}'", line)),
+        }
+    }
+}
+
+/// Lines sent by the engine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Id {
+        name: String,
+    },
+    HelloOk,
+    ReadyOk,
+    Info(String),
+    /// 1-based column, `None` if the engine has no move
+    BestMove(Option<usize>),
+}
+
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reply::Id { name } => write!(f, "id name {}", name),
+            Reply::HelloOk => write!(f, "c5iok"),
+            Reply::ReadyOk => write!(f, "readyok"),
+            Reply::Info(info) => write!(f, "info {}", info),
+            Reply::BestMove(Some(column)) => write!(f, "bestmove {}", column),
+            Reply::BestMove(None) => write!(f, "bestmove none"),
+        }
+    }
+}
+
+impl FromStr for Reply {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.trim();
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match (keyword, rest) {
+            ("id", _) if rest.starts_with("name ") => Ok(Reply::Id {
+                name: rest["name ".len()..].trim().to_string(),
+            }),
+            ("c5iok", "") => Ok(Reply::HelloOk),
+            ("readyok", "") => Ok(Reply::ReadyOk),
+            ("info", _) => Ok(Reply::Info(rest.to_string())),
+            ("bestmove", "none") => Ok(Reply::BestMove(None)),
+            ("bestmove", column) => column
+                .parse()
+                .map(|column| Reply::BestMove(Some(column)))
+                .map_err(|_| format!("Invalid move '{}'", column)),
+            _ => Err(format!("Unknown reply '{}'", line)),
+        }
+    }
+}
+
+/// A running engine process.
+struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+    name: String,
+}
+
+impl EngineProcess {
+    fn spawn(command_line: &str) -> Result<Self, String> {
+        let mut words = command_line.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| format!("Set {} to the command of the engine", ENGINE_COMMAND_VAR))?;
+        let mut child = Process::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("Can't start the engine '{}': {}", command_line, error))?;
+        let stdin = child.stdin.take().ok_or("The engine has no stdin")?;
+        let stdout = child.stdout.take().ok_or("The engine has no stdout")?;
+        // reading on a thread lets the replies be waited for with a timeout
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut process = EngineProcess {
+            child,
+            stdin,
+            replies,
+            name: command_line.to_string(),
+        };
+        process.send(&Command::Hello)?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            match process.receive(deadline)? {
+                Reply::Id { name } => process.name = name,
+                Reply::HelloOk => break,
+                _ => {}
+            }
+        }
+        process.send(&Command::IsReady)?;
+        while process.receive(deadline)? != Reply::ReadyOk {}
+        Ok(process)
+    }
+
+    fn send(&mut self, command: &Command) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|error| format!("Can't write to the engine: {}", error))
+    }
+
+    /// The next reply the server knows, skipping the other lines.
+    fn receive(&mut self, deadline: Instant) -> Result<Reply, String> {
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.replies.recv_timeout(timeout) {
+                Ok(line) => {
+                    if let Ok(reply) = line.parse() {
+                        return Ok(reply);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("The engine {} doesn't answer", self.name))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(format!("The engine {} has stopped", self.name))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        let _ = self.send(&Command::Quit);
+        for _ in 0..10 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Plays the moves of an external engine. The process starts on the first move and is restarted
+/// after an error.
+pub struct ExternalEngine {
+    command_line: String,
+    movetime: Duration,
+    process: Option<EngineProcess>,
+    info: Option<String>,
+}
+
+impl ExternalEngine {
+    pub fn new(command_line: &str, movetime: Duration) -> Self {
+        ExternalEngine {
+            command_line: command_line.to_string(),
+            movetime,
+            process: None,
+            info: None,
+        }
+    }
+
+    /// The engine of the `EXTERNAL_ENGINE` environment variable.
+    pub fn from_env(movetime: Duration) -> Self {
+        ExternalEngine::new(
+            &std::env::var(ENGINE_COMMAND_VAR).unwrap_or_default(),
+            movetime,
+        )
+    }
+
+    /// Starts the engine if it isn't running and returns the name it gave.
+    pub fn start(&mut self) -> Result<&str, String> {
+        if self.process.is_none() {
+            self.process = Some(EngineProcess::spawn(&self.command_line)?);
+        }
+        Ok(self
+            .process
+            .as_ref()
+            .map(|process| process.name.as_str())
+            .unwrap_or_default())
+    }
+
+    /// Last `info` line of the last search, the engine's own view of the position.
+    pub fn info(&self) -> Option<&str> {
+        self.info.as_deref()
+    }
+
+    /// Zero based column the engine plays in `position`.
+    pub fn best_move(&mut self, position: &BitBoard) -> Result<Option<usize>, String> {
+        let result = self.search(position);
+        if result.is_err() {
+            self.process = None;
+        }
+        result
+    }
+
+    fn search(&mut self, position: &BitBoard) -> Result<Option<usize>, String> {
+        self.start()?;
+        self.info = None;
+        let movetime = self.movetime;
+        let process = self.process.as_mut().ok_or("The engine is not running")?;
+        process.send(&Command::position(position))?;
+        process.send(&Command::Go { movetime })?;
+        let deadline = Instant::now() + movetime + MOVE_GRACE;
+        loop {
+            match process.receive(deadline)? {
+                Reply::Info(info) => self.info = Some(info),
+                Reply::BestMove(None) => return Ok(None),
+                Reply::BestMove(Some(column)) => {
+                    return match column.checked_sub(1) {
+                        Some(col) if col < position.size().columns && position.can_play(col) => {
+                            Ok(Some(col))
+                        }
+                        _ => Err(format!(
+                            "The engine {} played the illegal move {}",
+                            process.name, column
+                        )),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl AiPlayer for ExternalEngine {
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+        match self.best_move(position) {
+            Ok(col) => col,
+            Err(error) => {
+                println!("{}", error);
+                None
+            }
+        }
+    }
+}
+
+/// The engine of the `EXTERNAL_ENGINE` environment variable, started once and used by every
+/// game. Searches of different games wait for each other, on the threads of `web::block` that
+/// run the moves of the computer (see `api::play`), not on the server's workers.
+fn shared() -> &'static Mutex<ExternalEngine> {
+    static ENGINE: OnceLock<Mutex<ExternalEngine>> = OnceLock::new();
+    ENGINE.get_or_init(|| Mutex::new(ExternalEngine::from_env(Duration::default())))
+}
+
+/// Plays the moves of the shared `EXTERNAL_ENGINE` process, thinking for `movetime`.
+pub struct SharedEngine {
+    movetime: Duration,
+}
+
+impl SharedEngine {
+    pub fn new(movetime: Duration) -> Self {
+        SharedEngine { movetime }
+    }
+
+    /// Starts the shared engine if it isn't running and returns the name it gave.
+    pub fn start() -> Result<String, String> {
+        let mut engine = shared().lock().unwrap_or_else(PoisonError::into_inner);
+        engine.start().map(str::to_string)
+    }
+
+    /// Zero based column the engine plays in `position`.
+    pub fn best_move(&self, position: &BitBoard) -> Result<Option<usize>, String> {
+        let mut engine = shared().lock().unwrap_or_else(PoisonError::into_inner);
+        engine.movetime = self.movetime;
+        engine.best_move(position)
+    }
+}
+
+impl AiPlayer for SharedEngine {
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+        match self.best_move(position) {
+            Ok(col) => col,
+            Err(error) => {
+                println!("{}", error);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{BoardSize, WinRule};
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::external::{Command, ExternalEngine, Reply};
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_command_round_trip() {
+        let mut position = BitBoard::new(BoardSize::new(2, 3, 2)).unwrap();
+        position.play_sequence(&[1, 1, 0]).unwrap();
+        let command = Command::position(&position);
+        assert_eq!(command.to_string(), "position 2 3 2 -o-xx-");
+        let exact = Command::Position {
+            size: BoardSize::new(2, 3, 2),
+            win_rule: WinRule::Exact,
+            cells: "-o-xx-".to_string(),
+        };
+        assert_eq!(exact.to_string(), "position 2 3 2 -o-xx- exact");
+        for command in [
+            command,
+            exact,
+            Command::Hello,
+            Command::IsReady,
+            Command::Go {
+                movetime: Duration::from_millis(250),
+            },
+            Command::Quit,
+        ]
+        .iter()
+        {
+            assert_eq!(&command.to_string().parse::<Command>().unwrap(), command);
+        }
+        assert!("go movetime soon".parse::<Command>().is_err());
+        assert!("position 6 7".parse::<Command>().is_err());
+        assert!("position 2 3 2 -o-xx- sudden".parse::<Command>().is_err());
+    }
+
+    #[test]
+    pub fn test_reply_round_trip() {
+        for reply in [
+            Reply::Id {
+                name: "connect5-rust minimax".to_string(),
+            },
+            Reply::HelloOk,
+            Reply::ReadyOk,
+            Reply::Info("depth 4 score 12 pv 4 5".to_string()),
+            Reply::BestMove(Some(4)),
+            Reply::BestMove(None),
+        ]
+        .iter()
+        {
+            assert_eq!(&reply.to_string().parse::<Reply>().unwrap(), reply);
+        }
+        assert!("bestmove left".parse::<Reply>().is_err());
+        assert!("option name Hash".parse::<Reply>().is_err());
+    }
+
+    #[test]
+    pub fn test_engine_not_found() {
+        let position = BitBoard::new(BoardSize::default()).unwrap();
+        let mut engine = ExternalEngine::new("", Duration::from_millis(10));
+        assert!(engine
+            .best_move(&position)
+            .unwrap_err()
+            .contains("EXTERNAL_ENGINE"));
+        let mut engine = ExternalEngine::new("./no-such-engine", Duration::from_millis(10));
+        assert!(engine
+            .best_move(&position)
+            .unwrap_err()
+            .starts_with("Can't start the engine"));
+    }
+}
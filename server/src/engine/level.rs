@@ -0,0 +1,162 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::engine::bitboard::BitBoard;
+use crate::engine::AiPlayer;
+
+/// Strength of the computer opponent. Stored by name in `game_state.ai_level`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AiLevel {
+    Beginner,
+    #[default]
+    Intermediate,
+    Expert,
+}
+
+impl AiLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AiLevel::Beginner => "beginner",
+            AiLevel::Intermediate => "intermediate",
+            AiLevel::Expert => "expert",
+        }
+    }
+
+    /// Maximum search depth in plies.
+    pub fn max_depth(&self) -> usize {
+        match self {
+            AiLevel::Beginner => 2,
+            AiLevel::Intermediate => 6,
+            AiLevel::Expert => 42,
+        }
+    }
+
+    /// Thinking time per move.
+    pub fn time_budget(&self) -> Duration {
+        match self {
+            AiLevel::Beginner => Duration::from_millis(100),
+            AiLevel::Intermediate => Duration::from_millis(300),
+            AiLevel::Expert => Duration::from_secs(1),
+        }
+    }
+
+    /// Share of the moves played at random instead of the engine's choice.
+    pub fn mistake_rate(&self) -> f64 {
+        match self {
+            AiLevel::Beginner => 0.3,
+            AiLevel::Intermediate => 0.1,
+            AiLevel::Expert => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for AiLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AiLevel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "beginner" => Ok(AiLevel::Beginner),
+            "intermediate" => Ok(AiLevel::Intermediate),
+            "expert" => Ok(AiLevel::Expert),
+            _ => Err(format!("Unknown AI level: {}", name)),
+        }
+    }
+}
+
+/// Wraps a player and replaces a share of its moves with random legal moves.
+pub struct Handicapped {
+    player: Box<dyn AiPlayer>,
+    mistake_rate: f64,
+    rng: StdRng,
+}
+
+impl Handicapped {
+    pub fn new(player: Box<dyn AiPlayer>, mistake_rate: f64) -> Self {
+        Handicapped::with_rng(player, mistake_rate, StdRng::from_entropy())
+    }
+
+    /// Same as `new`, with a seeded random generator for reproducible games.
+    pub fn with_rng(player: Box<dyn AiPlayer>, mistake_rate: f64, rng: StdRng) -> Self {
+        Handicapped {
+            player,
+            mistake_rate,
+            rng,
+        }
+    }
+}
+
+impl AiPlayer for Handicapped {
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+        if self.mistake_rate > 0.0 && self.rng.gen_bool(self.mistake_rate.min(1.0)) {
+            return position.legal_moves().choose(&mut self.rng);
+        }
+        self.player.choose_move(position)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::BoardSize;
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::level::{AiLevel, Handicapped};
+    use crate::engine::AiPlayer;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Always plays the first column.
+    struct FirstColumn;
+
+    impl AiPlayer for FirstColumn {
+        fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+            position.legal_moves().next()
+        }
+    }
+
+    fn first_column_share(mistake_rate: f64) -> f64 {
+        let position = BitBoard::new(BoardSize::default()).unwrap();
+        let rng = StdRng::seed_from_u64(42);
+        let mut player = Handicapped::with_rng(Box::new(FirstColumn), mistake_rate, rng);
+        let first = (0..1000)
+            .filter(|_| player.choose_move(&position) == Some(0))
+            .count();
+        first as f64 / 1000.0
+    }
+
+    #[test]
+    pub fn test_mistake_rate() {
+        assert_eq!(first_column_share(0.0), 1.0);
+        // 9 columns: 70% of the moves plus a ninth of the random ones land on the first column
+        let share = first_column_share(0.3);
+        assert!(share > 0.66 && share < 0.80, "share: {}", share);
+        assert!(first_column_share(1.0) < 0.2);
+    }
+
+    #[test]
+    pub fn test_level_names() {
+        for level in &[AiLevel::Beginner, AiLevel::Intermediate, AiLevel::Expert] {
+            assert_eq!(level.name().parse::<AiLevel>().unwrap(), *level);
+        }
+        assert!("grandmaster".parse::<AiLevel>().is_err());
+    }
+
+    #[test]
+    pub fn test_levels_get_stronger() {
+        let levels = [AiLevel::Beginner, AiLevel::Intermediate, AiLevel::Expert];
+        for pair in levels.windows(2) {
+            assert!(pair[0].max_depth() < pair[1].max_depth());
+            assert!(pair[0].time_budget() < pair[1].time_budget());
+            assert!(pair[0].mistake_rate() > pair[1].mistake_rate());
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::engine::bitboard::BitBoard;
+use crate::engine::AiPlayer;
+
+/// Exploration constant of the UCT formula.
+const EXPLORATION: f64 = 1.41;
+
+/// Monte Carlo Tree Search with UCT selection and random playouts.
+///
+/// It needs no evaluation function, only the win check of the position, so it works unchanged
+/// on large boards and on variants where a heuristic is hard to write. The search stops after
+/// `max_iterations` playouts or when the time budget is used, whichever comes first.
+#[derive(Debug, Clone)]
+pub struct Mcts {
+    max_iterations: Option<usize>,
+    time_budget: Option<Duration>,
+    iterations: usize,
+    rng: StdRng,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    parent: Option<usize>,
+    /// column played to reach the node
+    col: usize,
+    /// player who played `col`
+    player: usize,
+    children: Vec<usize>,
+    untried: Vec<usize>,
+    /// winner if the game is over in this node, `Some(None)` for a draw
+    outcome: Option<Option<usize>>,
+    visits: u32,
+    /// sum of the results for `player`: 1 for a win, 0.5 for a draw
+    reward: f64,
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Mcts::new(None, Some(Duration::from_millis(500)))
+    }
+}
+
+impl Mcts {
+    /// At least one of the limits should be set, otherwise the search never stops.
+    pub fn new(max_iterations: Option<usize>, time_budget: Option<Duration>) -> Self {
+        Mcts {
+            max_iterations,
+            time_budget,
+            iterations: 0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Uses a seeded random generator, for reproducible searches.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Number of playouts of the last search.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Returns the most visited column and its win rate for the player to move.
+    pub fn search(&mut self, position: &BitBoard) -> Option<(usize, f64)> {
+        let moves = position.legal_moves().collect::<Vec<_>>();
+        if let Some(col) = moves.iter().find(|col| position.is_winning_move(**col)) {
+            return Some((*col, 1.0));
+        }
+        if moves.len() <= 1 {
+            return moves.first().map(|col| (*col, 0.5));
+        }
+
+        let deadline = self.time_budget.map(|budget| Instant::now() + budget);
+        let mut tree = vec![Node::new(None, 0, 1 - position.current_player(), moves)];
+        self.iterations = 0;
+        while self.max_iterations.is_none_or(|max| self.iterations < max)
+            && deadline.is_none_or(|deadline| Instant::now() < deadline)
+        {
+            self.iterate(&mut tree, position);
+            self.iterations += 1;
+        }
+
+        tree[0]
+            .children
+            .iter()
+            .map(|child| &tree[*child])
+            .max_by_key(|node| node.visits)
+            .map(|node| (node.col, node.reward / node.visits.max(1) as f64))
+    }
+
+    fn iterate(&mut self, tree: &mut Vec<Node>, root: &BitBoard) {
+        let mut position = root.clone();
+        let mut node = 0;
+
+        // selection
+        while tree[node].untried.is_empty() && tree[node].outcome.is_none() {
+            node = self.select_child(tree, node);
+            position.play(tree[node].col);
+        }
+
+        // expansion
+        if tree[node].outcome.is_none() {
+            let index = self.rng.gen_range(0..tree[node].untried.len());
+            let col = tree[node].untried.swap_remove(index);
+            let player = position.current_player();
+            let wins = position.is_winning_move(col);
+            position.play(col);
+            let mut child = Node::new(Some(node), col, player, position.legal_moves().collect());
+            if wins {
+                child.outcome = Some(Some(player));
+            } else if position.is_full() {
+                child.outcome = Some(None);
+            }
+            tree.push(child);
+            let child = tree.len() - 1;
+            tree[node].children.push(child);
+            node = child;
+        }
+
+        // simulation
+        let winner = match tree[node].outcome {
+            Some(outcome) => outcome,
+            None => self.playout(&mut position),
+        };
+
+        // back propagation
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut tree[index];
+            node.visits += 1;
+            node.reward += match winner {
+                Some(player) if player == node.player => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = node.parent;
+        }
+    }
+
+    fn select_child(&self, tree: &[Node], node: usize) -> usize {
+        let log_visits = (tree[node].visits.max(1) as f64).ln();
+        let uct = |child: &Node| {
+            let visits = child.visits.max(1) as f64;
+            child.reward / visits + EXPLORATION * (log_visits / visits).sqrt()
+        };
+        *tree[node]
+            .children
+            .iter()
+            .max_by(|a, b| uct(&tree[**a]).partial_cmp(&uct(&tree[**b])).unwrap())
+            .unwrap()
+    }
+
+    // plays random moves to the end of the game, returns the winner
+    fn playout(&mut self, position: &mut BitBoard) -> Option<usize> {
+        let mut moves = Vec::with_capacity(position.size().columns);
+        while !position.is_full() {
+            moves.clear();
+            moves.extend(position.legal_moves());
+            let col = *moves.choose(&mut self.rng).unwrap();
+            if position.is_winning_move(col) {
+                return Some(position.current_player());
+            }
+            position.play(col);
+        }
+        None
+    }
+}
+
+impl Node {
+    fn new(parent: Option<usize>, col: usize, player: usize, untried: Vec<usize>) -> Self {
+        Node {
+            parent,
+            col,
+            player,
+            children: vec![],
+            untried,
+            outcome: None,
+            visits: 0,
+            reward: 0.0,
+        }
+    }
+}
+
+impl AiPlayer for Mcts {
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+        self.search(position).map(|(col, _win_rate)| col)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::BoardSize;
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::mcts::Mcts;
+    use std::time::Duration;
+
+    fn position(size: BoardSize, moves: &[usize]) -> BitBoard {
+        let mut position = BitBoard::new(size).unwrap();
+        position.play_sequence(moves).unwrap();
+        position
+    }
+
+    #[test]
+    pub fn test_takes_the_win() {
+        let position = position(BoardSize::new(6, 7, 4), &[0, 6, 1, 6, 2, 5]);
+        let (col, win_rate) = Mcts::new(Some(100), None).search(&position).unwrap();
+        assert_eq!(col, 3);
+        assert_eq!(win_rate, 1.0);
+    }
+
+    #[test]
+    pub fn test_blocks_the_opponent() {
+        let position = position(BoardSize::new(6, 7, 4), &[0, 6, 1, 6, 2]);
+        let mut mcts = Mcts::new(Some(5000), None).with_seed(1);
+        assert_eq!(mcts.search(&position).unwrap().0, 3);
+    }
+
+    #[test]
+    pub fn test_iteration_limit() {
+        let position = position(BoardSize::default(), &[]);
+        let mut mcts = Mcts::new(Some(200), None).with_seed(1);
+        assert!(mcts.search(&position).is_some());
+        assert_eq!(mcts.iterations(), 200);
+    }
+
+    #[test]
+    pub fn test_time_budget() {
+        let position = position(BoardSize::new(15, 7, 5), &[]);
+        let mut mcts = Mcts::new(None, Some(Duration::from_millis(50)));
+        let start = std::time::Instant::now();
+        assert!(mcts.search(&position).is_some());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(mcts.iterations() > 0);
+    }
+
+    #[test]
+    pub fn test_same_seed_same_move() {
+        let position = position(BoardSize::default(), &[4, 4, 3]);
+        let first = Mcts::new(Some(500), None).with_seed(7).search(&position);
+        let second = Mcts::new(Some(500), None).with_seed(7).search(&position);
+        assert_eq!(first, second);
+    }
+}
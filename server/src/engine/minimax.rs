@@ -0,0 +1,388 @@
+use std::time::{Duration, Instant};
+
+use crate::engine::bitboard::BitBoard;
+use crate::engine::transposition::{Bound, Entry, TranspositionTable};
+use crate::engine::zobrist::Zobrist;
+use crate::engine::AiPlayer;
+
+/// Score of a won position. Wins found earlier in the game score higher.
+pub const WIN_SCORE: i32 = 1 << 28;
+
+/// Number of entries of the transposition table.
+const TABLE_SIZE: usize = 1 << 18;
+
+/// Negamax search with alpha-beta pruning, iterative deepening and a time budget.
+///
+/// Leaves are scored by counting the lines of `win_length` cells still open for each player,
+/// weighted by the number of pieces already on them. Results are kept in a transposition table,
+/// so positions reached through different move orders are searched once.
+#[derive(Debug, Clone)]
+pub struct Minimax {
+    max_depth: usize,
+    time_budget: Duration,
+    deadline: Option<Instant>,
+    nodes: u64,
+    lines: Vec<u128>,
+    zobrist: Option<Zobrist>,
+    table: TranspositionTable,
+}
+
+#[derive(Debug)]
+struct OutOfTime;
+
+impl Default for Minimax {
+    fn default() -> Self {
+        Minimax::new(42, Duration::from_millis(500))
+    }
+}
+
+impl Minimax {
+    pub fn new(max_depth: usize, time_budget: Duration) -> Self {
+        Minimax {
+            max_depth,
+            time_budget,
+            deadline: None,
+            nodes: 0,
+            lines: vec![],
+            zobrist: None,
+            table: TranspositionTable::new(TABLE_SIZE),
+        }
+    }
+
+    /// Number of positions visited by the last search.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Searches the position and returns the best column with its score, from the point of view
+    /// of the player to move. Returns `None` if there are no legal moves.
+    pub fn search(&mut self, position: &BitBoard) -> Option<(usize, i32)> {
+        let mut moves = move_order(position);
+        let mut best = (*moves.first()?, 0);
+        if let Some(col) = moves.iter().find(|col| position.is_winning_move(**col)) {
+            return Some((*col, win_score(position)));
+        }
+
+        let key = self.start(position);
+        let remaining = position.size().cell_count() - position.moves();
+        for depth in 1..=self.max_depth.min(remaining) {
+            match self.search_root(position, key, &moves, depth) {
+                Ok(result) => best = result,
+                Err(OutOfTime) => break,
+            }
+            if best.1.abs() > WIN_SCORE / 2 {
+                break;
+            }
+            // the next iteration starts with the best move so far
+            move_first(&mut moves, best.0);
+        }
+        Some(best)
+    }
+
+    /// Searches every legal move with a full window, so that the score of each move is exact for
+    /// the depth searched rather than a bound. Returns the scores for the player to move, in
+    /// `move_order`, and the depth of the last completed iteration.
+    pub fn analyse(&mut self, position: &BitBoard) -> (Vec<(usize, i32)>, usize) {
+        let moves = move_order(position);
+        let key = self.start(position);
+        let remaining = position.size().cell_count() - position.moves();
+        let mut result = (vec![], 0);
+        for depth in 1..=self.max_depth.min(remaining) {
+            let mut scores = vec![];
+            let mut position = position.clone();
+            for col in &moves {
+                if position.is_winning_move(*col) {
+                    scores.push((*col, win_score(&position)));
+                    continue;
+                }
+                let child_key = key ^ self.move_key(&position, *col);
+                position.play(*col);
+                let score =
+                    self.negamax(&mut position, child_key, depth - 1, -WIN_SCORE, WIN_SCORE);
+                position.unplay(*col);
+                match score {
+                    Ok(score) => scores.push((*col, -score)),
+                    Err(OutOfTime) => return result,
+                }
+            }
+            let decided = scores.iter().all(|(_, score)| score.abs() > WIN_SCORE / 2);
+            result = (scores, depth);
+            if decided {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Best line starting with `col`, as found by the last search of `position`.
+    pub fn principal_variation(
+        &self,
+        position: &BitBoard,
+        col: usize,
+        max_len: usize,
+    ) -> Vec<usize> {
+        let mut position = position.clone();
+        let mut key = self
+            .zobrist
+            .as_ref()
+            .map_or(0, |zobrist| zobrist.hash(&position));
+        let mut line = vec![];
+        let mut next = Some(col);
+        while let Some(col) = next.filter(|col| position.can_play(*col)) {
+            line.push(col);
+            if line.len() >= max_len || position.is_winning_move(col) {
+                break;
+            }
+            key ^= self.move_key(&position, col);
+            position.play(col);
+            if position.is_full() {
+                break;
+            }
+            next = match position
+                .legal_moves()
+                .find(|col| position.is_winning_move(*col))
+            {
+                Some(win) => Some(win),
+                None => self
+                    .table
+                    .probe(key)
+                    .and_then(|entry| entry.best_move)
+                    .map(|col| col as usize),
+            };
+        }
+        line
+    }
+
+    // resets the counters for a new search of `position`, returns its hash
+    fn start(&mut self, position: &BitBoard) -> u64 {
+        self.deadline = Some(Instant::now() + self.time_budget);
+        self.nodes = 0;
+        self.lines = position.line_masks();
+        let zobrist = Zobrist::new(position.size());
+        let key = zobrist.hash(position);
+        self.zobrist = Some(zobrist);
+        key
+    }
+
+    fn search_root(
+        &mut self,
+        position: &BitBoard,
+        key: u64,
+        moves: &[usize],
+        depth: usize,
+    ) -> Result<(usize, i32), OutOfTime> {
+        let mut position = position.clone();
+        let mut alpha = -WIN_SCORE;
+        let mut best = (moves[0], -WIN_SCORE);
+        for col in moves {
+            let child_key = key ^ self.move_key(&position, *col);
+            position.play(*col);
+            let result = self.negamax(&mut position, child_key, depth - 1, -WIN_SCORE, -alpha);
+            position.unplay(*col);
+            let score = -result?;
+            if score > best.1 {
+                best = (*col, score);
+            }
+            alpha = alpha.max(score);
+        }
+        Ok(best)
+    }
+
+    fn negamax(
+        &mut self,
+        position: &mut BitBoard,
+        key: u64,
+        depth: usize,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> Result<i32, OutOfTime> {
+        self.nodes += 1;
+        if self.nodes & 1023 == 0 && self.deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(OutOfTime);
+        }
+        if position.is_full() {
+            return Ok(0);
+        }
+        let mut moves = move_order(position);
+        if moves.iter().any(|col| position.is_winning_move(*col)) {
+            return Ok(win_score(position));
+        }
+        if depth == 0 {
+            return Ok(self.evaluate(position));
+        }
+
+        let original_alpha = alpha;
+        if let Some(entry) = self.table.probe(key) {
+            if entry.depth as usize >= depth {
+                match entry.bound {
+                    Bound::Exact => return Ok(entry.score),
+                    Bound::Lower => alpha = alpha.max(entry.score),
+                    Bound::Upper => beta = beta.min(entry.score),
+                }
+                if alpha >= beta {
+                    return Ok(entry.score);
+                }
+            }
+            if let Some(col) = entry.best_move {
+                move_first(&mut moves, col as usize);
+            }
+        }
+
+        let mut best = (-WIN_SCORE, None);
+        for col in moves {
+            let child_key = key ^ self.move_key(position, col);
+            position.play(col);
+            let result = self.negamax(position, child_key, depth - 1, -beta, -alpha);
+            position.unplay(col);
+            let score = -result?;
+            if score > best.0 {
+                best = (score, Some(col));
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best.0 <= original_alpha {
+            Bound::Upper
+        } else if best.0 >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.store(Entry {
+            key,
+            depth: depth as u8,
+            score: best.0,
+            bound,
+            best_move: best.1.map(|col| col as u8),
+        });
+        Ok(best.0)
+    }
+
+    fn move_key(&self, position: &BitBoard, col: usize) -> u64 {
+        self.zobrist
+            .as_ref()
+            .map_or(0, |zobrist| zobrist.move_key(position, col))
+    }
+
+    // heuristic score for the player to move
+    fn evaluate(&self, position: &BitBoard) -> i32 {
+        let player = position.current_player();
+        let mine = position.pieces(player);
+        let theirs = position.pieces(1 - player);
+        self.lines
+            .iter()
+            .map(|line| {
+                let own = (line & mine).count_ones() as i32;
+                let other = (line & theirs).count_ones() as i32;
+                match (own, other) {
+                    (0, 0) => 0,
+                    (_, 0) => own * own,
+                    (0, _) => -other * other,
+                    _ => 0,
+                }
+            })
+            .sum()
+    }
+}
+
+impl AiPlayer for Minimax {
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+        self.search(position).map(|(col, _score)| col)
+    }
+}
+
+/// Score of winning with the next move.
+fn win_score(position: &BitBoard) -> i32 {
+    WIN_SCORE - position.moves() as i32
+}
+
+/// Legal moves, center columns first: they take part in more lines and prune better.
+pub fn move_order(position: &BitBoard) -> Vec<usize> {
+    let center = (position.size().columns as i32 - 1) / 2;
+    let mut moves = position.legal_moves().collect::<Vec<_>>();
+    moves.sort_by_key(|col| ((*col as i32 - center).abs(), *col));
+    moves
+}
+
+fn move_first(moves: &mut [usize], col: usize) {
+    if let Some(index) = moves.iter().position(|m| *m == col) {
+        moves[..=index].rotate_right(1);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::BoardSize;
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::minimax::{move_order, Minimax, WIN_SCORE};
+    use std::time::Duration;
+
+    fn position(size: BoardSize, moves: &[usize]) -> BitBoard {
+        let mut position = BitBoard::new(size).unwrap();
+        position.play_sequence(moves).unwrap();
+        position
+    }
+
+    #[test]
+    pub fn test_move_order() {
+        let position = position(BoardSize::new(2, 7, 2), &[3, 3]);
+        assert_eq!(move_order(&position), vec![2, 4, 1, 5, 0, 6]);
+    }
+
+    #[test]
+    pub fn test_takes_the_win() {
+        let position = position(BoardSize::new(6, 7, 4), &[0, 6, 1, 6, 2, 5]);
+        let (col, score) = Minimax::default().search(&position).unwrap();
+        assert_eq!(col, 3);
+        assert!(score > WIN_SCORE / 2);
+    }
+
+    #[test]
+    pub fn test_blocks_the_opponent() {
+        let position = position(BoardSize::new(6, 7, 4), &[0, 6, 1, 6, 2]);
+        let (col, _score) = Minimax::default().search(&position).unwrap();
+        assert_eq!(col, 3);
+    }
+
+    #[test]
+    pub fn test_finds_forced_win() {
+        // X on 1, 2 and O on 6, 6: playing 3 threatens both 0 and 4
+        let position = position(BoardSize::new(6, 7, 4), &[1, 6, 2, 6]);
+        let (col, score) = Minimax::new(8, Duration::from_secs(10))
+            .search(&position)
+            .unwrap();
+        assert_eq!(col, 3);
+        assert!(score > WIN_SCORE / 2);
+    }
+
+    #[test]
+    pub fn test_respects_time_budget() {
+        let position = position(BoardSize::new(10, 11, 5), &[]);
+        let mut minimax = Minimax::new(100, Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        assert!(minimax.search(&position).is_some());
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(minimax.nodes() > 0);
+    }
+
+    #[test]
+    pub fn test_transposition_table_saves_work() {
+        let position = position(BoardSize::new(6, 7, 4), &[3, 3, 2]);
+        let mut minimax = Minimax::new(7, Duration::from_secs(30));
+        let first = minimax.search(&position).unwrap();
+        let first_nodes = minimax.nodes();
+        // the second search finds the results of the first one in the table
+        let second = minimax.search(&position).unwrap();
+        assert_eq!(first, second);
+        assert!(minimax.nodes() < first_nodes);
+    }
+
+    #[test]
+    pub fn test_full_board() {
+        let position = position(BoardSize::new(1, 2, 2), &[0, 1]);
+        assert_eq!(Minimax::default().search(&position), None);
+    }
+}
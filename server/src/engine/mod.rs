@@ -0,0 +1,87 @@
+//! Game engine used for search and analysis. Works on a bitboard copy of the position rather
+//! than on `Board`, which is only used to talk to the rest of the server.
+pub mod analysis;
+pub mod bitboard;
+pub mod book;
+pub mod external;
+pub mod level;
+pub mod mcts;
+pub mod minimax;
+pub mod tournament;
+pub mod transposition;
+pub mod zobrist;
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitboard::BitBoard;
+use book::BookPlayer;
+use external::SharedEngine;
+use level::{AiLevel, Handicapped};
+use mcts::Mcts;
+use minimax::Minimax;
+
+/// A computer player, choosing moves for the player to move.
+pub trait AiPlayer {
+    /// Zero based column to play, `None` if there are no legal moves.
+    fn choose_move(&mut self, position: &BitBoard) -> Option<usize>;
+}
+
+/// Engines the computer opponent can play with. Stored by name in `game_state.ai_engine`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AiEngine {
+    Minimax,
+    Mcts,
+    /// the engine process started by the `EXTERNAL_ENGINE` command
+    External,
+}
+
+impl AiEngine {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AiEngine::Minimax => "minimax",
+            AiEngine::Mcts => "mcts",
+            AiEngine::External => "external",
+        }
+    }
+
+    /// Checks that the engine can play, starting the external engine if it isn't running.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            AiEngine::External => SharedEngine::start().map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The engine at the given level. It opens from the opening book, if one is loaded.
+    pub fn player(&self, level: AiLevel) -> Box<dyn AiPlayer> {
+        let mut player: Box<dyn AiPlayer> = match self {
+            AiEngine::Minimax => Box::new(Minimax::new(level.max_depth(), level.time_budget())),
+            AiEngine::Mcts => Box::new(Mcts::new(None, Some(level.time_budget()))),
+            AiEngine::External => Box::new(SharedEngine::new(level.time_budget())),
+        };
+        if let Some(book) = book::global() {
+            player = Box::new(BookPlayer::new(book, player));
+        }
+        Box::new(Handicapped::new(player, level.mistake_rate()))
+    }
+}
+
+impl fmt::Display for AiEngine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AiEngine {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "minimax" => Ok(AiEngine::Minimax),
+            "mcts" => Ok(AiEngine::Mcts),
+            "external" => Ok(AiEngine::External),
+            _ => Err(format!("Unknown AI engine: {}", name)),
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! Engine against engine matches, to check that a change of an engine is an improvement. The
+//! match runner binary plays the games, this module scores them.
+use std::fmt;
+use std::str::FromStr;
+
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+use crate::board::{BoardSize, WinRule};
+use crate::engine::bitboard::BitBoard;
+use crate::engine::level::AiLevel;
+use crate::engine::{AiEngine, AiPlayer};
+
+/// Quantile of the normal distribution for the 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// An engine at a level, written `engine` or `engine:level`, e.g. `mcts:expert`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineConfig {
+    pub engine: AiEngine,
+    pub level: AiLevel,
+}
+
+impl EngineConfig {
+    pub fn player(&self) -> Box<dyn AiPlayer> {
+        self.engine.player(self.level)
+    }
+}
+
+impl fmt::Display for EngineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.engine, self.level)
+    }
+}
+
+impl FromStr for EngineConfig {
+    type Err = String;
+
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        let (engine, level) = match config.split_once(':') {
+            Some((engine, level)) => (engine, level.parse()?),
+            None => (config, AiLevel::default()),
+        };
+        Ok(EngineConfig {
+            engine: engine.parse()?,
+            level,
+        })
+    }
+}
+
+/// A random opening of `plies` moves that doesn't end the game. Shorter if the board fills up.
+pub fn random_opening(
+    size: BoardSize,
+    win_rule: WinRule,
+    plies: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<usize>, String> {
+    let mut position = BitBoard::new(size)?.with_win_rule(win_rule);
+    let mut opening = vec![];
+    while opening.len() < plies {
+        let safe = position
+            .legal_moves()
+            .filter(|col| !position.is_winning_move(*col))
+            .collect::<Vec<_>>();
+        let col = match safe.into_iter().choose(rng) {
+            Some(col) => col,
+            None => break,
+        };
+        position.play(col);
+        opening.push(col);
+        if position.is_full() {
+            break;
+        }
+    }
+    Ok(opening)
+}
+
+/// Plays the opening, then lets the players move in turn until the game ends. `players[0]`
+/// moves first. Returns the zero based columns of all the moves and the index of the winner,
+/// `None` for a draw.
+pub fn play_game(
+    players: &mut [Box<dyn AiPlayer>; 2],
+    size: BoardSize,
+    win_rule: WinRule,
+    opening: &[usize],
+) -> Result<(Vec<usize>, Option<usize>), String> {
+    let mut position = BitBoard::new(size)?.with_win_rule(win_rule);
+    let mut moves = vec![];
+    while !position.is_full() {
+        let player = moves.len() % 2;
+        let col = match opening.get(moves.len()) {
+            Some(col) => *col,
+            None => players[player]
+                .choose_move(&position)
+                .ok_or("The engine has no move to play")?,
+        };
+        if !position.can_play(col) {
+            return Err(format!("Illegal move {} after {:?}", col + 1, moves));
+        }
+        let wins = position.is_winning_move(col);
+        position.play(col);
+        moves.push(col);
+        if wins {
+            return Ok((moves, Some(player)));
+        }
+    }
+    Ok((moves, None))
+}
+
+/// Wins, draws and losses of an engine against another one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MatchResult {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchResult {
+    /// Counts a game, `won` is `None` for a draw.
+    pub fn add(&mut self, won: Option<bool>) {
+        match won {
+            Some(true) => self.wins += 1,
+            Some(false) => self.losses += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Average points per game, a draw counting half a point.
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// Variance of the points of a single game.
+    fn variance(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        let score = self.score();
+        let games = self.games() as f64;
+        (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games
+    }
+
+    /// Elo difference matching the score. Infinite if one engine won every game.
+    pub fn elo(&self) -> f64 {
+        elo(self.score())
+    }
+
+    /// Half the width of the 95% confidence interval of `elo`.
+    pub fn elo_error(&self) -> f64 {
+        if self.games() == 0 {
+            return f64::INFINITY;
+        }
+        let margin = Z_95 * (self.variance() / self.games() as f64).sqrt();
+        let error = (elo(self.score() + margin) - elo(self.score() - margin)) / 2.0;
+        if error.is_nan() {
+            f64::INFINITY
+        } else {
+            error
+        }
+    }
+
+    /// Log likelihood ratio of the engine being `elo1` rather than `elo0` stronger, with the
+    /// normal approximation of the game results. The variance counts an extra win and loss, so
+    /// that a one sided match doesn't end the test after a game.
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        let variance = MatchResult {
+            wins: self.wins + 1,
+            draws: self.draws,
+            losses: self.losses + 1,
+        }
+        .variance();
+        let (score0, score1) = (expected_score(elo0), expected_score(elo1));
+        (score1 - score0) * (2.0 * self.score() - score0 - score1) * self.games() as f64
+            / (2.0 * variance)
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "W/D/L {}/{}/{}, score {:.3}, Elo {:+.1} ± {:.1}",
+            self.wins,
+            self.draws,
+            self.losses,
+            self.score(),
+            self.elo(),
+            self.elo_error()
+        )
+    }
+}
+
+fn elo(score: f64) -> f64 {
+    let score = score.clamp(0.0, 1.0);
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Outcome of the sequential probability ratio test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SprtResult {
+    Continue,
+    /// the engine is not `elo1` stronger
+    AcceptH0,
+    /// the engine is `elo1` stronger rather than `elo0`
+    AcceptH1,
+}
+
+/// Sequential probability ratio test of `elo0` against `elo1`, stopping the match as soon as
+/// the results are conclusive. `alpha` and `beta` are the false positive and false negative
+/// rates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Self {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    /// Lower and upper bounds of the log likelihood ratio.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn test(&self, result: &MatchResult) -> SprtResult {
+        let llr = result.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtResult::AcceptH1
+        } else if llr <= lower {
+            SprtResult::AcceptH0
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{BoardSize, WinRule};
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::level::AiLevel;
+    use crate::engine::tournament::{
+        play_game, random_opening, EngineConfig, MatchResult, Sprt, SprtResult,
+    };
+    use crate::engine::{AiEngine, AiPlayer};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Always plays the first column.
+    struct FirstColumn;
+
+    impl AiPlayer for FirstColumn {
+        fn choose_move(&mut self, position: &BitBoard) -> Option<usize> {
+            position.legal_moves().next()
+        }
+    }
+
+    #[test]
+    pub fn test_engine_config() {
+        let config = "mcts:expert".parse::<EngineConfig>().unwrap();
+        assert_eq!(config.engine, AiEngine::Mcts);
+        assert_eq!(config.level, AiLevel::Expert);
+        assert_eq!(config.to_string(), "mcts:expert");
+        let config = "minimax".parse::<EngineConfig>().unwrap();
+        assert_eq!(config.level, AiLevel::Intermediate);
+        assert!("minimax:grandmaster".parse::<EngineConfig>().is_err());
+        assert!("alphabeta".parse::<EngineConfig>().is_err());
+    }
+
+    #[test]
+    pub fn test_random_opening() {
+        let size = BoardSize::new(6, 7, 4);
+        let mut rng = StdRng::seed_from_u64(3);
+        let opening = random_opening(size, WinRule::Freestyle, 4, &mut rng).unwrap();
+        assert_eq!(opening.len(), 4);
+        let mut position = BitBoard::new(size).unwrap();
+        position.play_sequence(&opening).unwrap();
+        assert_eq!(position.winner(), None);
+        assert_eq!(
+            random_opening(BoardSize::new(1, 2, 2), WinRule::Freestyle, 4, &mut rng)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    pub fn test_play_game() {
+        let size = BoardSize::new(6, 7, 4);
+        let mut players: [Box<dyn AiPlayer>; 2] = [Box::new(FirstColumn), Box::new(FirstColumn)];
+        // both fill the first free column, the first player connects 4 on the bottom row
+        let (moves, winner) = play_game(&mut players, size, WinRule::Freestyle, &[]).unwrap();
+        assert_eq!(winner, Some(0));
+        assert_eq!(moves.len(), 19);
+        let (moves, winner) = play_game(&mut players, size, WinRule::Freestyle, &[1]).unwrap();
+        assert_eq!(moves[0], 1);
+        assert!(winner.is_some());
+        let (moves, winner) = play_game(
+            &mut players,
+            BoardSize::new(1, 2, 2),
+            WinRule::Freestyle,
+            &[],
+        )
+        .unwrap();
+        assert_eq!((moves, winner), (vec![0, 1], None));
+    }
+
+    #[test]
+    pub fn test_match_result() {
+        let mut result = MatchResult::default();
+        assert_eq!(result.score(), 0.5);
+        for won in [Some(true), Some(true), None, Some(false)].iter() {
+            result.add(*won);
+        }
+        assert_eq!(result.games(), 4);
+        assert_eq!(result.score(), 0.625);
+        assert!((result.elo() - 88.7).abs() < 0.1);
+        assert!(result.elo_error() > result.elo());
+        assert!(result
+            .to_string()
+            .starts_with("W/D/L 2/1/1, score 0.625, Elo +88.7 ± "));
+
+        let all_wins = MatchResult {
+            wins: 3,
+            draws: 0,
+            losses: 0,
+        };
+        assert_eq!(all_wins.elo(), f64::INFINITY);
+        assert_eq!(all_wins.elo_error(), f64::INFINITY);
+        assert!(all_wins.llr(0.0, 10.0) > 0.0);
+        assert_eq!(MatchResult::default().llr(0.0, 10.0), 0.0);
+    }
+
+    #[test]
+    pub fn test_sprt() {
+        let sprt = Sprt::new(0.0, 20.0);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+
+        let even = MatchResult {
+            wins: 10,
+            draws: 10,
+            losses: 10,
+        };
+        assert_eq!(sprt.test(&even), SprtResult::Continue);
+        let stronger = MatchResult {
+            wins: 700,
+            draws: 200,
+            losses: 500,
+        };
+        assert_eq!(sprt.test(&stronger), SprtResult::AcceptH1);
+        let weaker = MatchResult {
+            wins: 500,
+            draws: 200,
+            losses: 700,
+        };
+        assert_eq!(sprt.test(&weaker), SprtResult::AcceptH0);
+    }
+}
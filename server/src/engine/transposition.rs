@@ -0,0 +1,114 @@
+/// Kind of score stored in the table. Alpha-beta only knows the exact score of a position if it
+/// fell inside the search window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// the score is at least this much
+    Lower,
+    /// the score is at most this much
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<u8>,
+}
+
+/// Fixed size table of search results indexed by Zobrist hash. A new entry replaces the one in
+/// its slot, unless that one holds a deeper search of the same position.
+#[derive(Debug, Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    /// `capacity` is rounded down to a power of two.
+    pub fn new(capacity: usize) -> Self {
+        let size = capacity.max(1).next_power_of_two();
+        let size = if size > capacity.max(1) {
+            size / 2
+        } else {
+            size
+        };
+        TranspositionTable {
+            entries: vec![None; size],
+            mask: size - 1,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.entries[self.index(key)].filter(|entry| entry.key == key)
+    }
+
+    pub fn store(&mut self, entry: Entry) {
+        let index = self.index(entry.key);
+        match self.entries[index] {
+            Some(old) if old.key == entry.key && old.depth > entry.depth => {}
+            _ => self.entries[index] = Some(entry),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & self.mask
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::engine::transposition::{Bound, Entry, TranspositionTable};
+
+    fn entry(key: u64, depth: u8, score: i32) -> Entry {
+        Entry {
+            key,
+            depth,
+            score,
+            bound: Bound::Exact,
+            best_move: Some(3),
+        }
+    }
+
+    #[test]
+    pub fn test_capacity() {
+        assert_eq!(TranspositionTable::new(1000).capacity(), 512);
+        assert_eq!(TranspositionTable::new(1024).capacity(), 1024);
+        assert_eq!(TranspositionTable::new(0).capacity(), 1);
+    }
+
+    #[test]
+    pub fn test_store_and_probe() {
+        let mut table = TranspositionTable::new(16);
+        table.store(entry(5, 3, 10));
+        assert_eq!(table.probe(5), Some(entry(5, 3, 10)));
+        // same slot, different position
+        assert_eq!(table.probe(21), None);
+
+        table.clear();
+        assert_eq!(table.probe(5), None);
+    }
+
+    #[test]
+    pub fn test_replacement() {
+        let mut table = TranspositionTable::new(16);
+        table.store(entry(5, 3, 10));
+        table.store(entry(5, 2, 20));
+        assert_eq!(table.probe(5).unwrap().score, 10);
+        table.store(entry(5, 4, 30));
+        assert_eq!(table.probe(5).unwrap().score, 30);
+        table.store(entry(21, 1, 40));
+        assert_eq!(table.probe(5), None);
+        assert_eq!(table.probe(21).unwrap().score, 40);
+    }
+}
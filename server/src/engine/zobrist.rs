@@ -0,0 +1,163 @@
+use crate::board::{Board, BoardSize, EMPTY_CELL};
+use crate::engine::bitboard::BitBoard;
+
+/// Zobrist keys of a board size: one random key per cell and player, plus one for the second
+/// player to move. Positions are hashed by xor-ing the keys of the occupied cells.
+///
+/// Keys are generated from a fixed seed mixed with the board size, so the same position always
+/// gets the same hash, across runs and processes. That makes the hash usable as a persistent
+/// position key, see `position_key`.
+#[derive(Debug, Clone)]
+pub struct Zobrist {
+    rows: usize,
+    cells: Vec<[u64; 2]>,
+    side: u64,
+}
+
+const SEED: u64 = 0x436f_6e6e_6563_7435;
+
+impl Zobrist {
+    pub fn new(size: BoardSize) -> Self {
+        let mut state = SEED
+            ^ (size.rows as u64)
+            ^ ((size.columns as u64) << 16)
+            ^ ((size.win_length as u64) << 32);
+        let cells = (0..size.cell_count())
+            .map(|_| [splitmix64(&mut state), splitmix64(&mut state)])
+            .collect();
+        Zobrist {
+            rows: size.rows,
+            cells,
+            side: splitmix64(&mut state),
+        }
+    }
+
+    /// Key of a piece of `player` on the cell. `row` counts from the bottom.
+    pub fn cell_key(&self, row: usize, col: usize, player: usize) -> u64 {
+        self.cells[col * self.rows + row][player]
+    }
+
+    /// Value to xor into the hash of `position` when the player to move plays the column.
+    pub fn move_key(&self, position: &BitBoard, col: usize) -> u64 {
+        let (row, col) = position.bit_cell(position.move_bit(col));
+        self.cell_key(row, col, position.current_player()) ^ self.side
+    }
+
+    pub fn hash(&self, position: &BitBoard) -> u64 {
+        let mut hash = if position.current_player() == 1 {
+            self.side
+        } else {
+            0
+        };
+        for player in 0..2 {
+            let mut pieces = position.pieces(player);
+            while pieces != 0 {
+                let bit = pieces & pieces.wrapping_neg();
+                let (row, col) = position.bit_cell(bit);
+                hash ^= self.cell_key(row, col, player);
+                pieces ^= bit;
+            }
+        }
+        hash
+    }
+
+    /// Hashes a `Board` of any supported size, `first_color` being the color of the player who
+    /// moved first. Any other piece belongs to the second player.
+    pub fn board_hash(&self, board: &Board, first_color: char) -> u64 {
+        let mut hash = 0;
+        let mut moves = 0;
+        for (row, cells) in board.cells().iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                if *cell == EMPTY_CELL {
+                    continue;
+                }
+                let player = if *cell == first_color { 0 } else { 1 };
+                hash ^= self.cell_key(board.rows() - 1 - row, col, player);
+                moves += 1;
+            }
+        }
+        if moves % 2 == 1 {
+            hash ^= self.side;
+        }
+        hash
+    }
+}
+
+/// Stable key of the position on the board, stored in `game_state.position_key`. Positions
+/// reached through different move orders get the same key.
+pub fn position_key(board: &Board, first_color: char) -> String {
+    let hash = Zobrist::new(board.size()).board_hash(board, first_color);
+    format!("{:016x}", hash)
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{Board, BoardSize};
+    use crate::engine::bitboard::BitBoard;
+    use crate::engine::zobrist::{position_key, Zobrist};
+
+    fn position(size: BoardSize, moves: &[usize]) -> BitBoard {
+        let mut position = BitBoard::new(size).unwrap();
+        position.play_sequence(moves).unwrap();
+        position
+    }
+
+    #[test]
+    pub fn test_keys_are_stable() {
+        let size = BoardSize::default();
+        let board = position(size, &[4, 4, 3]).to_board(['X', 'O']);
+        assert_eq!(position_key(&board, 'X'), position_key(&board, 'X'));
+        assert_eq!(position_key(&board, 'X'), "50b666b3c62feadb");
+        assert_ne!(
+            Zobrist::new(size).cell_key(0, 0, 0),
+            Zobrist::new(BoardSize::new(6, 7, 4)).cell_key(0, 0, 0)
+        );
+    }
+
+    #[test]
+    pub fn test_transpositions_share_the_hash() {
+        let size = BoardSize::new(6, 7, 4);
+        let zobrist = Zobrist::new(size);
+        let first = position(size, &[3, 2, 4, 5]);
+        let second = position(size, &[4, 5, 3, 2]);
+        let other = position(size, &[3, 2, 5, 4]);
+        assert_eq!(zobrist.hash(&first), zobrist.hash(&second));
+        assert_ne!(zobrist.hash(&first), zobrist.hash(&other));
+    }
+
+    #[test]
+    pub fn test_incremental_hash() {
+        let size = BoardSize::default();
+        let zobrist = Zobrist::new(size);
+        let mut position = BitBoard::new(size).unwrap();
+        let mut hash = zobrist.hash(&position);
+        for col in &[4, 4, 3, 8, 0, 4, 4] {
+            hash ^= zobrist.move_key(&position, *col);
+            position.play(*col);
+            assert_eq!(hash, zobrist.hash(&position));
+        }
+    }
+
+    #[test]
+    pub fn test_board_hash_matches_bitboard() {
+        let size = BoardSize::default();
+        let zobrist = Zobrist::new(size);
+        let position = position(size, &[4, 4, 3, 8, 0]);
+        let board = position.to_board(['R', 'Y']);
+        assert_eq!(zobrist.board_hash(&board, 'R'), zobrist.hash(&position));
+
+        // boards too big for the bitboard can still be hashed
+        let big = BoardSize::new(20, 20, 5);
+        let mut board = Board::new(big);
+        board.drop_piece(10, 'X').unwrap();
+        assert_eq!(position_key(&board, 'X').len(), 16);
+    }
+}
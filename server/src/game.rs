@@ -1,141 +1,656 @@
+use crate::board::WinRule;
+pub use crate::board::{Board, BoardSize, Cell};
 pub use crate::db;
+use crate::engine::analysis::{analyse, Analysis};
+pub use crate::engine::bitboard::BitBoard;
+use crate::engine::book::{self, BookMove, OpeningBook};
+pub use crate::engine::level::AiLevel;
+use crate::engine::zobrist::position_key;
+pub use crate::engine::AiEngine;
 pub use crate::models;
-pub use crate::utils;
+use crate::notation::Notation;
+use crate::projection;
+use crate::record::{GameRecord, GameResult, UNKNOWN_DATE};
+use crate::rules::{self, Outcome, Rules};
+pub use crate::rules::{Move, RuleSet};
 use diesel::SqliteConnection;
-use itertools::Itertools;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
 #[cfg(test)]
 use mocktopus::macros::*;
 
-//---------- Gameplay functions---------------------------------------------------------------------
-pub fn is_winner(board: &[Vec<char>]) -> bool {
-    let winning_seq_len = 5;
-    let rows = board;
-    let cols: &Vec<Vec<char>> = &get_columns(board);
-    let diags_left = &get_diagonals_left(board);
-    let diags_right = &get_diagonals_right(board);
-    let lines = vec![rows, cols, diags_left, diags_right];
-    let lines_flatten = lines.iter().flat_map(|it| it.iter());
-    for line in lines_flatten {
-        for (color, group) in &line.iter().group_by(|elt| **elt) {
-            let gr: Vec<&char> = group.collect();
-            // println!("{}, {:?}", color, gr);
-            if color != '-' && gr.len() >= winning_seq_len {
-                return true;
-            }
+/// Reasons for a move to be rejected. `code()` is sent to the client alongside the message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameError {
+    NotAPlayer,
+    NotYourTurn,
+    GameOver,
+    NotFound,
+    NotFinished,
+    /// the game has changed since the client loaded it
+    Conflict,
+    TakebackNotAllowed(String),
+    /// the position is being played in a rated game
+    AnalysisDisabled,
+    NoOpeningBook,
+    InvalidPosition(String),
+    InvalidMove(String),
+    InvalidRecord(String),
+    /// the game can't be created with these settings
+    InvalidGame(String),
+    Internal(String),
+}
+
+impl GameError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            GameError::NotAPlayer => "not_a_player",
+            GameError::NotYourTurn => "not_your_turn",
+            GameError::GameOver => "game_over",
+            GameError::NotFound => "not_found",
+            GameError::NotFinished => "game_not_finished",
+            GameError::Conflict => "version_conflict",
+            GameError::TakebackNotAllowed(_) => "takeback_not_allowed",
+            GameError::AnalysisDisabled => "analysis_disabled",
+            GameError::NoOpeningBook => "no_opening_book",
+            GameError::InvalidPosition(_) => "invalid_position",
+            GameError::InvalidMove(_) => "invalid_move",
+            GameError::InvalidRecord(_) => "invalid_record",
+            GameError::InvalidGame(_) => "invalid_game",
+            GameError::Internal(_) => "internal_error",
         }
     }
-    false
 }
 
-fn get_columns(board: &[Vec<char>]) -> Vec<Vec<char>> {
-    let mut cols: Vec<Vec<char>> = vec![vec![' '; board.len()]; board[0].len()];
-    for (row_idx, row) in board.iter().enumerate() {
-        for (col_idx, x) in row.iter().enumerate() {
-            cols[col_idx][row_idx] = *x;
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::NotAPlayer => write!(f, "You are not a player in this game"),
+            GameError::NotYourTurn => {
+                write!(f, "It is not your turn. Please, wait for the other user")
+            }
+            GameError::GameOver => write!(f, "This game is over"),
+            GameError::NotFound => write!(f, "There is no such game"),
+            GameError::NotFinished => write!(f, "This game is not finished yet"),
+            GameError::Conflict => write!(
+                f,
+                "The game has changed since it was loaded. Please, check the board and try again"
+            ),
+            GameError::TakebackNotAllowed(msg) => write!(f, "{}", msg),
+            GameError::AnalysisDisabled => {
+                write!(f, "This position is being played in a rated game")
+            }
+            GameError::NoOpeningBook => write!(f, "No opening book is loaded"),
+            GameError::InvalidPosition(msg) => write!(f, "Invalid position: {}", msg),
+            GameError::InvalidMove(msg) => write!(f, "{}", msg),
+            GameError::InvalidRecord(msg) => write!(f, "Invalid game record: {}", msg),
+            GameError::InvalidGame(msg) => write!(f, "{}", msg),
+            GameError::Internal(msg) => write!(f, "{}", msg),
         }
     }
-    cols
 }
 
-fn get_diagonals_left(board: &[Vec<char>]) -> Vec<Vec<char>> {
-    let h = board.len();
-    let w = board[0].len();
-    let mut diags: Vec<Vec<char>> = Vec::new();
-    for p in 0..(h + w - 1) {
-        let mut d: Vec<char> = Vec::new();
-        let lower_bound = (p as i8 - h as i8 + 1).max(0) as usize;
-        let higher_bound = (p as i8 + 1).min(w as i8) as usize;
-        for col in lower_bound..higher_bound {
-            let row = (h as i8 - p as i8 + col as i8 - 1) as usize;
-            d.push(board[row][col])
+impl From<diesel::result::Error> for GameError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::NotFound => GameError::NotFound,
+            error => GameError::Internal(error.to_string()),
         }
-        diags.push(d);
     }
-    diags
 }
 
-fn get_diagonals_right(board: &[Vec<char>]) -> Vec<Vec<char>> {
-    let h = board.len();
-    let w = board[0].len();
-    let mut diags: Vec<Vec<char>> = Vec::new();
-    for p in 0..(h + w - 1) {
-        let mut d: Vec<char> = Vec::new();
-        let lower_bound = (p as i8 - h as i8 + 1).max(0) as usize;
-        let higher_bound = (p as i8 + 1).min(w as i8) as usize;
-        for col in lower_bound..higher_bound {
-            let row = (p as i8 - col as i8) as usize;
-            d.push(board[row][col])
+/// Name the computer player is registered with.
+pub const AI_USER_NAME: &str = "Computer";
+
+/// Search time of `analyse_position`.
+const ANALYSIS_TIME: Duration = Duration::from_secs(1);
+
+//---------- Gameplay functions---------------------------------------------------------------------
+pub fn is_winner(board: &Board) -> bool {
+    board.winner().is_some()
+}
+
+/// Creates a new game of `user_id` with the rules and, if given, the computer as the other
+/// player. Everything is stored in one transaction, no game is left half configured. Fails with
+/// `GameError::InvalidGame` if the computer can't play these rules.
+#[cfg_attr(test, mockable)]
+pub fn new_game(
+    user_id: Uuid,
+    board_size: BoardSize,
+    win_rule: WinRule,
+    rule_set: RuleSet,
+    rated: bool,
+    ai_opponent: Option<(AiEngine, AiLevel)>,
+    conn: &SqliteConnection,
+) -> Result<Uuid, GameError> {
+    if ai_opponent.is_some() {
+        check_ai_rules(board_size, win_rule, rule_set)?;
+    }
+    conn.immediate_transaction(|| {
+        let ses_id =
+            db::create_new_session(&user_id, &board_size, conn).map_err(GameError::Internal)?;
+        if win_rule != WinRule::default() {
+            db::update_win_rule(&ses_id, win_rule, conn)?;
+        }
+        if rule_set != RuleSet::default() {
+            let initial_board = rule_set.rules().initial_board(board_size);
+            db::update_rules(&ses_id, rule_set.name(), &initial_board.to_string(), conn)?;
         }
-        diags.push(d);
+        if rated {
+            db::update_rated(&ses_id, true, conn)?;
+        }
+        if let Some((engine, level)) = ai_opponent {
+            add_ai_player(ses_id, user_id, engine, level, conn)?;
+        }
+        Ok(ses_id)
+    })
+}
+
+/// Checks that the computer can play a game with these rules. Its engines only drop pieces on
+/// boards that fit a `BitBoard` and don't know the renju restrictions.
+fn check_ai_rules(
+    board_size: BoardSize,
+    win_rule: WinRule,
+    rule_set: RuleSet,
+) -> Result<(), GameError> {
+    if rule_set != RuleSet::Standard {
+        return Err(GameError::InvalidGame(
+            "The computer only plays the standard rules".to_string(),
+        ));
     }
-    diags
+    if win_rule == WinRule::Renju {
+        return Err(GameError::InvalidGame(
+            "The computer doesn't play with the renju restrictions".to_string(),
+        ));
+    }
+    BitBoard::new(board_size).map_err(GameError::InvalidGame)?;
+    Ok(())
 }
 
+/// Plays the move in a transaction, following the rules of the game. If `version` is given, the
+/// move is rejected with `GameError::Conflict` unless the game is still at that version.
+///
+/// In games against the computer, the computer answers right after the move. The move is kept
+/// if the computer fails to answer, the answer is then played by the next `user_move` or
+/// `get_game_state` call.
 #[cfg_attr(test, mockable)]
 pub fn user_move(
     ses_id: Uuid,
     user_id: Uuid,
-    col_num: usize,
+    mv: Move,
+    version: Option<i32>,
     conn: &SqliteConnection,
-) -> Result<models::GameState, String> {
+) -> Result<models::GameState, GameError> {
     //TODO: needs refactoring. should not do db calls
-    db::get_board(&ses_id, conn).and_then(|s| {
-        let board = utils::str_to_arr(&s);
-        do_move(user_id, col_num, &board, conn).and_then(|new_board| {
-            let board_arr = utils::arr_to_str(&new_board);
-            let is_winner = is_winner(&new_board);
-            let game_over = is_winner;
-            db::update_game_state(&ses_id, &user_id, &board_arr, is_winner, game_over, conn)
-                .map_err(|err| err.to_string())
-                .map(|_| {
-                    //return the updated game state
-                    db::get_game_state(&ses_id, conn).unwrap()
-                })
-        })
+    play_pending_ai_move(&ses_id, conn)?;
+    conn.immediate_transaction(|| {
+        let game_state = db::get_game_state(&ses_id, conn)?;
+        if version.is_some_and(|version| version != game_state.version) {
+            return Err(GameError::Conflict);
+        }
+        check_turn(&game_state, &user_id)?;
+        play_move(&ses_id, &game_state, user_id, mv, conn)
+    })?;
+    play_pending_ai_move(&ses_id, conn)?;
+    //return the updated game state
+    Ok(db::get_game_state(&ses_id, conn)?)
+}
+
+/// The state of the game for one of its players, after playing the answer of the computer if
+/// it is missing. Others only read the game, they don't get the computer to move.
+#[cfg_attr(test, mockable)]
+pub fn get_game_state(
+    ses_id: &Uuid,
+    user_id: &Uuid,
+    conn: &SqliteConnection,
+) -> Result<models::GameState, GameError> {
+    if !db::get_game_state(ses_id, conn)?.is_seated(user_id) {
+        return Err(GameError::NotAPlayer);
+    }
+    play_pending_ai_move(ses_id, conn)?;
+    Ok(db::get_game_state(ses_id, conn)?)
+}
+
+/// Asks the other player to take back the last move, which has to be the move of `user_id`.
+#[cfg_attr(test, mockable)]
+pub fn request_takeback(
+    ses_id: Uuid,
+    user_id: Uuid,
+    conn: &SqliteConnection,
+) -> Result<models::GameState, GameError> {
+    conn.immediate_transaction(|| {
+        let game_state = db::get_game_state(&ses_id, conn)?;
+        if !game_state.is_seated(&user_id) {
+            return Err(GameError::NotAPlayer);
+        }
+        if game_state.ended {
+            return Err(GameError::GameOver);
+        }
+        if game_state.last_user_id != Some(user_id.to_string()) {
+            return Err(GameError::TakebackNotAllowed(
+                "You can only take back your move before the other player answers".to_string(),
+            ));
+        }
+        db::bump_version(&ses_id, game_state.version, conn)?;
+        db::update_takeback_request(&ses_id, Some(&user_id), conn)?;
+        Ok(db::get_game_state(&ses_id, conn)?)
     })
 }
 
-fn do_move(
+/// Answers the takeback request of the other player. If `accept` is true, the last move is
+/// removed from the moves and the game state is rebuilt from the remaining ones.
+#[cfg_attr(test, mockable)]
+pub fn answer_takeback(
+    ses_id: Uuid,
     user_id: Uuid,
-    col_num: usize,
-    board: &[Vec<char>],
+    accept: bool,
     conn: &SqliteConnection,
-) -> Result<Vec<Vec<char>>, String> {
-    if col_num < 1 || col_num > board[0].len() {
-        return Err(format!(
-            "There is no column with this number. Max column is: {}",
-            board[0].len()
-        ));
+) -> Result<models::GameState, GameError> {
+    conn.immediate_transaction(|| {
+        let game_state = db::get_game_state(&ses_id, conn)?;
+        if !game_state.is_seated(&user_id) {
+            return Err(GameError::NotAPlayer);
+        }
+        match &game_state.takeback_requested_by {
+            Some(requester) if *requester != user_id.to_string() => {}
+            _ => {
+                return Err(GameError::TakebackNotAllowed(
+                    "There is no takeback request to answer".to_string(),
+                ))
+            }
+        }
+        db::update_takeback_request(&ses_id, None, conn)?;
+        if accept {
+            db::delete_last_move(&ses_id, conn)?;
+            projection::rebuild_game_state(&ses_id, conn)
+        } else {
+            db::bump_version(&ses_id, game_state.version, conn)?;
+            Ok(db::get_game_state(&ses_id, conn)?)
+        }
+    })
+}
+
+/// Seats the computer as `user_2` of a new game, playing with the color the user did not pick.
+#[cfg_attr(test, mockable)]
+pub fn add_ai_player(
+    ses_id: Uuid,
+    user_id: Uuid,
+    engine: AiEngine,
+    level: AiLevel,
+    conn: &SqliteConnection,
+) -> Result<Uuid, GameError> {
+    let user_color = db::get_user_color(&user_id, conn)?;
+    let ai_color = if user_color == 'O' { "X" } else { "O" };
+    let ai_user_id =
+        db::create_new_user(AI_USER_NAME, ai_color, conn).map_err(GameError::Internal)?;
+    db::seat_ai_player(&ses_id, &ai_user_id, engine.name(), level.name(), conn)?;
+    Ok(ai_user_id)
+}
+
+/// Plays the move by the rules of the game and stores the new board. Returns true if the move
+/// ended the game. Fails with `GameError::Conflict` if the game has changed since `game_state`
+/// was read.
+fn play_move(
+    ses_id: &Uuid,
+    game_state: &models::GameState,
+    user_id: Uuid,
+    mv: Move,
+    conn: &SqliteConnection,
+) -> Result<bool, GameError> {
+    let rules = game_state.rule_set().map_err(GameError::Internal)?.rules();
+    if db::bump_version(ses_id, game_state.version, conn)? == 0 {
+        return Err(GameError::Conflict);
+    }
+    // answering with a move declines the takeback request
+    if game_state.takeback_requested_by.is_some() {
+        db::update_takeback_request(ses_id, None, conn)?;
+    }
+    let user_1 = parse_user_id(game_state.user_1.as_deref().unwrap_or_default())?;
+    let first_color = db::get_user_color(&user_1, conn)?;
+    let board = Board::parse(
+        game_state.board.as_deref().unwrap_or_default(),
+        game_state.board_size(),
+    )
+    .map_err(GameError::Internal)?
+    .with_win_rule(game_state.win_rule(), first_color);
+    let color = db::get_user_color(&user_id, conn)?;
+    let (new_board, (row, column)) = rules
+        .apply(&board, mv, color)
+        .map_err(GameError::InvalidMove)?;
+    let board_arr = new_board.to_string();
+    let mut outcome = rules.outcome(&new_board, (row, column), color);
+    if outcome.is_none() && rules.repetition_limit().is_some() {
+        let history = positions(ses_id, game_state, rules, conn)?;
+        outcome = rules::repetition_draw(rules, &history, &new_board);
     }
+    let is_winner = matches!(outcome, Some(Outcome::Win(_)));
+    let is_draw = outcome == Some(Outcome::Draw);
+    let game_over = outcome.is_some();
+    db::update_game_state(
+        ses_id, &user_id, &board_arr, is_winner, is_draw, game_over, conn,
+    )?;
+    if let Some(Outcome::Win(line)) = outcome {
+        let cells = line.cell_indices(new_board.columns());
+        db::update_winning_line(ses_id, line.color, &cells, conn)?;
+    }
+    db::update_position_key(ses_id, &position_key(&new_board, first_color), conn)?;
+    let move_list = format!("{} {}", game_state.move_list, mv);
+    db::update_move_list(ses_id, move_list.trim_start(), conn)?;
+    db::insert_move(
+        &models::NewMove {
+            game_id: ses_id.to_string(),
+            ply: move_list.split_whitespace().count() as i32,
+            user_id: user_id.to_string(),
+            col_num: column as i32 + 1,
+            row_num: (new_board.rows() - row) as i32,
+            created_at: None,
+            kind: mv.kind().to_string(),
+        },
+        conn,
+    )?;
+    Ok(game_over)
+}
 
-    let y_curr = board
+/// Boards of the game so far, starting with the initial board, replayed from the moves.
+fn positions(
+    ses_id: &Uuid,
+    game_state: &models::GameState,
+    rules: &dyn Rules,
+    conn: &SqliteConnection,
+) -> Result<Vec<Board>, GameError> {
+    let colors = projection::player_colors(game_state, conn)?;
+    let moves = db::get_moves(ses_id, conn)?
         .iter()
-        .enumerate()
-        .filter(|(_, row)| row[col_num - 1] == '-')
-        .map(|(y, _)| y)
-        .max();
-    match y_curr {
-        Some(row_num) => {
-            let color: char = db::get_user_color(&user_id, conn).unwrap();
-            let mut new_board = board.to_owned();
-            new_board[row_num][col_num - 1] = color;
-            Ok(new_board)
+        .map(|m| Move::from_kind(&m.kind, m.row_num as usize, m.col_num as usize))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(GameError::Internal)?;
+    let replay = rules::replay(
+        rules,
+        game_state.board_size(),
+        game_state.win_rule(),
+        &moves,
+        colors,
+    )
+    .map_err(GameError::Internal)?;
+    Ok(replay.positions)
+}
+
+/// Lets the computer answer if it is its turn. A failing engine doesn't fail the call, the error
+/// is logged and the answer is tried again on the next call.
+fn play_pending_ai_move(ses_id: &Uuid, conn: &SqliteConnection) -> Result<(), GameError> {
+    let game_state = db::get_game_state(ses_id, conn)?;
+    if game_state.is_ai_turn() {
+        if let Err(error) = ai_move(ses_id, &game_state, conn) {
+            println!("The computer didn't answer in game {}: {}", ses_id, error);
         }
-        None => Err("This column is full. Please, try another move".to_owned()),
+    }
+    Ok(())
+}
+
+/// Lets the computer, seated as `user_2`, answer the last move.
+fn ai_move(
+    ses_id: &Uuid,
+    game_state: &models::GameState,
+    conn: &SqliteConnection,
+) -> Result<(), GameError> {
+    let engine = game_state
+        .ai_engine
+        .as_deref()
+        .unwrap_or_default()
+        .parse::<AiEngine>()
+        .map_err(GameError::Internal)?;
+    // games created before levels were added play at the default level
+    let level = match &game_state.ai_level {
+        Some(level) => level.parse::<AiLevel>().map_err(GameError::Internal)?,
+        None => AiLevel::default(),
+    };
+    let (user_1, ai_user) = match (&game_state.user_1, &game_state.user_2) {
+        (Some(user_1), Some(user_2)) => (parse_user_id(user_1)?, parse_user_id(user_2)?),
+        _ => {
+            return Err(GameError::Internal(
+                "The computer is not seated".to_string(),
+            ))
+        }
+    };
+    let colors = [
+        db::get_user_color(&user_1, conn)?,
+        db::get_user_color(&ai_user, conn)?,
+    ];
+    let position = BitBoard::from_board_str(
+        game_state.board.as_deref().unwrap_or_default(),
+        game_state.board_size(),
+        colors,
+    )
+    .map(|position| position.with_win_rule(game_state.win_rule()))
+    .map_err(GameError::Internal)?;
+    let column = choose_ai_move(engine, level, &position)?;
+    // the search runs outside of the transaction, the move is dropped if the game has changed
+    conn.immediate_transaction(|| {
+        play_move(ses_id, game_state, ai_user, Move::Drop(column + 1), conn)
+    })?;
+    Ok(())
+}
+
+/// Zero based column the computer plays in the position.
+#[cfg_attr(test, mockable)]
+fn choose_ai_move(
+    engine: AiEngine,
+    level: AiLevel,
+    position: &BitBoard,
+) -> Result<usize, GameError> {
+    engine
+        .player(level)
+        .choose_move(position)
+        .ok_or_else(|| GameError::Internal("The computer has no move to play".to_string()))
+}
+
+/// Evaluates every legal move of the position, `colors` being the colors of the first and the
+/// second player. Refused while the position is on the board of a rated game that is not over.
+#[cfg_attr(test, mockable)]
+pub fn analyse_position(
+    board: &Board,
+    colors: [char; 2],
+    conn: &SqliteConnection,
+) -> Result<Analysis, GameError> {
+    let position = BitBoard::from_board(board, colors).map_err(GameError::InvalidPosition)?;
+    if position.winner().is_some() {
+        return Err(GameError::InvalidPosition(
+            "The game is already won".to_string(),
+        ));
+    }
+    let key = position_key(board, colors[0]);
+    let live_rated = db::find_games_by_position_key(&key, conn)?
+        .iter()
+        .any(|game_state| game_state.rated && !game_state.ended);
+    if live_rated {
+        return Err(GameError::AnalysisDisabled);
+    }
+    let mut analysis = analyse(&position, ANALYSIS_TIME);
+    if let Some(book) = opening_book() {
+        analysis.book = book.moves(&position);
+    }
+    Ok(analysis)
+}
+
+/// The opening book loaded from `book::BOOK_PATH_VAR`, if any.
+#[cfg_attr(test, mockable)]
+pub fn opening_book() -> Option<Arc<OpeningBook>> {
+    book::global()
+}
+
+/// Moves of the opening book for the position, `colors` being the colors of the first and the
+/// second player.
+#[cfg_attr(test, mockable)]
+pub fn book_moves(board: &Board, colors: [char; 2]) -> Result<Vec<BookMove>, GameError> {
+    let position = BitBoard::from_board(board, colors).map_err(GameError::InvalidPosition)?;
+    match opening_book() {
+        Some(book) => Ok(book.moves(&position)),
+        None => Err(GameError::NoOpeningBook),
+    }
+}
+
+/// Record of a finished game, with the moves taken from `game_state.move_list`.
+#[cfg_attr(test, mockable)]
+pub fn export_game(ses_id: Uuid, conn: &SqliteConnection) -> Result<GameRecord, GameError> {
+    let game_state = db::get_game_state(&ses_id, conn)?;
+    if !game_state.ended {
+        return Err(GameError::NotFinished);
+    }
+    // the record format has no notation for the moves of the other rule sets
+    if game_state.rule_set() != Ok(RuleSet::Standard) {
+        return Err(GameError::InvalidRecord(format!(
+            "Games with the {} rules can't be exported",
+            game_state.rules
+        )));
+    }
+    let mut players = vec![];
+    let mut colors = vec![];
+    for user_id in &[&game_state.user_1, &game_state.user_2] {
+        let user_id = parse_user_id(user_id.as_deref().unwrap_or_default())?;
+        let user = db::get_user(&user_id, conn)?;
+        colors.push(user.user_color.chars().next().unwrap_or_default());
+        players.push(user.user_name);
+    }
+    let colors = [colors[0], colors[1]];
+    let notation = Notation::from_move_list(
+        game_state.board_size(),
+        game_state.win_rule(),
+        &game_state.move_list,
+    )
+    .map_err(GameError::Internal)?;
+    let replay = notation.replay(colors).map_err(GameError::Internal)?;
+    // games finished before the moves were recorded
+    if Some(replay.board.to_string()) != game_state.board {
+        return Err(GameError::Internal(
+            "The moves of this game are not available".to_string(),
+        ));
+    }
+    // the game is dated by its first move
+    let date = match db::get_moves(&ses_id, conn)?.first() {
+        Some(first) => first.created_at[..10].replace('-', "."),
+        None => UNKNOWN_DATE.to_string(),
+    };
+    Ok(GameRecord {
+        players: [players[0].clone(), players[1].clone()],
+        colors,
+        date,
+        result: GameResult::of(&replay, colors[0]),
+        notation,
+    })
+}
+
+/// Replays a recorded game and stores it as a new game. The players are registered as new
+/// users. Nothing is stored if the import fails.
+#[cfg_attr(test, mockable)]
+pub fn import_game(record: &GameRecord, conn: &SqliteConnection) -> Result<Uuid, GameError> {
+    let replay = record.validate().map_err(GameError::InvalidRecord)?;
+    conn.immediate_transaction(|| {
+        let mut users = vec![];
+        for (name, color) in record.players.iter().zip(&record.colors) {
+            let user_id =
+                db::create_new_user(name, &color.to_string(), conn).map_err(GameError::Internal)?;
+            users.push(user_id);
+        }
+        let ses_id = db::create_new_session(&users[0], &record.notation.size, conn)
+            .map_err(GameError::Internal)?;
+        if record.notation.win_rule != WinRule::default() {
+            db::update_win_rule(&ses_id, record.notation.win_rule, conn)?;
+        }
+        db::join_game_session(&ses_id, &users[1], conn)?;
+
+        let game_state = record
+            .notation
+            .to_game_state(&ses_id, &users[0], &users[1], record.colors)
+            .map_err(GameError::InvalidRecord)?;
+        // keep the date of the record, moves without a date are stamped with the import time
+        let created_at = match record.date.as_str() {
+            UNKNOWN_DATE => None,
+            date => Some(format!("{} 00:00:00", date.replace('.', "-"))),
+        };
+        for (index, (row, col)) in replay.cells.iter().enumerate() {
+            db::insert_move(
+                &models::NewMove {
+                    game_id: ses_id.to_string(),
+                    ply: index as i32 + 1,
+                    user_id: users[index % 2].to_string(),
+                    col_num: *col as i32 + 1,
+                    row_num: (replay.board.rows() - row) as i32,
+                    created_at: created_at.clone(),
+                    kind: Move::Drop(*col + 1).kind().to_string(),
+                },
+                conn,
+            )?;
+        }
+        if let Some(last_user_id) = &game_state.last_user_id {
+            db::update_game_state(
+                &ses_id,
+                &parse_user_id(last_user_id)?,
+                game_state.board.as_deref().unwrap_or_default(),
+                game_state.winner,
+                game_state.draw,
+                game_state.ended,
+                conn,
+            )?;
+        }
+        if let (Some(color), Some(cells)) = (&game_state.winner_color, &game_state.winning_cells) {
+            let color = color.chars().next().unwrap_or_default();
+            db::update_winning_line(&ses_id, color, cells, conn)?;
+        }
+        if let Some(key) = &game_state.position_key {
+            db::update_position_key(&ses_id, key, conn)?;
+        }
+        db::update_move_list(&ses_id, &game_state.move_list, conn)?;
+        Ok(ses_id)
+    })
+}
+
+fn parse_user_id(user_id: &str) -> Result<Uuid, GameError> {
+    Uuid::parse_str(user_id).map_err(|error| GameError::Internal(error.to_string()))
+}
+
+/// Checks that `user_id` is seated in the game, the game is still running and that it is
+/// `user_id`'s turn. `user_1` moves first.
+pub fn check_turn(game_state: &models::GameState, user_id: &Uuid) -> Result<(), GameError> {
+    if !game_state.is_seated(user_id) {
+        return Err(GameError::NotAPlayer);
+    }
+    if game_state.ended {
+        return Err(GameError::GameOver);
+    }
+    let user_id = user_id.to_string();
+    match &game_state.last_user_id {
+        Some(last_user_id) if last_user_id == &user_id => Err(GameError::NotYourTurn),
+        None if game_state.user_1.as_ref() != Some(&user_id) => Err(GameError::NotYourTurn),
+        _ => Ok(()),
     }
 }
 
 #[cfg(test)]
 pub mod tests {
+    use crate::board::WinRule;
     use crate::db;
-    use crate::game::{do_move, get_diagonals_left, get_diagonals_right, is_winner, user_move};
-    use crate::utils;
+    use crate::game::{
+        add_ai_player, is_winner, user_move, AiEngine, AiLevel, Board, BoardSize, GameError, Move,
+    };
+    use crate::game::{
+        analyse_position, answer_takeback, export_game, import_game, request_takeback,
+    };
+    use crate::game::{book_moves, choose_ai_move, get_game_state, opening_book};
+    use crate::game::{new_game, BookMove, OpeningBook, RuleSet};
+    use crate::notation::Notation;
+    use crate::projection;
+    use crate::record::{GameRecord, GameResult};
     use db::create_conn_pool;
+    use diesel::SqliteConnection;
     use itertools::Itertools;
     use mocktopus::mocking::*;
+    use std::cell::Cell;
     use std::ops::Deref;
+    use std::rc::Rc;
+    use std::sync::Arc;
     use uuid::Uuid;
 
     #[test]
@@ -148,7 +663,7 @@ pub mod tests {
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
         ];
-        assert!(!is_winner(&board));
+        assert!(!is_winner(&Board::from_cells(board, 5)));
 
         board = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -158,7 +673,7 @@ pub mod tests {
             vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
             vec!['-', '-', '-', 'X', 'X', 'X', '-', '-', '-'],
         ];
-        assert!(!is_winner(&board));
+        assert!(!is_winner(&Board::from_cells(board, 5)));
 
         board = vec![
             vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
@@ -168,7 +683,7 @@ pub mod tests {
             vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
             vec!['-', '-', '-', 'X', 'X', 'X', '-', '-', '-'],
         ];
-        assert!(is_winner(&board));
+        assert!(is_winner(&Board::from_cells(board, 5)));
 
         board = vec![
             vec!['-', '-', '-', '-', '-', 'O', '-', '-', '-'],
@@ -178,7 +693,7 @@ pub mod tests {
             vec!['-', '-', '-', '-', 'X', 'O', '-', '-', '-'],
             vec!['-', '-', '-', 'X', 'X', 'X', '-', '-', '-'],
         ];
-        assert!(is_winner(&board));
+        assert!(is_winner(&Board::from_cells(board, 5)));
 
         board = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -188,7 +703,7 @@ pub mod tests {
             vec!['-', '-', '-', '-', 'X', 'O', '-', '-', '-'],
             vec!['-', '-', '-', 'X', 'X', 'X', '-', '-', '-'],
         ];
-        assert!(is_winner(&board));
+        assert!(is_winner(&Board::from_cells(board, 5)));
 
         board = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -198,7 +713,7 @@ pub mod tests {
             vec!['-', 'X', 'X', 'X', 'X', 'X', 'X', '-', '-'],
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
         ];
-        assert!(is_winner(&board));
+        assert!(is_winner(&Board::from_cells(board, 5)));
 
         board = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -208,7 +723,7 @@ pub mod tests {
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
             vec!['X', 'X', '-', 'X', 'X', 'X', '-', 'X', 'X'],
         ];
-        assert!(!is_winner(&board));
+        assert!(!is_winner(&Board::from_cells(board, 5)));
 
         let target_board = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -219,224 +734,796 @@ pub mod tests {
             vec!['-', 'X', 'X', '-', '-', '-', '-', '-', '-'],
         ];
 
-        assert!(!is_winner(&target_board));
+        assert!(!is_winner(&Board::from_cells(target_board, 5)));
     }
 
     #[test]
-    pub fn test_get_diagonals_left() {
-        let board = vec![
-            vec!['1', '2', '3', '4'],
-            vec!['A', 'B', 'C', '4'],
-            vec!['W', 'X', 'Y', 'Z'],
-            vec!['9', '8', '7', '6'],
-        ];
+    pub fn test_group_by() {
+        let data = vec![1, 3, -2, -2, 1, 0, 1, 2];
+        for (key, group) in &data.into_iter().group_by(|elt| *elt >= 0) {
+            let g = group.collect::<Vec<i32>>();
+            println!("{}, {:?}", key, g);
+        }
+    }
 
-        let target = vec![
-            vec!['9'],
-            vec!['W', '8'],
-            vec!['A', 'X', '7'],
-            vec!['1', 'B', 'Y', '6'],
-            vec!['2', 'C', 'Z'],
-            vec!['3', '4'],
-            vec!['4'],
-        ];
+    fn board(notation: &str) -> Board {
+        notation.parse::<Notation>().unwrap().board().unwrap()
+    }
+
+    #[test]
+    pub fn test_user_move() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let new_session_id =
+            db::create_new_session(&user_id, &BoardSize::default(), conn.deref()).unwrap();
+        let user_2_id = db::create_new_user("test-user-2", "O", conn.deref()).unwrap();
+        db::join_game_session(&new_session_id, &user_2_id, conn.deref()).unwrap();
+
+        let board_str = board("6x9/5 3 9").to_string();
+        let res = db::update_game_state(
+            &new_session_id,
+            &user_2_id,
+            &board_str,
+            false,
+            false,
+            false,
+            conn.deref(),
+        );
 
-        let res = get_diagonals_left(&board);
+        assert_eq!(res.unwrap(), 1);
+
+        let tmp_board = db::get_board(&new_session_id, conn.deref()).unwrap();
+        assert_eq!(tmp_board, board_str);
 
-        assert_eq!(res, target)
+        let new_state =
+            user_move(new_session_id, user_id, Move::Drop(2), None, conn.deref()).unwrap();
+        assert_eq!(new_state.board.unwrap(), board("6x9/5 3 9 2").to_string());
+        assert_eq!(new_state.last_user_id.unwrap(), user_id.to_string());
+        assert_eq!(new_state.winner, false);
     }
 
     #[test]
-    pub fn test_get_diagonals_right() {
-        let board = vec![
-            vec!['1', '2', '3', '4'],
-            vec!['A', 'B', 'C', '4'],
-            vec!['W', 'X', 'Y', 'Z'],
-            vec!['9', '8', '7', '6'],
-        ];
+    pub fn test_user_move_win_column() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let new_session_id =
+            db::create_new_session(&user_id, &BoardSize::default(), conn.deref()).unwrap();
+        let user_2_id = db::create_new_user("test-user-2", "O", conn.deref()).unwrap();
+        db::join_game_session(&new_session_id, &user_2_id, conn.deref()).unwrap();
+        let _ = db::update_game_state(
+            &new_session_id,
+            &user_2_id,
+            &board("6x9/5 2 1 2 1 2 1 2 1").to_string(),
+            false,
+            false,
+            false,
+            conn.deref(),
+        );
 
-        let target = vec![
-            vec!['1'],
-            vec!['A', '2'],
-            vec!['W', 'B', '3'],
-            vec!['9', 'X', 'C', '4'],
-            vec!['8', 'Y', '4'],
-            vec!['7', 'Z'],
-            vec!['6'],
-        ];
+        let new_state =
+            user_move(new_session_id, user_id, Move::Drop(2), None, conn.deref()).unwrap();
+        assert_eq!(
+            new_state.board.unwrap(),
+            board("6x9/5 2 1 2 1 2 1 2 1 2").to_string()
+        );
+        assert_eq!(new_state.last_user_id.unwrap(), user_id.to_string());
+        assert_eq!(new_state.winner, true);
+        assert_eq!(new_state.ended, true);
+        assert_eq!(new_state.winner_color.unwrap(), "X");
+        assert_eq!(new_state.winning_cells.unwrap(), "10,19,28,37,46");
+    }
+
+    #[test]
+    pub fn test_user_move_connect_4() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let board_size = BoardSize::new(6, 7, 4);
+        let new_session_id = db::create_new_session(&user_id, &board_size, conn.deref()).unwrap();
+        let user_2_id = db::create_new_user("test-user-2", "O", conn.deref()).unwrap();
+        db::join_game_session(&new_session_id, &user_2_id, conn.deref()).unwrap();
+        let board = Board::parse(&("-".repeat(39) + "XXX"), board_size).unwrap();
+        let _ = db::update_game_state(
+            &new_session_id,
+            &user_2_id,
+            &board.to_string(),
+            false,
+            false,
+            false,
+            conn.deref(),
+        );
 
-        let res = get_diagonals_right(&board);
+        let new_state =
+            user_move(new_session_id, user_id, Move::Drop(4), None, conn.deref()).unwrap();
+        assert_eq!(new_state.board.unwrap(), "-".repeat(38) + "XXXX");
+        assert_eq!(new_state.winner, true);
+        assert_eq!(new_state.ended, true);
+    }
 
-        assert_eq!(res, target)
+    fn new_two_player_game(conn: &SqliteConnection) -> (Uuid, Uuid, Uuid) {
+        let user_1 = db::create_new_user("test-user-1", "X", conn).unwrap();
+        let user_2 = db::create_new_user("test-user-2", "O", conn).unwrap();
+        let session_id = db::create_new_session(&user_1, &BoardSize::default(), conn).unwrap();
+        db::join_game_session(&session_id, &user_2, conn).unwrap();
+        (session_id, user_1, user_2)
     }
 
     #[test]
-    pub fn test_group_by() {
-        let data = vec![1, 3, -2, -2, 1, 0, 1, 2];
-        for (key, group) in &data.into_iter().group_by(|elt| *elt >= 0) {
-            let g = group.collect::<Vec<i32>>();
-            println!("{}, {:?}", key, g);
+    pub fn test_user_move_user_1_moves_first() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+
+        let res = user_move(session_id, user_2, Move::Drop(1), None, conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::NotYourTurn);
+
+        let new_state = user_move(session_id, user_1, Move::Drop(1), None, conn.deref()).unwrap();
+        assert_eq!(new_state.last_user_id.unwrap(), user_1.to_string());
+    }
+
+    #[test]
+    pub fn test_user_move_not_your_turn() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+
+        user_move(session_id, user_1, Move::Drop(1), None, conn.deref()).unwrap();
+        let res = user_move(session_id, user_1, Move::Drop(2), None, conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::NotYourTurn);
+
+        let new_state = user_move(session_id, user_2, Move::Drop(2), None, conn.deref()).unwrap();
+        assert_eq!(new_state.last_user_id.unwrap(), user_2.to_string());
+    }
+
+    #[test]
+    pub fn test_user_move_popout() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        let res = user_move(session_id, user_1, Move::Pop(1), None, conn.deref());
+        assert!(matches!(res, Err(GameError::InvalidMove(_))));
+
+        let board = Board::new(BoardSize::default()).to_string();
+        db::update_rules(&session_id, "popout", &board, conn.deref()).unwrap();
+        let res = user_move(session_id, user_1, Move::Pop(1), None, conn.deref());
+        assert!(matches!(res, Err(GameError::InvalidMove(_))));
+        // the empty board with X to move comes back for the third time after 8 moves
+        let players = [user_1, user_2];
+        let cycle = [Move::Drop(1), Move::Drop(2), Move::Pop(1), Move::Pop(2)];
+        for (ply, mv) in cycle.iter().cycle().take(7).enumerate() {
+            let state = user_move(session_id, players[ply % 2], *mv, None, conn.deref()).unwrap();
+            assert!(!state.ended);
+        }
+        let state = user_move(session_id, user_2, Move::Pop(2), None, conn.deref()).unwrap();
+        assert!(state.ended && state.draw);
+        assert_eq!(state.move_list, "1 2 p1 p2 1 2 p1 p2");
+        assert_eq!(
+            db::get_moves(&session_id, conn.deref()).unwrap()[2].kind,
+            "pop"
+        );
+        assert!(projection::check_game(&session_id, conn.deref())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    pub fn test_user_move_gomoku() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        let res = user_move(session_id, user_1, Move::Place(6, 1), None, conn.deref());
+        assert!(matches!(res, Err(GameError::InvalidMove(_))));
+
+        let board = Board::new(BoardSize::default()).to_string();
+        db::update_rules(&session_id, "gomoku", &board, conn.deref()).unwrap();
+        let res = user_move(session_id, user_1, Move::Drop(1), None, conn.deref());
+        assert!(matches!(res, Err(GameError::InvalidMove(_))));
+        // X fills the top row from the left, O the row below it
+        for col_num in 1..5 {
+            user_move(
+                session_id,
+                user_1,
+                Move::Place(6, col_num),
+                None,
+                conn.deref(),
+            )
+            .unwrap();
+            let state = user_move(
+                session_id,
+                user_2,
+                Move::Place(5, col_num),
+                None,
+                conn.deref(),
+            )
+            .unwrap();
+            assert!(!state.ended);
         }
+        let res = user_move(session_id, user_1, Move::Place(5, 1), None, conn.deref());
+        assert!(matches!(res, Err(GameError::InvalidMove(_))));
+        let state = user_move(session_id, user_1, Move::Place(6, 5), None, conn.deref()).unwrap();
+        assert!(state.ended && state.winner);
+        assert_eq!(state.winner_color.as_deref(), Some("X"));
+        assert_eq!(
+            state.board.as_deref().unwrap_or_default()[..18],
+            *"XXXXX----OOOO-----"
+        );
+        assert_eq!(state.move_list, "a6 a5 b6 b5 c6 c5 d6 d5 e6");
+        assert!(projection::check_game(&session_id, conn.deref())
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    pub fn test_do_move() {
-        let user_id = Uuid::new_v4();
+    pub fn test_user_move_win_rules() {
         let conn = create_conn_pool().get().unwrap();
-        let board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-        ];
+        for win_rule in [WinRule::Exact, WinRule::Renju].iter() {
+            let user_1 = db::create_new_user("test-user-1", "X", conn.deref()).unwrap();
+            let user_2 = db::create_new_user("test-user-2", "O", conn.deref()).unwrap();
+            let session_id =
+                db::create_new_session(&user_1, &BoardSize::default(), conn.deref()).unwrap();
+            db::update_win_rule(&session_id, *win_rule, conn.deref()).unwrap();
+            db::join_game_session(&session_id, &user_2, conn.deref()).unwrap();
+            // X in the bottom row, O on top of it
+            for (ply, col_num) in [1, 1, 2, 2, 3, 3, 4, 4, 6, 9].iter().enumerate() {
+                let user_id = if ply % 2 == 0 { user_1 } else { user_2 };
+                user_move(
+                    session_id,
+                    user_id,
+                    Move::Drop(*col_num),
+                    None,
+                    conn.deref(),
+                )
+                .unwrap();
+            }
+            // six in a row
+            let res = user_move(session_id, user_1, Move::Drop(5), None, conn.deref());
+            if *win_rule == WinRule::Renju {
+                assert!(matches!(res, Err(GameError::InvalidMove(_))));
+                continue;
+            }
+            assert!(!res.unwrap().ended);
+            let state = user_move(session_id, user_2, Move::Drop(5), None, conn.deref()).unwrap();
+            assert!(state.ended && state.winner);
+            assert_eq!(state.winner_color.as_deref(), Some("O"));
+            assert!(projection::check_game(&session_id, conn.deref())
+                .unwrap()
+                .is_empty());
+        }
+    }
 
-        let target_board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', 'X', '-', '-'],
-        ];
+    #[test]
+    pub fn test_user_move_version() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        let version = db::get_game_state(&session_id, conn.deref())
+            .unwrap()
+            .version;
+
+        let new_state = user_move(
+            session_id,
+            user_1,
+            Move::Drop(1),
+            Some(version),
+            conn.deref(),
+        )
+        .unwrap();
+        assert_eq!(new_state.version, version + 1);
+        // user_2 chose the move on the board before the move of user_1
+        let res = user_move(
+            session_id,
+            user_2,
+            Move::Drop(2),
+            Some(version),
+            conn.deref(),
+        );
+        assert_eq!(res.unwrap_err(), GameError::Conflict);
+        let board = db::get_game_state(&session_id, conn.deref()).unwrap().board;
+        assert_eq!(board, new_state.board);
 
-        db::get_user_color.mock_safe(move |_x, _conn| MockResult::Return(Result::Ok('X')));
-        let new_board = do_move(user_id, 7, &board, conn.deref()).unwrap();
-        assert_eq!(new_board, target_board);
+        let new_state = user_move(
+            session_id,
+            user_2,
+            Move::Drop(2),
+            Some(version + 1),
+            conn.deref(),
+        )
+        .unwrap();
+        assert_eq!(new_state.version, version + 2);
     }
 
     #[test]
-    pub fn test_do_move_adjacent() {
-        let user_id = Uuid::new_v4();
+    pub fn test_takeback_accepted() {
         let conn = create_conn_pool().get().unwrap();
-        // let user_id = db::create_new_user("test-user".to_string(), "X".to_string(), conn.deref()).unwrap();
-        let board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', 'X', '-', '-', '-', '-', '-', '-'],
-        ];
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        let before = user_move(session_id, user_1, Move::Drop(4), None, conn.deref()).unwrap();
+        user_move(session_id, user_2, Move::Drop(5), None, conn.deref()).unwrap();
 
-        let target_board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', 'X', '-', '-', '-', '-', '-', '-'],
-        ];
+        let res = request_takeback(session_id, user_1, conn.deref());
+        assert!(matches!(res, Err(GameError::TakebackNotAllowed(_))));
+        let state = request_takeback(session_id, user_2, conn.deref()).unwrap();
+        assert_eq!(state.takeback_requested_by, Some(user_2.to_string()));
+        let res = answer_takeback(session_id, user_2, true, conn.deref());
+        assert!(matches!(res, Err(GameError::TakebackNotAllowed(_))));
+
+        let state = answer_takeback(session_id, user_1, true, conn.deref()).unwrap();
+        assert_eq!(state.board, before.board);
+        assert_eq!(state.last_user_id, Some(user_1.to_string()));
+        assert_eq!(state.move_list, "4");
+        assert_eq!(state.takeback_requested_by, None);
+        assert_eq!(db::get_moves(&session_id, conn.deref()).unwrap().len(), 1);
+        // user_2 plays again
+        user_move(session_id, user_2, Move::Drop(4), None, conn.deref()).unwrap();
+    }
+
+    #[test]
+    pub fn test_takeback_declined() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        let after = user_move(session_id, user_1, Move::Drop(4), None, conn.deref()).unwrap();
 
-        db::get_user_color.mock_safe(move |_x, _conn| MockResult::Return(Result::Ok('X')));
+        request_takeback(session_id, user_1, conn.deref()).unwrap();
+        let state = answer_takeback(session_id, user_2, false, conn.deref()).unwrap();
+        assert_eq!(state.board, after.board);
+        assert_eq!(state.takeback_requested_by, None);
+        let res = answer_takeback(session_id, user_2, true, conn.deref());
+        assert!(matches!(res, Err(GameError::TakebackNotAllowed(_))));
 
-        let new_board = do_move(user_id, 2, &board, conn.deref()).unwrap();
-        assert_eq!(new_board, target_board);
+        // a move of the other player declines the request too
+        request_takeback(session_id, user_1, conn.deref()).unwrap();
+        let state = user_move(session_id, user_2, Move::Drop(4), None, conn.deref()).unwrap();
+        assert_eq!(state.takeback_requested_by, None);
     }
 
     #[test]
-    pub fn test_do_move_full() {
-        let user_id = Uuid::new_v4();
+    pub fn test_analyse_position() {
         let conn = create_conn_pool().get().unwrap();
-        let board = vec![
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-        ];
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        db::update_rated(&session_id, true, conn.deref()).unwrap();
+        // a position no other test reaches
+        for (user, col) in [(user_1, 1), (user_2, 9), (user_1, 1), (user_2, 9)].iter() {
+            user_move(session_id, *user, Move::Drop(*col), None, conn.deref()).unwrap();
+        }
+        let board = Notation::new(BoardSize::default(), vec![1, 9, 1, 9])
+            .board()
+            .unwrap();
+
+        let res = analyse_position(&board, ['X', 'O'], conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::AnalysisDisabled);
 
-        db::get_user_color.mock_safe(move |_x, _conn| MockResult::Return(Result::Ok('X')));
+        user_move(session_id, user_1, Move::Drop(1), None, conn.deref()).unwrap();
+        let analysis = analyse_position(&board, ['X', 'O'], conn.deref()).unwrap();
+        assert_eq!(analysis.columns.len(), 9);
+        assert!(analysis.best.is_some());
 
-        let new_board = do_move(user_id, 2, &board, conn.deref());
-        // log:debug!("{:?}", new_board);
+        let won = Notation::new(BoardSize::new(6, 7, 2), vec![1, 7, 2])
+            .board()
+            .unwrap();
+        let res = analyse_position(&won, ['X', 'O'], conn.deref());
+        assert!(matches!(res, Err(GameError::InvalidPosition(_))));
+    }
+
+    #[test]
+    pub fn test_book_moves() {
+        let board = Board::new(BoardSize::default());
+        opening_book.mock_safe(|| {
+            let book = "6x9/5\n: 5:3".parse::<OpeningBook>().unwrap();
+            MockResult::Return(Some(Arc::new(book)))
+        });
+        let moves = book_moves(&board, ['X', 'O']).unwrap();
         assert_eq!(
-            new_board,
-            Err("This column is full. Please, try another move".to_string())
+            moves,
+            vec![BookMove {
+                column: 5,
+                weight: 3
+            }]
         );
+
+        opening_book.mock_safe(|| MockResult::Return(None));
+        let res = book_moves(&board, ['X', 'O']);
+        assert_eq!(res.unwrap_err(), GameError::NoOpeningBook);
     }
 
     #[test]
-    pub fn test_user_move() {
+    pub fn test_user_move_game_over() {
         let conn = create_conn_pool().get().unwrap();
-        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
-        let new_session_id = db::create_new_session(&user_id, conn.deref()).unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        let board_str = "-".repeat(54);
+        db::update_game_state(
+            &session_id,
+            &user_1,
+            &board_str,
+            true,
+            false,
+            true,
+            conn.deref(),
+        )
+        .unwrap();
 
-        let board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', 'X', '-', '-', '-', '-', '-', '-'],
-        ];
+        let res = user_move(session_id, user_2, Move::Drop(1), None, conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::GameOver);
+    }
 
-        let res = db::update_game_state(
-            &new_session_id,
-            &user_id,
-            &utils::arr_to_str(&board),
+    #[test]
+    pub fn test_user_move_not_a_player() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, _user_1, _user_2) = new_two_player_game(conn.deref());
+        let outsider = db::create_new_user("test-user-3", "Z", conn.deref()).unwrap();
+
+        let res = user_move(session_id, outsider, Move::Drop(1), None, conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::NotAPlayer);
+    }
+
+    #[test]
+    pub fn test_user_move_draw() {
+        let conn = create_conn_pool().get().unwrap();
+        let board_size = BoardSize::new(3, 3, 3);
+        let user_1 = db::create_new_user("test-user-1", "X", conn.deref()).unwrap();
+        let user_2 = db::create_new_user("test-user-2", "O", conn.deref()).unwrap();
+        let session_id = db::create_new_session(&user_1, &board_size, conn.deref()).unwrap();
+        db::join_game_session(&session_id, &user_2, conn.deref()).unwrap();
+        let board_str = board("3x3/3 1 2 3 2 1 1 2 3").to_string();
+        db::update_game_state(
+            &session_id,
+            &user_2,
+            &board_str,
+            false,
             false,
             false,
             conn.deref(),
+        )
+        .unwrap();
+
+        let new_state = user_move(session_id, user_1, Move::Drop(3), None, conn.deref()).unwrap();
+        assert_eq!(
+            new_state.board.unwrap(),
+            board("3x3/3 1 2 3 2 1 1 2 3 3").to_string()
         );
+        assert_eq!(new_state.winner, false);
+        assert_eq!(new_state.draw, true);
+        assert_eq!(new_state.ended, true);
+    }
 
-        assert_eq!(res.unwrap(), 1);
+    #[test]
+    pub fn test_user_move_position_key() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_1, user_1, user_2) = new_two_player_game(conn.deref());
+        for (user, col) in &[(user_1, 4), (user_2, 5), (user_1, 6), (user_2, 7)] {
+            user_move(session_1, *user, Move::Drop(*col), None, conn.deref()).unwrap();
+        }
+        let (session_2, user_3, user_4) = new_two_player_game(conn.deref());
+        for (user, col) in &[(user_3, 6), (user_4, 7), (user_3, 4), (user_4, 5)] {
+            user_move(session_2, *user, Move::Drop(*col), None, conn.deref()).unwrap();
+        }
 
-        let tmp_board = db::get_board(&new_session_id, conn.deref()).unwrap();
-        assert_eq!(tmp_board, utils::arr_to_str(&board));
+        let key = db::get_game_state(&session_1, conn.deref())
+            .unwrap()
+            .position_key
+            .unwrap();
+        let games = db::find_games_by_position_key(&key, conn.deref()).unwrap();
+        assert!(games.iter().any(|gs| gs.id == session_2.to_string()));
+    }
 
-        let target_board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', 'X', '-', '-', '-', '-', '-', '-'],
-        ];
+    #[test]
+    pub fn test_add_ai_player() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = db::create_new_user("test-user", "O", conn.deref()).unwrap();
+        let session_id =
+            db::create_new_session(&user_id, &BoardSize::default(), conn.deref()).unwrap();
 
-        let new_state = user_move(new_session_id, user_id, 2, conn.deref()).unwrap();
-        assert_eq!(new_state.board.unwrap(), utils::arr_to_str(&target_board));
-        assert_eq!(new_state.last_user_id.unwrap(), user_id.to_string());
-        assert_eq!(new_state.winner, false);
+        let ai_user_id = add_ai_player(
+            session_id,
+            user_id,
+            AiEngine::Minimax,
+            AiLevel::Expert,
+            conn.deref(),
+        )
+        .unwrap();
+        let game_state = db::get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(game_state.user_2.unwrap(), ai_user_id.to_string());
+        assert_eq!(game_state.ai_engine.unwrap(), "minimax");
+        assert_eq!(game_state.ai_level.unwrap(), "expert");
+        assert_eq!(db::get_user_color(&ai_user_id, conn.deref()).unwrap(), 'X');
     }
 
     #[test]
-    pub fn test_user_move_win_column() {
+    pub fn test_new_game() {
         let conn = create_conn_pool().get().unwrap();
         let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
-        let new_session_id = db::create_new_session(&user_id, conn.deref()).unwrap();
-        let board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-        ];
-        let board_str = utils::arr_to_str(&board);
-        let _ = db::update_game_state(
-            &new_session_id,
-            &user_id,
-            &board_str,
+        let session_id = new_game(
+            user_id,
+            BoardSize::new(6, 7, 4),
+            WinRule::Exact,
+            RuleSet::PopOut,
+            true,
+            None,
+            conn.deref(),
+        )
+        .unwrap();
+        let game_state = db::get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(game_state.rules, "popout");
+        assert_eq!(game_state.win_rule(), WinRule::Exact);
+        assert!(game_state.rated);
+        assert!(game_state.user_2.is_none());
+
+        let ai_opponent = Some((AiEngine::Mcts, AiLevel::Beginner));
+        let session_id = new_game(
+            user_id,
+            BoardSize::new(6, 7, 4),
+            WinRule::Exact,
+            RuleSet::Standard,
+            false,
+            ai_opponent,
+            conn.deref(),
+        )
+        .unwrap();
+        let game_state = db::get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(game_state.win_rule(), WinRule::Exact);
+        assert_eq!(game_state.ai_engine.unwrap(), "mcts");
+
+        // the computer only drops pieces and doesn't know the renju restrictions
+        for (win_rule, rule_set) in [
+            (WinRule::Freestyle, RuleSet::PopOut),
+            (WinRule::Freestyle, RuleSet::Gomoku),
+            (WinRule::Renju, RuleSet::Standard),
+        ]
+        .iter()
+        {
+            let res = new_game(
+                user_id,
+                rule_set.rules().default_size(),
+                *win_rule,
+                *rule_set,
+                false,
+                ai_opponent,
+                conn.deref(),
+            );
+            assert!(matches!(res, Err(GameError::InvalidGame(_))));
+        }
+
+        // nothing is stored if the computer can't be seated
+        let created = Rc::new(Cell::new(None));
+        let seen = created.clone();
+        add_ai_player.mock_safe(move |session_id, _user_id, _engine, _level, _conn| {
+            seen.set(Some(session_id));
+            MockResult::Return(Err(GameError::Internal("no computer".to_string())))
+        });
+        let res = new_game(
+            user_id,
+            BoardSize::new(6, 7, 4),
+            WinRule::Exact,
+            RuleSet::Standard,
+            true,
+            ai_opponent,
+            conn.deref(),
+        );
+        assert!(res.is_err());
+        let session_id = created.get().unwrap();
+        let res = db::get_game_state(&session_id, conn.deref());
+        assert_eq!(res.unwrap_err(), diesel::result::Error::NotFound);
+    }
+
+    #[test]
+    pub fn test_user_move_ai_answers() {
+        let conn = create_conn_pool().get().unwrap();
+        let board_size = BoardSize::new(6, 7, 4);
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let session_id = db::create_new_session(&user_id, &board_size, conn.deref()).unwrap();
+        let ai_user_id = add_ai_player(
+            session_id,
+            user_id,
+            AiEngine::Minimax,
+            AiLevel::Expert,
+            conn.deref(),
+        )
+        .unwrap();
+
+        let new_state = user_move(session_id, user_id, Move::Drop(4), None, conn.deref()).unwrap();
+        assert_eq!(new_state.last_user_id.unwrap(), ai_user_id.to_string());
+        let board = new_state.board.unwrap();
+        assert_eq!(board.matches('X').count(), 1);
+        assert_eq!(board.matches('O').count(), 1);
+
+        let res = user_move(session_id, ai_user_id, Move::Drop(1), None, conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::NotYourTurn);
+    }
+
+    #[test]
+    pub fn test_user_move_ai_fails() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let session_id =
+            db::create_new_session(&user_id, &BoardSize::new(6, 7, 4), conn.deref()).unwrap();
+        let ai_user_id = add_ai_player(
+            session_id,
+            user_id,
+            AiEngine::Minimax,
+            AiLevel::Beginner,
+            conn.deref(),
+        )
+        .unwrap();
+        choose_ai_move.mock_safe(|_engine, _level, _position| {
+            MockResult::Return(Err(GameError::Internal("engine crashed".to_string())))
+        });
+
+        // the move is kept, the computer owes the answer
+        let new_state = user_move(session_id, user_id, Move::Drop(1), None, conn.deref()).unwrap();
+        assert_eq!(new_state.last_user_id, Some(user_id.to_string()));
+        assert!(new_state.is_ai_turn());
+        let game_state = get_game_state(&session_id, &user_id, conn.deref()).unwrap();
+        assert!(game_state.is_ai_turn());
+        let res = user_move(session_id, user_id, Move::Drop(2), None, conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::NotYourTurn);
+
+        // the engine is back, the answer is played when the game is fetched
+        choose_ai_move.mock_safe(|_engine, _level, _position| MockResult::Return(Ok(6)));
+        let res = get_game_state(&session_id, &Uuid::new_v4(), conn.deref());
+        assert_eq!(res.unwrap_err(), GameError::NotAPlayer);
+        assert!(db::get_game_state(&session_id, conn.deref())
+            .unwrap()
+            .is_ai_turn());
+        let game_state = get_game_state(&session_id, &user_id, conn.deref()).unwrap();
+        assert_eq!(game_state.last_user_id, Some(ai_user_id.to_string()));
+        assert_eq!(game_state.move_list, "1 7");
+
+        let new_state = user_move(session_id, user_id, Move::Drop(2), None, conn.deref()).unwrap();
+        assert_eq!(new_state.last_user_id, Some(ai_user_id.to_string()));
+        assert_eq!(new_state.move_list, "1 7 2 7");
+    }
+
+    #[test]
+    pub fn test_user_move_ai_mcts() {
+        let conn = create_conn_pool().get().unwrap();
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let session_id =
+            db::create_new_session(&user_id, &BoardSize::new(6, 7, 4), conn.deref()).unwrap();
+        let ai_user_id = add_ai_player(
+            session_id,
+            user_id,
+            AiEngine::Mcts,
+            AiLevel::Beginner,
+            conn.deref(),
+        )
+        .unwrap();
+
+        let new_state = user_move(session_id, user_id, Move::Drop(1), None, conn.deref()).unwrap();
+        assert_eq!(new_state.ai_engine.unwrap(), "mcts");
+        assert_eq!(new_state.last_user_id.unwrap(), ai_user_id.to_string());
+        assert_eq!(new_state.board.unwrap().matches('O').count(), 1);
+    }
+
+    #[test]
+    pub fn test_user_move_ai_blocks() {
+        let conn = create_conn_pool().get().unwrap();
+        let board_size = BoardSize::new(6, 7, 4);
+        let user_id = db::create_new_user("test-user", "X", conn.deref()).unwrap();
+        let session_id = db::create_new_session(&user_id, &board_size, conn.deref()).unwrap();
+        let ai_user_id = add_ai_player(
+            session_id,
+            user_id,
+            AiEngine::Minimax,
+            AiLevel::Expert,
+            conn.deref(),
+        )
+        .unwrap();
+        let board = Board::parse(&("-".repeat(35) + "XX---OO"), board_size).unwrap();
+        db::update_game_state(
+            &session_id,
+            &ai_user_id,
+            &board.to_string(),
+            false,
             false,
             false,
             conn.deref(),
+        )
+        .unwrap();
+
+        let new_state = user_move(session_id, user_id, Move::Drop(3), None, conn.deref()).unwrap();
+        assert_eq!(new_state.board.unwrap(), "-".repeat(35) + "XXXO-OO");
+        assert_eq!(new_state.ended, false);
+    }
+
+    #[test]
+    pub fn test_user_move_move_list() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        user_move(session_id, user_1, Move::Drop(5), None, conn.deref()).unwrap();
+        let new_state = user_move(session_id, user_2, Move::Drop(4), None, conn.deref()).unwrap();
+        assert_eq!(new_state.move_list, "5 4");
+    }
+
+    #[test]
+    pub fn test_user_move_stores_moves() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        user_move(session_id, user_1, Move::Drop(5), None, conn.deref()).unwrap();
+        user_move(session_id, user_2, Move::Drop(5), None, conn.deref()).unwrap();
+
+        let moves = db::get_moves(&session_id, conn.deref()).unwrap();
+        assert_eq!(moves.len(), 2);
+        assert_eq!(
+            (moves[0].ply, moves[0].col_num, moves[0].row_num),
+            (1, 5, 1)
         );
-        let target_board = vec![
-            vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-            vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
-        ];
+        assert_eq!(moves[0].user_id, user_1.to_string());
+        assert_eq!(
+            (moves[1].ply, moves[1].col_num, moves[1].row_num),
+            (2, 5, 2)
+        );
+        assert_eq!(moves[1].user_id, user_2.to_string());
+    }
 
-        let new_state = user_move(new_session_id, user_id, 2, conn.deref()).unwrap();
-        assert_eq!(new_state.board.unwrap(), utils::arr_to_str(&target_board));
-        assert_eq!(new_state.last_user_id.unwrap(), user_id.to_string());
-        assert_eq!(new_state.winner, true);
-        assert_eq!(new_state.ended, true);
+    #[test]
+    pub fn test_export_game() {
+        let conn = create_conn_pool().get().unwrap();
+        let (session_id, user_1, user_2) = new_two_player_game(conn.deref());
+        for (ply, col) in [2, 1, 2, 1, 2, 1, 2, 1].iter().enumerate() {
+            let user = if ply % 2 == 0 { user_1 } else { user_2 };
+            user_move(session_id, user, Move::Drop(*col), None, conn.deref()).unwrap();
+        }
+        assert_eq!(
+            export_game(session_id, conn.deref()).unwrap_err(),
+            GameError::NotFinished
+        );
+
+        user_move(session_id, user_1, Move::Drop(2), None, conn.deref()).unwrap();
+        let record = export_game(session_id, conn.deref()).unwrap();
+        assert_eq!(record.players[0], "test-user-1");
+        assert_eq!(record.colors, ['X', 'O']);
+        assert_eq!(record.result, GameResult::FirstWins);
+        assert_eq!(record.notation.to_string(), "6x9/5 2 1 2 1 2 1 2 1 2");
+        let first_move = &db::get_moves(&session_id, conn.deref()).unwrap()[0];
+        assert_eq!(record.date, first_move.created_at[..10].replace('-', "."));
+
+        assert_eq!(
+            export_game(Uuid::new_v4(), conn.deref()).unwrap_err(),
+            GameError::NotFound
+        );
+    }
+
+    #[test]
+    pub fn test_import_game() {
+        let conn = create_conn_pool().get().unwrap();
+        let record = "[Date \"2021.06.05\"]\n[Player1 \"alice\"]\n[Player2 \"bob\"]\n\
+                      [Rules \"6x7/4\"]\n\
+                      1. 4 4 2. 3 3 3. 2 2 4. 1 1-0"
+            .parse::<GameRecord>()
+            .unwrap();
+        let session_id = import_game(&record, conn.deref()).unwrap();
+
+        let game_state = db::get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(
+            game_state.board,
+            Some(record.notation.board().unwrap().to_string())
+        );
+        assert_eq!(game_state.board_size(), BoardSize::new(6, 7, 4));
+        assert!(game_state.winner);
+        assert!(game_state.ended);
+        assert_eq!(game_state.winner_color.unwrap(), "X");
+        assert_eq!(game_state.move_list, "4 4 3 3 2 2 1");
+        let moves = db::get_moves(&session_id, conn.deref()).unwrap();
+        assert_eq!(moves.len(), 7);
+        assert_eq!((moves[6].col_num, moves[6].row_num), (1, 1));
+        assert_eq!(moves[6].created_at, "2021-06-05 00:00:00");
+        assert_eq!(export_game(session_id, conn.deref()).unwrap(), record);
+
+        let mut invalid = record.clone();
+        invalid.notation.moves.push(1);
+        assert!(matches!(
+            import_game(&invalid, conn.deref()),
+            Err(GameError::InvalidRecord(_))
+        ));
+
+        // nothing is stored, neither the game nor its players, if a step of the import fails
+        let created = Rc::new(Cell::new(None));
+        let seen = created.clone();
+        db::update_move_list.mock_safe(move |session_id, _moves, conn| {
+            let user_1 = db::get_game_state(session_id, conn).unwrap().user_1;
+            seen.set(Some((
+                *session_id,
+                Uuid::parse_str(&user_1.unwrap()).unwrap(),
+            )));
+            MockResult::Return(Err(diesel::result::Error::NotFound))
+        });
+        assert!(import_game(&record, conn.deref()).is_err());
+        let (session_id, user_1) = created.get().unwrap();
+        let res = db::get_game_state(&session_id, conn.deref());
+        assert_eq!(res.unwrap_err(), diesel::result::Error::NotFound);
+        assert!(db::get_moves(&session_id, conn.deref()).unwrap().is_empty());
+        assert!(db::get_user(&user_1, conn.deref()).is_err());
     }
 }
@@ -8,9 +8,15 @@ use actix_session::CookieSession;
 use actix_web::{web, App, HttpServer};
 
 pub mod api;
+pub mod board;
 pub mod db;
+pub mod engine;
 pub mod game;
 pub mod models;
+pub mod notation;
+pub mod projection;
+pub mod record;
+pub mod rules;
 pub mod schema;
 pub mod utils;
 
@@ -31,9 +37,35 @@ pub async fn start_server() -> std::io::Result<()> {
                     .service(
                         web::resource("/join/{game_session_id}").route(web::post().to(api::join)),
                     )
+                    .service(
+                        web::resource("/spectate/{game_session_id}")
+                            .route(web::post().to(api::spectate)),
+                    )
+                    .service(
+                        web::resource("/export/{game_session_id}")
+                            .route(web::get().to(api::export)),
+                    )
+                    .service(web::resource("/import").route(web::post().to(api::import)))
                     .service(web::resource("/game-state").route(web::get().to(api::game_state)))
                     .service(
                         web::resource("/make-move/{column}").route(web::post().to(api::make_move)),
+                    )
+                    .service(web::resource("/pop/{column}").route(web::post().to(api::pop)))
+                    .service(
+                        web::resource("/place/{row}/{column}").route(web::post().to(api::place)),
+                    )
+                    .service(web::resource("/analysis").route(web::get().to(api::analysis)))
+                    .service(web::resource("/book").route(web::get().to(api::book)))
+                    .service(
+                        web::resource("/takeback").route(web::post().to(api::request_takeback)),
+                    )
+                    .service(
+                        web::resource("/takeback/accept")
+                            .route(web::post().to(api::accept_takeback)),
+                    )
+                    .service(
+                        web::resource("/takeback/decline")
+                            .route(web::post().to(api::decline_takeback)),
                     ),
             )
             .service(fs::Files::new("/", "./static").index_file("index.html"))
@@ -1,6 +1,10 @@
+use super::board::{BoardSize, WinRule};
+use super::rules::RuleSet;
 use super::schema::game_state;
+use super::schema::moves;
 use super::schema::user;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Default, Queryable, Insertable, Debug, Clone)]
 #[table_name = "user"]
@@ -20,6 +24,63 @@ pub struct GameState {
     pub last_user_id: Option<String>,
     pub last_user_color: Option<String>,
     pub ended: bool,
+    pub board_rows: i32,
+    pub board_columns: i32,
+    pub win_length: i32,
+    pub draw: bool,
+    pub winner_color: Option<String>,
+    /// comma separated indices of the winning cells in `board`
+    pub winning_cells: Option<String>,
+    /// engine of the computer player, if `user_2` is the computer
+    pub ai_engine: Option<String>,
+    pub ai_level: Option<String>,
+    /// see `engine::zobrist::position_key`
+    pub position_key: Option<String>,
+    /// moves in `notation` format, without the header
+    pub move_list: String,
+    /// incremented on every change of the game, see `game::user_move`
+    pub version: i32,
+    /// player waiting for the answer to a takeback request, see `game::request_takeback`
+    pub takeback_requested_by: Option<String>,
+    pub rated: bool,
+    /// name of the rule set, see `rules::RuleSet`
+    pub rules: String,
+    /// name of the win rule, see `board::WinRule`
+    pub win_rule: String,
+}
+
+impl GameState {
+    /// True if the user plays in this game, i.e. is `user_1` or `user_2`.
+    pub fn is_seated(&self, user_id: &Uuid) -> bool {
+        let user_id = Some(user_id.to_string());
+        self.user_1 == user_id || self.user_2 == user_id
+    }
+
+    /// True if the computer, seated as `user_2`, has yet to answer the last move of `user_1`.
+    pub fn is_ai_turn(&self) -> bool {
+        self.ai_engine.is_some()
+            && !self.ended
+            && self.user_2.is_some()
+            && self.last_user_id.is_some()
+            && self.last_user_id == self.user_1
+    }
+
+    pub fn rule_set(&self) -> Result<RuleSet, String> {
+        self.rules.parse()
+    }
+
+    pub fn board_size(&self) -> BoardSize {
+        BoardSize::new(
+            self.board_rows as usize,
+            self.board_columns as usize,
+            self.win_length as usize,
+        )
+    }
+
+    /// The win rule of the game, freestyle if it is unknown.
+    pub fn win_rule(&self) -> WinRule {
+        self.win_rule.parse().unwrap_or_default()
+    }
 }
 
 #[derive(Deserialize, Serialize, Insertable)]
@@ -28,4 +89,36 @@ pub struct NewGameState {
     pub id: String,
     pub board: Option<String>,
     pub user_1: Option<String>,
+    pub board_rows: i32,
+    pub board_columns: i32,
+    pub win_length: i32,
+}
+
+/// A move of the game `game_id`. `ply` counts the moves of the game from 1, `col_num` is 1-based
+/// and `row_num` counts from 1 at the bottom of the board.
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone, PartialEq)]
+pub struct Move {
+    pub id: i32,
+    pub game_id: String,
+    pub ply: i32,
+    pub user_id: String,
+    pub col_num: i32,
+    pub row_num: i32,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC
+    pub created_at: String,
+    /// see `rules::Move::kind`
+    pub kind: String,
+}
+
+#[derive(Deserialize, Serialize, Insertable)]
+#[table_name = "moves"]
+pub struct NewMove {
+    pub game_id: String,
+    pub ply: i32,
+    pub user_id: String,
+    pub col_num: i32,
+    pub row_num: i32,
+    /// defaults to the current time
+    pub created_at: Option<String>,
+    pub kind: String,
 }
@@ -0,0 +1,354 @@
+//! Text notation of a game: a header with the board size and rules, followed by the 1-based
+//! columns of the moves in the order they were played, the same numbers `make_move` takes.
+//!
+//! `6x9/5 5 5 4` is a 6 rows by 9 columns board, 5 in a row to win, where the first player
+//! played column 5, the second one column 5 and the first one column 4. Without a header the
+//! default board size is assumed. Win rules other than freestyle follow the win length, e.g.
+//! `6x9/5/exact`.
+use std::fmt;
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::board::{Board, BoardSize, Cell, WinRule, WinningLine};
+use crate::engine::zobrist::position_key;
+use crate::models::GameState;
+use crate::rules::RuleSet;
+
+/// Colors used when replaying a game without players.
+pub const DEFAULT_COLORS: [char; 2] = ['X', 'O'];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notation {
+    pub size: BoardSize,
+    pub win_rule: WinRule,
+    /// 1-based columns
+    pub moves: Vec<usize>,
+}
+
+/// Board at the end of a replayed game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub board: Board,
+    /// cell each move landed on
+    pub cells: Vec<Cell>,
+    pub winning_line: Option<WinningLine>,
+}
+
+impl Replay {
+    pub fn is_draw(&self) -> bool {
+        self.winning_line.is_none() && self.board.is_full()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winning_line.is_some() || self.board.is_full()
+    }
+}
+
+impl Notation {
+    pub fn new(size: BoardSize, moves: Vec<usize>) -> Self {
+        Notation {
+            size,
+            win_rule: WinRule::default(),
+            moves,
+        }
+    }
+
+    /// The moves played with the win rule, freestyle by default.
+    pub fn with_win_rule(self, win_rule: WinRule) -> Self {
+        Notation { win_rule, ..self }
+    }
+
+    /// Plays the moves on an empty board, `colors` being the colors of the first and the second
+    /// player. Fails on illegal moves and on moves played after the game ended.
+    pub fn replay(&self, colors: [char; 2]) -> Result<Replay, String> {
+        self.size.validate()?;
+        let mut board = Board::new(self.size).with_win_rule(self.win_rule, colors[0]);
+        let mut cells = vec![];
+        let mut winning_line = None;
+        for (ply, col_num) in self.moves.iter().enumerate() {
+            if winning_line.is_some() || board.is_full() {
+                return Err(format!(
+                    "Move {} is played after the end of the game",
+                    ply + 1
+                ));
+            }
+            if *col_num < 1 || *col_num > board.columns() {
+                return Err(format!(
+                    "Move {}: there is no column {}. Max column is: {}",
+                    ply + 1,
+                    col_num,
+                    board.columns()
+                ));
+            }
+            let row = board
+                .drop_piece(col_num - 1, colors[ply % 2])
+                .map_err(|_| format!("Move {}: column {} is full", ply + 1, col_num))?;
+            if let Some(reason) = board.forbidden_move(row, col_num - 1) {
+                return Err(format!("Move {}: {} is forbidden", ply + 1, reason));
+            }
+            winning_line = board.winning_line_at(row, col_num - 1);
+            cells.push((row, col_num - 1));
+        }
+        Ok(Replay {
+            board,
+            cells,
+            winning_line,
+        })
+    }
+
+    /// The moves without the header, as stored in `game_state.move_list`.
+    pub fn move_list(&self) -> String {
+        self.moves
+            .iter()
+            .map(|col_num| col_num.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parses `game_state.move_list`.
+    pub fn from_move_list(
+        size: BoardSize,
+        win_rule: WinRule,
+        move_list: &str,
+    ) -> Result<Self, String> {
+        let notation = move_list.parse::<Notation>()?;
+        Ok(Notation {
+            size,
+            win_rule,
+            ..notation
+        })
+    }
+
+    /// Shortcut for the board of `replay` with the default colors.
+    pub fn board(&self) -> Result<Board, String> {
+        Ok(self.replay(DEFAULT_COLORS)?.board)
+    }
+
+    /// Rebuilds the state of a game between `user_1`, who moved first, and `user_2`.
+    pub fn to_game_state(
+        &self,
+        id: &Uuid,
+        user_1: &Uuid,
+        user_2: &Uuid,
+        colors: [char; 2],
+    ) -> Result<GameState, String> {
+        let replay = self.replay(colors)?;
+        let last = match self.moves.len() % 2 {
+            0 if self.moves.is_empty() => None,
+            0 => Some((user_2, colors[1])),
+            _ => Some((user_1, colors[0])),
+        };
+        let columns = replay.board.columns();
+        Ok(GameState {
+            id: id.to_string(),
+            board: Some(replay.board.to_string()),
+            user_1: Some(user_1.to_string()),
+            user_2: Some(user_2.to_string()),
+            winner: replay.winning_line.is_some(),
+            last_user_id: last.map(|(user, _)| user.to_string()),
+            last_user_color: last.map(|(_, color)| color.to_string()),
+            ended: replay.is_over(),
+            board_rows: self.size.rows as i32,
+            board_columns: self.size.columns as i32,
+            win_length: self.size.win_length as i32,
+            draw: replay.is_draw(),
+            winner_color: replay
+                .winning_line
+                .as_ref()
+                .map(|line| line.color.to_string()),
+            winning_cells: replay
+                .winning_line
+                .as_ref()
+                .map(|line| line.cell_indices(columns)),
+            ai_engine: None,
+            ai_level: None,
+            position_key: Some(position_key(&replay.board, colors[0])),
+            move_list: self.move_list(),
+            version: 0,
+            takeback_requested_by: None,
+            rated: false,
+            rules: RuleSet::default().name().to_string(),
+            win_rule: self.win_rule.name().to_string(),
+        })
+    }
+}
+
+/// The header of the board size and the win rule, e.g. `6x9/5` or `15x15/5/renju`.
+pub fn header(size: BoardSize, win_rule: WinRule) -> String {
+    let header = format!("{}x{}/{}", size.rows, size.columns, size.win_length);
+    match win_rule {
+        WinRule::Freestyle => header,
+        win_rule => format!("{}/{}", header, win_rule),
+    }
+}
+
+impl fmt::Display for Notation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", header(self.size, self.win_rule))?;
+        for col_num in &self.moves {
+            write!(f, " {}", col_num)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Notation {
+    type Err = String;
+
+    fn from_str(notation: &str) -> Result<Self, Self::Err> {
+        let mut tokens = notation.split_whitespace().peekable();
+        let (size, win_rule) = match tokens.peek() {
+            Some(header) if header.contains('x') => {
+                let header = parse_header(header)?;
+                tokens.next();
+                header
+            }
+            _ => (BoardSize::default(), WinRule::default()),
+        };
+        let moves = tokens
+            .map(|token| {
+                token
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid move '{}'", token))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Notation {
+            size,
+            win_rule,
+            moves,
+        })
+    }
+}
+
+// `<rows>x<columns>/<win length>[/<win rule>]`
+fn parse_header(header: &str) -> Result<(BoardSize, WinRule), String> {
+    let invalid = || format!("Invalid header '{}', expected e.g. 6x9/5", header);
+    let (dimensions, win) = header.split_once('/').ok_or_else(invalid)?;
+    let (rows, columns) = dimensions.split_once('x').ok_or_else(invalid)?;
+    let (win_length, win_rule) = match win.split_once('/') {
+        Some((win_length, win_rule)) => (win_length, win_rule.parse()?),
+        None => (win, WinRule::default()),
+    };
+    let number = |value: &str| value.parse::<usize>().map_err(|_| invalid());
+    let size = BoardSize::new(number(rows)?, number(columns)?, number(win_length)?);
+    size.validate()?;
+    Ok((size, win_rule))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{BoardSize, WinRule};
+    use crate::notation::{Notation, DEFAULT_COLORS};
+    use uuid::Uuid;
+
+    #[test]
+    pub fn test_parse_and_display() {
+        let notation = "6x7/4 4 4 5".parse::<Notation>().unwrap();
+        assert_eq!(notation.size, BoardSize::new(6, 7, 4));
+        assert_eq!(notation.moves, vec![4, 4, 5]);
+        assert_eq!(notation.to_string(), "6x7/4 4 4 5");
+
+        let notation = " 1  2\n3 ".parse::<Notation>().unwrap();
+        assert_eq!(notation.size, BoardSize::default());
+        assert_eq!(notation.to_string(), "6x9/5 1 2 3");
+
+        assert!("6x9/5".parse::<Notation>().unwrap().moves.is_empty());
+
+        let notation = "15x15/5/renju 8".parse::<Notation>().unwrap();
+        assert_eq!(notation.win_rule, WinRule::Renju);
+        assert_eq!(notation.to_string(), "15x15/5/renju 8");
+        assert!("6x9/5/sudden 1".parse::<Notation>().is_err());
+    }
+
+    #[test]
+    pub fn test_parse_errors() {
+        assert!("6x9 1 2".parse::<Notation>().is_err());
+        assert!("6x9/a 1".parse::<Notation>().is_err());
+        assert!("6x9/12 1".parse::<Notation>().is_err());
+        assert!("6x9/5 1 b".parse::<Notation>().is_err());
+    }
+
+    #[test]
+    pub fn test_replay() {
+        let replay = "3x3/3 2 1 2"
+            .parse::<Notation>()
+            .unwrap()
+            .replay(['R', 'Y'])
+            .unwrap();
+        assert_eq!(replay.board.to_string(), "----R-YR-");
+        assert_eq!(replay.cells, vec![(2, 1), (2, 0), (1, 1)]);
+        assert_eq!(replay.winning_line, None);
+        assert!(!replay.is_over());
+
+        let replay = "4x4/3 1 2 1 2 1"
+            .parse::<Notation>()
+            .unwrap()
+            .replay(DEFAULT_COLORS)
+            .unwrap();
+        assert_eq!(replay.winning_line.unwrap().color, 'X');
+
+        // four in the bottom row don't win with exactly three
+        let replay = "2x5/3/exact 1 1 2 2 4 4 3"
+            .parse::<Notation>()
+            .unwrap()
+            .replay(DEFAULT_COLORS)
+            .unwrap();
+        assert_eq!(replay.winning_line, None);
+        assert_eq!(
+            "2x5/3/renju 1 1 2 2 4 4 3"
+                .parse::<Notation>()
+                .unwrap()
+                .board(),
+            Err("Move 7: an overline is forbidden".to_string())
+        );
+    }
+
+    #[test]
+    pub fn test_replay_errors() {
+        let replay = |notation: &str| notation.parse::<Notation>().unwrap().board();
+        assert!(replay("3x3/3 4").is_err());
+        assert!(replay("3x3/3 0").is_err());
+        assert!(replay("3x3/3 1 1 1 1").is_err());
+        assert_eq!(
+            replay("4x4/3 1 2 1 2 1 2"),
+            Err("Move 6 is played after the end of the game".to_string())
+        );
+    }
+
+    #[test]
+    pub fn test_to_game_state() {
+        let (id, user_1, user_2) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let notation = "6x9/5 2 1 2 1 2 1 2 1 2".parse::<Notation>().unwrap();
+        let game_state = notation
+            .to_game_state(&id, &user_1, &user_2, DEFAULT_COLORS)
+            .unwrap();
+        assert_eq!(game_state.id, id.to_string());
+        assert_eq!(game_state.board_size(), notation.size);
+        assert_eq!(game_state.move_list, "2 1 2 1 2 1 2 1 2");
+        let move_list = &game_state.move_list;
+        assert_eq!(
+            Notation::from_move_list(game_state.board_size(), game_state.win_rule(), move_list)
+                .unwrap(),
+            notation
+        );
+        assert_eq!(game_state.last_user_id.unwrap(), user_1.to_string());
+        assert!(game_state.winner);
+        assert!(game_state.ended);
+        assert_eq!(game_state.winner_color.unwrap(), "X");
+        assert_eq!(game_state.winning_cells.unwrap(), "10,19,28,37,46");
+
+        let notation = "3x3/3 1 2 3 2 1 1 2 3 3".parse::<Notation>().unwrap();
+        let game_state = notation
+            .to_game_state(&id, &user_1, &user_2, DEFAULT_COLORS)
+            .unwrap();
+        assert!(game_state.draw);
+        assert!(!game_state.winner);
+
+        let game_state = Notation::new(BoardSize::default(), vec![])
+            .to_game_state(&id, &user_1, &user_2, DEFAULT_COLORS)
+            .unwrap();
+        assert_eq!(game_state.last_user_id, None);
+        assert!(!game_state.ended);
+    }
+}
@@ -0,0 +1,329 @@
+//! `game_state` as a projection of the `moves` log. `project` replays the moves of a game
+//! through the rules and derives every column that depends on them; `rebuild_game_state` writes
+//! the projection back and `check_game` reports the columns where the stored row disagrees.
+use diesel::SqliteConnection;
+use uuid::Uuid;
+
+use crate::board::EMPTY_CELL;
+use crate::db;
+use crate::engine::zobrist::position_key;
+use crate::game::GameError;
+use crate::models::{GameState, Move};
+use crate::rules::{self, Outcome};
+
+#[cfg(test)]
+use mocktopus::macros::*;
+
+/// A column of `game_state` whose stored value differs from the replay of the moves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub column: &'static str,
+    pub stored: String,
+    pub replayed: String,
+}
+
+/// Replays `moves` and returns `stored` with the columns derived from the moves replaced.
+/// `colors` are the colors of `user_1` and `user_2`. Fails if the log is not a legal game
+/// between the seated users.
+pub fn project(stored: &GameState, moves: &[Move], colors: [char; 2]) -> Result<GameState, String> {
+    let players = [&stored.user_1, &stored.user_2];
+    for (index, m) in moves.iter().enumerate() {
+        if m.ply as usize != index + 1 {
+            return Err(format!("Expected ply {}, found ply {}", index + 1, m.ply));
+        }
+        if players[index % 2].as_deref() != Some(m.user_id.as_str()) {
+            return Err(format!("Ply {} is played by the wrong user", m.ply));
+        }
+    }
+    let plays = moves
+        .iter()
+        .map(|m| rules::Move::from_kind(&m.kind, m.row_num as usize, m.col_num as usize))
+        .collect::<Result<Vec<_>, _>>()?;
+    let rules = stored.rule_set()?.rules();
+    let replay = rules::replay(
+        rules,
+        stored.board_size(),
+        stored.win_rule(),
+        &plays,
+        colors,
+    )?;
+    let board = replay.board();
+    for (m, (row, _col)) in moves.iter().zip(&replay.cells) {
+        if m.row_num as usize != board.rows() - row {
+            return Err(format!("Ply {} landed on the wrong row", m.ply));
+        }
+    }
+
+    let last_move = moves.last();
+    let winning_line = match &replay.outcome {
+        Some(Outcome::Win(line)) => Some(line),
+        _ => None,
+    };
+    Ok(GameState {
+        board: Some(board.to_string()),
+        last_user_id: last_move.map(|m| m.user_id.clone()),
+        winner: winning_line.is_some(),
+        ended: replay.outcome.is_some(),
+        draw: replay.outcome == Some(Outcome::Draw),
+        winner_color: winning_line.map(|line| line.color.to_string()),
+        winning_cells: winning_line.map(|line| line.cell_indices(board.columns())),
+        position_key: last_move.map(|_| position_key(board, colors[0])),
+        move_list: plays
+            .iter()
+            .map(|play| play.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+        ..stored.clone()
+    })
+}
+
+/// Columns where `stored` differs from `replayed`.
+pub fn compare(stored: &GameState, replayed: &GameState) -> Vec<Mismatch> {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    let columns = vec![
+        ("board", text(&stored.board), text(&replayed.board)),
+        (
+            "last_user_id",
+            text(&stored.last_user_id),
+            text(&replayed.last_user_id),
+        ),
+        (
+            "winner",
+            stored.winner.to_string(),
+            replayed.winner.to_string(),
+        ),
+        ("draw", stored.draw.to_string(), replayed.draw.to_string()),
+        (
+            "ended",
+            stored.ended.to_string(),
+            replayed.ended.to_string(),
+        ),
+        (
+            "winner_color",
+            text(&stored.winner_color),
+            text(&replayed.winner_color),
+        ),
+        (
+            "winning_cells",
+            text(&stored.winning_cells),
+            text(&replayed.winning_cells),
+        ),
+        (
+            "position_key",
+            text(&stored.position_key),
+            text(&replayed.position_key),
+        ),
+        (
+            "move_list",
+            stored.move_list.clone(),
+            replayed.move_list.clone(),
+        ),
+    ];
+    columns
+        .into_iter()
+        .filter(|(_, stored, replayed)| stored != replayed)
+        .map(|(column, stored, replayed)| Mismatch {
+            column,
+            stored,
+            replayed,
+        })
+        .collect()
+}
+
+/// Stored row and its projection from the moves log.
+fn load_and_project(
+    ses_id: &Uuid,
+    conn: &SqliteConnection,
+) -> Result<(GameState, GameState), GameError> {
+    let stored = db::get_game_state(ses_id, conn)?;
+    let moves = db::get_moves(ses_id, conn)?;
+    // games played before the moves were recorded cannot be rebuilt
+    let has_pieces = |board: &str| board.chars().any(|cell| cell != EMPTY_CELL);
+    if moves.is_empty() && stored.board.as_deref().is_some_and(has_pieces) {
+        return Err(GameError::Internal(format!(
+            "The moves of game {} are not available",
+            ses_id
+        )));
+    }
+    let colors = player_colors(&stored, conn)?;
+    let replayed = project(&stored, &moves, colors).map_err(|error| {
+        GameError::Internal(format!("Invalid moves of game {}: {}", ses_id, error))
+    })?;
+    Ok((stored, replayed))
+}
+
+/// Compares the stored row of the game with the replay of its moves.
+#[cfg_attr(test, mockable)]
+pub fn check_game(ses_id: &Uuid, conn: &SqliteConnection) -> Result<Vec<Mismatch>, GameError> {
+    let (stored, replayed) = load_and_project(ses_id, conn)?;
+    Ok(compare(&stored, &replayed))
+}
+
+/// Replays the moves of the game and overwrites the columns derived from them.
+#[cfg_attr(test, mockable)]
+pub fn rebuild_game_state(ses_id: &Uuid, conn: &SqliteConnection) -> Result<GameState, GameError> {
+    let (_stored, replayed) = load_and_project(ses_id, conn)?;
+    db::store_projection(&replayed, conn)?;
+    Ok(db::get_game_state(ses_id, conn)?)
+}
+
+/// Checks every game. Returns the ids of the games that disagree with their moves, or cannot be
+/// replayed, with a description of the problem.
+pub fn check_all_games(conn: &SqliteConnection) -> Result<Vec<(String, String)>, GameError> {
+    let mut problems = vec![];
+    for id in db::get_game_ids(conn)? {
+        let ses_id =
+            Uuid::parse_str(&id).map_err(|error| GameError::Internal(error.to_string()))?;
+        match check_game(&ses_id, conn) {
+            Ok(mismatches) if mismatches.is_empty() => {}
+            Ok(mismatches) => {
+                let columns = mismatches
+                    .iter()
+                    .map(|m| {
+                        format!(
+                            "{}: stored '{}', replayed '{}'",
+                            m.column, m.stored, m.replayed
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                problems.push((id, columns.join("; ")));
+            }
+            Err(error) => problems.push((id, error.to_string())),
+        }
+    }
+    Ok(problems)
+}
+
+/// Colors of `user_1` and `user_2`. Before anyone joins, `user_2` gets any other color.
+pub fn player_colors(
+    game_state: &GameState,
+    conn: &SqliteConnection,
+) -> Result<[char; 2], GameError> {
+    let color = |user_id: &Option<String>| -> Result<Option<char>, GameError> {
+        match user_id {
+            Some(user_id) => {
+                let user_id = Uuid::parse_str(user_id)
+                    .map_err(|error| GameError::Internal(error.to_string()))?;
+                let user = db::get_user(&user_id, conn)
+                    .map_err(|error| GameError::Internal(format!("User {}: {}", user_id, error)))?;
+                Ok(user.user_color.chars().next())
+            }
+            None => Ok(None),
+        }
+    };
+    let first = color(&game_state.user_1)?.unwrap_or('X');
+    let default_second = if first == 'O' { 'X' } else { 'O' };
+    Ok([first, color(&game_state.user_2)?.unwrap_or(default_second)])
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::BoardSize;
+    use crate::db;
+    use crate::db::create_conn_pool;
+    use crate::game::user_move;
+    use crate::projection::{check_all_games, check_game, project, rebuild_game_state};
+    use crate::rules;
+    use diesel::SqliteConnection;
+    use std::ops::Deref;
+    use uuid::Uuid;
+
+    fn new_game_with_moves(conn: &SqliteConnection, cols: &[usize]) -> Uuid {
+        let user_1 = db::create_new_user("test-user-1", "X", conn).unwrap();
+        let user_2 = db::create_new_user("test-user-2", "O", conn).unwrap();
+        let session_id = db::create_new_session(&user_1, &BoardSize::default(), conn).unwrap();
+        db::join_game_session(&session_id, &user_2, conn).unwrap();
+        for (ply, col) in cols.iter().enumerate() {
+            let user = if ply % 2 == 0 { user_1 } else { user_2 };
+            user_move(session_id, user, rules::Move::Drop(*col), None, conn).unwrap();
+        }
+        session_id
+    }
+
+    #[test]
+    pub fn test_project_matches_user_move() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id = new_game_with_moves(conn.deref(), &[2, 1, 2, 1, 2, 1, 2, 1, 2]);
+        let stored = db::get_game_state(&session_id, conn.deref()).unwrap();
+        let moves = db::get_moves(&session_id, conn.deref()).unwrap();
+
+        let replayed = project(&stored, &moves, ['X', 'O']).unwrap();
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", stored));
+        assert!(check_game(&session_id, conn.deref()).unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn test_project_invalid_log() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id = new_game_with_moves(conn.deref(), &[4, 4, 5]);
+        let stored = db::get_game_state(&session_id, conn.deref()).unwrap();
+        let moves = db::get_moves(&session_id, conn.deref()).unwrap();
+
+        assert!(project(&stored, &moves[1..], ['X', 'O']).is_err());
+        let mut wrong_row = moves.clone();
+        wrong_row[1].row_num = 1;
+        assert!(project(&stored, &wrong_row, ['X', 'O']).is_err());
+        let mut wrong_user = moves;
+        wrong_user[1].user_id = wrong_user[0].user_id.clone();
+        assert!(project(&stored, &wrong_user, ['X', 'O']).is_err());
+    }
+
+    #[test]
+    pub fn test_check_and_rebuild() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id = new_game_with_moves(conn.deref(), &[4, 4, 5]);
+        let stored = db::get_game_state(&session_id, conn.deref()).unwrap();
+        // a lost write: the board and the last user of the third move are missing
+        let user_2 = Uuid::parse_str(stored.user_2.as_ref().unwrap()).unwrap();
+        let board = db::get_moves(&session_id, conn.deref()).unwrap();
+        assert_eq!(board.len(), 3);
+        let two_moves = "-".repeat(36) + "---O-----" + "---X-----";
+        db::update_game_state(
+            &session_id,
+            &user_2,
+            &two_moves,
+            false,
+            false,
+            false,
+            conn.deref(),
+        )
+        .unwrap();
+
+        let mismatches = check_game(&session_id, conn.deref()).unwrap();
+        let columns = mismatches.iter().map(|m| m.column).collect::<Vec<_>>();
+        assert_eq!(columns, vec!["board", "last_user_id"]);
+        let problems = check_all_games(conn.deref()).unwrap();
+        assert!(problems.iter().any(|(id, _)| *id == session_id.to_string()));
+
+        let rebuilt = rebuild_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(rebuilt.board, stored.board);
+        assert_eq!(rebuilt.last_user_id, stored.last_user_id);
+        assert!(check_game(&session_id, conn.deref()).unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn test_rebuild_game_without_moves() {
+        let conn = create_conn_pool().get().unwrap();
+        let session_id = new_game_with_moves(conn.deref(), &[]);
+        assert!(check_game(&session_id, conn.deref()).unwrap().is_empty());
+        let user_1 = db::get_game_state(&session_id, conn.deref())
+            .unwrap()
+            .user_1
+            .unwrap();
+        let board = "-".repeat(45) + "----X----";
+        db::update_game_state(
+            &session_id,
+            &Uuid::parse_str(&user_1).unwrap(),
+            &board,
+            false,
+            false,
+            false,
+            conn.deref(),
+        )
+        .unwrap();
+        assert!(check_game(&session_id, conn.deref()).is_err());
+        assert!(rebuild_game_state(&session_id, conn.deref()).is_err());
+        let stored = db::get_game_state(&session_id, conn.deref()).unwrap();
+        assert_eq!(stored.board, Some(board));
+    }
+}
@@ -0,0 +1,301 @@
+//! Game records in the spirit of PGN: tagged headers followed by the numbered move list and the
+//! result.
+//!
+//! ```text
+//! [Date "2026.10.18"]
+//! [Player1 "alice"]
+//! [Player2 "bob"]
+//! [Color1 "X"]
+//! [Color2 "O"]
+//! [Result "1-0"]
+//! [Rules "6x9/5"]
+//!
+//! 1. 5 5 2. 4 6 3. 3 2 4. 6 7 5. 7 1-0
+//! ```
+//!
+//! Moves are the 1-based columns of `notation`. `Player1` moves first.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::notation::{self, Notation, Replay};
+
+pub const UNKNOWN_DATE: &str = "????.??.??";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    FirstWins,
+    SecondWins,
+    Draw,
+    Unfinished,
+}
+
+impl GameResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::FirstWins => "1-0",
+            GameResult::SecondWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unfinished => "*",
+        }
+    }
+
+    /// Result of a replayed game, `first_color` being the color of the player who moved first.
+    pub fn of(replay: &Replay, first_color: char) -> Self {
+        match &replay.winning_line {
+            Some(line) if line.color == first_color => GameResult::FirstWins,
+            Some(_) => GameResult::SecondWins,
+            None if replay.is_draw() => GameResult::Draw,
+            None => GameResult::Unfinished,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = String;
+
+    fn from_str(result: &str) -> Result<Self, Self::Err> {
+        match result {
+            "1-0" => Ok(GameResult::FirstWins),
+            "0-1" => Ok(GameResult::SecondWins),
+            "1/2-1/2" => Ok(GameResult::Draw),
+            "*" => Ok(GameResult::Unfinished),
+            _ => Err(format!("Invalid result '{}'", result)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub players: [String; 2],
+    pub colors: [char; 2],
+    /// `YYYY.MM.DD`, `UNKNOWN_DATE` if not known
+    pub date: String,
+    pub result: GameResult,
+    pub notation: Notation,
+}
+
+impl GameRecord {
+    /// Replays the moves and checks that they lead to the recorded result.
+    pub fn validate(&self) -> Result<Replay, String> {
+        if self.colors[0] == self.colors[1] {
+            return Err("The players must have different colors".to_string());
+        }
+        if self.date != UNKNOWN_DATE && !is_date(&self.date) {
+            return Err(format!("Invalid date '{}', expected YYYY.MM.DD", self.date));
+        }
+        let replay = self.notation.replay(self.colors)?;
+        let result = GameResult::of(&replay, self.colors[0]);
+        if result != self.result {
+            return Err(format!(
+                "The moves lead to {}, but the recorded result is {}",
+                result, self.result
+            ));
+        }
+        Ok(replay)
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "[Date \"{}\"]", self.date)?;
+        writeln!(f, "[Player1 \"{}\"]", self.players[0])?;
+        writeln!(f, "[Player2 \"{}\"]", self.players[1])?;
+        writeln!(f, "[Color1 \"{}\"]", self.colors[0])?;
+        writeln!(f, "[Color2 \"{}\"]", self.colors[1])?;
+        writeln!(f, "[Result \"{}\"]", self.result)?;
+        let header = notation::header(self.notation.size, self.notation.win_rule);
+        writeln!(f, "[Rules \"{}\"]", header)?;
+        writeln!(f)?;
+        for (index, col_num) in self.notation.moves.iter().enumerate() {
+            if index % 2 == 0 {
+                write!(f, "{}. ", index / 2 + 1)?;
+            }
+            write!(f, "{} ", col_num)?;
+        }
+        writeln!(f, "{}", self.result)
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = String;
+
+    fn from_str(record: &str) -> Result<Self, Self::Err> {
+        let mut tags = vec![];
+        let mut move_text = String::new();
+        for line in record.lines().map(str::trim) {
+            if line.starts_with('[') {
+                tags.push(parse_tag(line)?);
+            } else {
+                move_text.push_str(line);
+                move_text.push(' ');
+            }
+        }
+        let tag = |name: &str| {
+            tags.iter()
+                .find(|(tag, _)| tag == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let color = |name: &str, default: char| match tag(name) {
+            Some(value) => value
+                .chars()
+                .next()
+                .ok_or_else(|| format!("Empty {} tag", name)),
+            None => Ok(default),
+        };
+
+        let mut moves = vec![];
+        let mut final_result = None;
+        for token in move_text.split_whitespace() {
+            if token.ends_with('.') {
+                continue;
+            }
+            if final_result.is_some() {
+                return Err(format!("Unexpected '{}' after the result", token));
+            }
+            match token.parse::<GameResult>() {
+                Ok(result) => final_result = Some(result),
+                Err(_) => moves.push(token),
+            }
+        }
+        let result = match (tag("Result").map(str::parse).transpose()?, final_result) {
+            (Some(tag), Some(result)) if tag != result => {
+                return Err(format!(
+                    "Result tag {} does not match the result {} after the moves",
+                    tag, result
+                ))
+            }
+            (Some(result), _) | (None, Some(result)) => result,
+            (None, None) => GameResult::Unfinished,
+        };
+
+        let notation = format!("{} {}", tag("Rules").unwrap_or("6x9/5"), moves.join(" "));
+        Ok(GameRecord {
+            players: [
+                tag("Player1").unwrap_or("Player 1").to_string(),
+                tag("Player2").unwrap_or("Player 2").to_string(),
+            ],
+            colors: [color("Color1", 'X')?, color("Color2", 'O')?],
+            date: tag("Date").unwrap_or(UNKNOWN_DATE).to_string(),
+            result,
+            notation: notation.parse()?,
+        })
+    }
+}
+
+// `YYYY.MM.DD`
+fn is_date(date: &str) -> bool {
+    let parts = date.split('.').collect::<Vec<_>>();
+    parts.len() == 3
+        && parts
+            .iter()
+            .zip(&[4, 2, 2])
+            .all(|(part, len)| part.len() == *len && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+// `[Name "value"]`
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let invalid = || format!("Invalid tag: {}", line);
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|line| line.strip_suffix(']'))
+        .ok_or_else(invalid)?;
+    let (name, value) = inner.split_once(' ').ok_or_else(invalid)?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or_else(invalid)?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::BoardSize;
+    use crate::notation::Notation;
+    use crate::record::{GameRecord, GameResult, UNKNOWN_DATE};
+
+    const RECORD: &str = "[Date \"2026.10.18\"]
+[Player1 \"alice\"]
+[Player2 \"bob\"]
+[Color1 \"R\"]
+[Color2 \"Y\"]
+[Result \"1-0\"]
+[Rules \"6x7/4\"]
+
+1. 4 4 2. 3 3 3. 2 2 4. 1 1-0
+";
+
+    #[test]
+    pub fn test_parse_and_display() {
+        let record = RECORD.parse::<GameRecord>().unwrap();
+        assert_eq!(record.players, ["alice".to_string(), "bob".to_string()]);
+        assert_eq!(record.colors, ['R', 'Y']);
+        assert_eq!(record.date, "2026.10.18");
+        assert_eq!(record.result, GameResult::FirstWins);
+        assert_eq!(
+            record.notation,
+            Notation::new(BoardSize::new(6, 7, 4), vec![4, 4, 3, 3, 2, 2, 1])
+        );
+        assert_eq!(record.to_string(), RECORD);
+        assert!(record.validate().is_ok());
+    }
+
+    #[test]
+    pub fn test_parse_defaults() {
+        let record = "1 2 1 2".parse::<GameRecord>().unwrap();
+        assert_eq!(record.colors, ['X', 'O']);
+        assert_eq!(record.date, UNKNOWN_DATE);
+        assert_eq!(record.result, GameResult::Unfinished);
+        assert_eq!(record.notation.size, BoardSize::default());
+        assert!(record.validate().is_ok());
+    }
+
+    #[test]
+    pub fn test_parse_errors() {
+        assert!("[Date 2026]".parse::<GameRecord>().is_err());
+        assert!("[Result \"2-0\"]".parse::<GameRecord>().is_err());
+        assert!("[Result \"1-0\"]\n1. 4 4 0-1"
+            .parse::<GameRecord>()
+            .is_err());
+        assert!("1. 4 4 * 5".parse::<GameRecord>().is_err());
+        assert!("1. 4 x".parse::<GameRecord>().is_err());
+    }
+
+    #[test]
+    pub fn test_validate() {
+        let wrong_result = RECORD.replace("1-0", "0-1");
+        assert!(wrong_result
+            .parse::<GameRecord>()
+            .unwrap()
+            .validate()
+            .is_err());
+
+        let too_many_moves = RECORD.replace("4. 1 1-0", "4. 1 1 1-0");
+        assert!(too_many_moves
+            .parse::<GameRecord>()
+            .unwrap()
+            .validate()
+            .is_err());
+
+        let bad_date = RECORD.replace("2026.10.18", "18/10/2026");
+        assert!(bad_date.parse::<GameRecord>().unwrap().validate().is_err());
+
+        let same_colors = RECORD.replace("\"Y\"", "\"R\"");
+        assert!(same_colors
+            .parse::<GameRecord>()
+            .unwrap()
+            .validate()
+            .is_err());
+
+        let draw = "[Rules \"3x3/3\"]\n1. 1 2 2. 3 2 3. 1 1 4. 2 3 5. 3 1/2-1/2";
+        let record = draw.parse::<GameRecord>().unwrap();
+        assert_eq!(record.result, GameResult::Draw);
+        assert!(record.validate().is_ok());
+    }
+}
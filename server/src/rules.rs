@@ -0,0 +1,593 @@
+//! Rule sets of the game variants. Every game stores the name of its rule set in
+//! `game_state.rules`, `game::user_move` plays the moves through it.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::board::{Board, BoardSize, Cell, WinRule, WinningLine, EMPTY_CELL};
+
+/// A move of a player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    /// drops a piece into the 1-based column
+    Drop(usize),
+    /// removes the player's own piece from the bottom of the 1-based column
+    Pop(usize),
+    /// places a stone on the empty cell of the 1-based row, counted from the bottom like
+    /// `moves.row_num`, and the 1-based column
+    Place(usize, usize),
+}
+
+impl Move {
+    /// The move of a `moves` row, `kind` being the value of `Move::kind`.
+    pub fn from_kind(kind: &str, row_num: usize, col_num: usize) -> Result<Self, String> {
+        match kind {
+            "drop" => Ok(Move::Drop(col_num)),
+            "pop" => Ok(Move::Pop(col_num)),
+            "place" => Ok(Move::Place(row_num, col_num)),
+            _ => Err(format!("Unknown kind of move: {}", kind)),
+        }
+    }
+
+    /// Stored in `moves.kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Move::Drop(_) => "drop",
+            Move::Pop(_) => "pop",
+            Move::Place(_, _) => "place",
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    /// The move in `game_state.move_list`: the column of a drop, `p` and the column of a pop,
+    /// the column letter and the row of a placed stone, e.g. `h8`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Drop(col_num) => write!(f, "{}", col_num),
+            Move::Pop(col_num) => write!(f, "p{}", col_num),
+            Move::Place(row_num, col_num) => {
+                let letter = (b'a' + (*col_num as u8).wrapping_sub(1)) as char;
+                write!(f, "{}{}", letter, row_num)
+            }
+        }
+    }
+}
+
+/// How a game ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Win(WinningLine),
+    Draw,
+}
+
+pub trait Rules {
+    /// Board of a new game.
+    fn initial_board(&self, size: BoardSize) -> Board;
+
+    /// Moves `color` can play on `board`.
+    fn legal_moves(&self, board: &Board, color: char) -> Vec<Move>;
+
+    /// Plays the move of `color`. Returns the new board and the cell of the piece played.
+    fn apply(&self, board: &Board, mv: Move, color: char) -> Result<(Board, Cell), String>;
+
+    /// The end of the game after `color` played on `cell`, `None` if the game goes on.
+    fn outcome(&self, board: &Board, cell: Cell, color: char) -> Option<Outcome>;
+
+    /// Size of the board of a new game when the players don't choose one.
+    fn default_size(&self) -> BoardSize {
+        BoardSize::default()
+    }
+
+    /// False if the game can't be played with the win rule.
+    fn allows(&self, _win_rule: WinRule) -> bool {
+        true
+    }
+
+    /// Number of times the same position, with the same player to move, has to occur for the
+    /// game to end in a draw. `None` if positions can't repeat.
+    fn repetition_limit(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// A game replayed from its moves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    /// the initial board followed by the board after each move
+    pub positions: Vec<Board>,
+    /// the cell of each move, see `Rules::apply`
+    pub cells: Vec<Cell>,
+    pub outcome: Option<Outcome>,
+}
+
+impl Replay {
+    pub fn board(&self) -> &Board {
+        &self.positions[self.positions.len() - 1]
+    }
+}
+
+/// Plays `moves` from the initial board with the win rule, `colors` being the colors of the
+/// first and the second player. Fails on an illegal move and on moves after the end of the game.
+pub fn replay(
+    rules: &dyn Rules,
+    size: BoardSize,
+    win_rule: WinRule,
+    moves: &[Move],
+    colors: [char; 2],
+) -> Result<Replay, String> {
+    let initial_board = rules.initial_board(size).with_win_rule(win_rule, colors[0]);
+    let mut replay = Replay {
+        positions: vec![initial_board],
+        cells: vec![],
+        outcome: None,
+    };
+    for (index, mv) in moves.iter().enumerate() {
+        if replay.outcome.is_some() {
+            return Err(format!(
+                "Move {} is played after the end of the game",
+                index + 1
+            ));
+        }
+        let color = colors[index % 2];
+        let (board, cell) = rules
+            .apply(replay.board(), *mv, color)
+            .map_err(|error| format!("Move {}: {}", index + 1, error))?;
+        replay.outcome = rules
+            .outcome(&board, cell, color)
+            .or_else(|| repetition_draw(rules, &replay.positions, &board));
+        replay.positions.push(board);
+        replay.cells.push(cell);
+    }
+    Ok(replay)
+}
+
+/// `Outcome::Draw` if `board` occurs for the `Rules::repetition_limit` time with the same player
+/// to move. `history` are the boards before it, starting with the initial board.
+pub fn repetition_draw(rules: &dyn Rules, history: &[Board], board: &Board) -> Option<Outcome> {
+    let limit = rules.repetition_limit()?;
+    let occurrences = 1 + history
+        .iter()
+        .enumerate()
+        .filter(|(index, position)| (history.len() - index).is_multiple_of(2) && *position == board)
+        .count();
+    if occurrences >= limit {
+        Some(Outcome::Draw)
+    } else {
+        None
+    }
+}
+
+/// Connect-N: the pieces fall to the lowest empty cell of the column, `win_length` pieces in a
+/// row win and a full board is a draw.
+pub struct Standard;
+
+impl Rules for Standard {
+    fn initial_board(&self, size: BoardSize) -> Board {
+        Board::new(size)
+    }
+
+    fn legal_moves(&self, board: &Board, _color: char) -> Vec<Move> {
+        (0..board.columns())
+            .filter(|col| board.cell(0, *col) == EMPTY_CELL)
+            .map(|col| Move::Drop(col + 1))
+            .collect()
+    }
+
+    fn apply(&self, board: &Board, mv: Move, color: char) -> Result<(Board, Cell), String> {
+        match mv {
+            Move::Drop(col_num) => drop_piece(board, col_num, color),
+            _ => Err("Only drops are allowed by the standard rules".to_string()),
+        }
+    }
+
+    fn outcome(&self, board: &Board, cell: Cell, _color: char) -> Option<Outcome> {
+        match board.winning_line_at(cell.0, cell.1) {
+            Some(line) => Some(Outcome::Win(line)),
+            None if board.is_full() => Some(Outcome::Draw),
+            None => None,
+        }
+    }
+}
+
+/// PopOut: on their turn players either drop a piece or pop their own piece out of the bottom
+/// row, the pieces above it falling one row. A pop can complete lines of both players, the
+/// player who popped wins if it completes one of theirs. A full board goes on as long as the
+/// player to move can pop, and a position occurring for the third time is a draw.
+pub struct PopOut;
+
+impl Rules for PopOut {
+    fn initial_board(&self, size: BoardSize) -> Board {
+        Board::new(size)
+    }
+
+    fn legal_moves(&self, board: &Board, color: char) -> Vec<Move> {
+        let bottom = board.rows() - 1;
+        let pops = (0..board.columns())
+            .filter(|col| board.cell(bottom, *col) == color)
+            .map(|col| Move::Pop(col + 1));
+        Standard
+            .legal_moves(board, color)
+            .into_iter()
+            .chain(pops)
+            .collect()
+    }
+
+    fn apply(&self, board: &Board, mv: Move, color: char) -> Result<(Board, Cell), String> {
+        match mv {
+            Move::Drop(col_num) => drop_piece(board, col_num, color),
+            Move::Pop(col_num) => {
+                check_column(board, col_num)?;
+                let bottom = (board.rows() - 1, col_num - 1);
+                if board.cell(bottom.0, bottom.1) != color {
+                    return Err("You can only pop out your own pieces".to_string());
+                }
+                let mut new_board = board.clone();
+                new_board.pop_piece(col_num - 1)?;
+                Ok((new_board, bottom))
+            }
+            _ => Err("Only drops and pops are allowed by the popout rules".to_string()),
+        }
+    }
+
+    fn outcome(&self, board: &Board, _cell: Cell, color: char) -> Option<Outcome> {
+        // a pop moves a whole column, so the lines can be anywhere
+        let line = board
+            .winning_line_of(color)
+            .or_else(|| board.winning_line());
+        if let Some(line) = line {
+            return Some(Outcome::Win(line));
+        }
+        // the other player can pop unless the whole bottom row is the mover's
+        let bottom = board.rows() - 1;
+        let mover_owns_bottom = (0..board.columns()).all(|col| board.cell(bottom, col) == color);
+        if board.is_full() && mover_owns_bottom {
+            return Some(Outcome::Draw);
+        }
+        None
+    }
+
+    fn repetition_limit(&self) -> Option<usize> {
+        Some(3)
+    }
+
+    // the forbidden moves of the renju rules are shapes made by an added piece, pops don't add
+    fn allows(&self, win_rule: WinRule) -> bool {
+        win_rule != WinRule::Renju
+    }
+}
+
+/// Gomoku: the stones are placed on any empty cell, `win_length` stones in a row win and a full
+/// board is a draw.
+pub struct Gomoku;
+
+impl Rules for Gomoku {
+    fn initial_board(&self, size: BoardSize) -> Board {
+        Board::new(size)
+    }
+
+    fn legal_moves(&self, board: &Board, _color: char) -> Vec<Move> {
+        let rows = board.rows();
+        (0..rows)
+            .flat_map(|row| (0..board.columns()).map(move |col| (row, col)))
+            .filter(|(row, col)| board.cell(*row, *col) == EMPTY_CELL)
+            .map(|(row, col)| Move::Place(rows - row, col + 1))
+            .collect()
+    }
+
+    fn apply(&self, board: &Board, mv: Move, color: char) -> Result<(Board, Cell), String> {
+        match mv {
+            Move::Place(row_num, col_num) => {
+                check_column(board, col_num)?;
+                if row_num < 1 || row_num > board.rows() {
+                    return Err(format!(
+                        "There is no row with this number. Max row is: {}",
+                        board.rows()
+                    ));
+                }
+                let cell = (board.rows() - row_num, col_num - 1);
+                let mut new_board = board.clone();
+                new_board.place_piece(cell.0, cell.1, color)?;
+                check_forbidden(&new_board, cell)?;
+                Ok((new_board, cell))
+            }
+            _ => Err("Only placing stones is allowed by the gomoku rules".to_string()),
+        }
+    }
+
+    fn outcome(&self, board: &Board, cell: Cell, color: char) -> Option<Outcome> {
+        Standard.outcome(board, cell, color)
+    }
+
+    fn default_size(&self) -> BoardSize {
+        BoardSize::new(15, 15, 5)
+    }
+}
+
+fn check_column(board: &Board, col_num: usize) -> Result<(), String> {
+    if col_num < 1 || col_num > board.columns() {
+        return Err(format!(
+            "There is no column with this number. Max column is: {}",
+            board.columns()
+        ));
+    }
+    Ok(())
+}
+
+fn drop_piece(board: &Board, col_num: usize, color: char) -> Result<(Board, Cell), String> {
+    check_column(board, col_num)?;
+    let mut new_board = board.clone();
+    let row = new_board.drop_piece(col_num - 1, color)?;
+    check_forbidden(&new_board, (row, col_num - 1))?;
+    Ok((new_board, (row, col_num - 1)))
+}
+
+// the restrictions of the first player under `WinRule::Renju`
+fn check_forbidden(board: &Board, cell: Cell) -> Result<(), String> {
+    match board.forbidden_move(cell.0, cell.1) {
+        Some(reason) => Err(format!(
+            "The first player can't play {} with the renju rules",
+            reason
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Rule sets a game can be played with. Stored by name in `game_state.rules`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RuleSet {
+    #[default]
+    Standard,
+    PopOut,
+    Gomoku,
+}
+
+impl RuleSet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuleSet::Standard => "standard",
+            RuleSet::PopOut => "popout",
+            RuleSet::Gomoku => "gomoku",
+        }
+    }
+
+    pub fn rules(&self) -> &'static dyn Rules {
+        match self {
+            RuleSet::Standard => &Standard,
+            RuleSet::PopOut => &PopOut,
+            RuleSet::Gomoku => &Gomoku,
+        }
+    }
+}
+
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for RuleSet {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "standard" => Ok(RuleSet::Standard),
+            "popout" => Ok(RuleSet::PopOut),
+            "gomoku" => Ok(RuleSet::Gomoku),
+            _ => Err(format!("Unknown rules: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::board::{Board, BoardSize, WinRule};
+    use crate::notation::Notation;
+    use crate::rules::{replay, Gomoku, Move, Outcome, PopOut, RuleSet, Rules, Standard};
+
+    fn board(notation: &str) -> Board {
+        notation.parse::<Notation>().unwrap().board().unwrap()
+    }
+
+    #[test]
+    pub fn test_rule_set_names() {
+        assert_eq!(RuleSet::default(), RuleSet::Standard);
+        assert_eq!("standard".parse::<RuleSet>().unwrap(), RuleSet::Standard);
+        assert_eq!(RuleSet::Standard.to_string(), "standard");
+        assert_eq!("popout".parse::<RuleSet>().unwrap(), RuleSet::PopOut);
+        assert_eq!("gomoku".parse::<RuleSet>().unwrap(), RuleSet::Gomoku);
+        assert!("chess".parse::<RuleSet>().is_err());
+    }
+
+    #[test]
+    pub fn test_move_kinds() {
+        let moves = [
+            (Move::Drop(3), 1, 3),
+            (Move::Pop(4), 1, 4),
+            (Move::Place(8, 7), 8, 7),
+        ];
+        for (mv, row_num, col_num) in moves.iter() {
+            assert_eq!(Move::from_kind(mv.kind(), *row_num, *col_num), Ok(*mv));
+        }
+        assert_eq!(Move::Pop(4).to_string(), "p4");
+        assert_eq!(Move::Place(8, 7).to_string(), "g8");
+        assert!(Move::from_kind("jump", 1, 1).is_err());
+    }
+
+    #[test]
+    pub fn test_standard_apply() {
+        let board = Board::from_cells(
+            vec![
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', 'X', '-', '-', '-', '-', '-', '-'],
+            ],
+            5,
+        );
+        let target_board = Board::from_cells(
+            vec![
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', 'O', '-', '-', '-', '-', '-', '-'],
+                vec!['-', '-', 'X', '-', '-', '-', '-', '-', 'X'],
+            ],
+            5,
+        );
+        let (new_board, cell) = Standard.apply(&board, Move::Drop(9), 'X').unwrap();
+        assert_eq!(cell, (5, 8));
+        let (new_board, cell) = Standard.apply(&new_board, Move::Drop(3), 'O').unwrap();
+        assert_eq!(cell, (4, 2));
+        assert_eq!(new_board, target_board);
+        assert_eq!(
+            Standard.apply(&board, Move::Drop(10), 'X'),
+            Err("There is no column with this number. Max column is: 9".to_string())
+        );
+        assert!(Standard.apply(&board, Move::Drop(0), 'X').is_err());
+    }
+
+    #[test]
+    pub fn test_standard_apply_full() {
+        let board = Board::from_cells(
+            vec![
+                vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
+                vec!['-', 'X', '-', '-', '-', '-', '-', '-', '-'],
+            ],
+            5,
+        );
+        assert_eq!(
+            Standard.apply(&board, Move::Drop(2), 'X'),
+            Err("This column is full. Please, try another move".to_string())
+        );
+        let legal = Standard.legal_moves(&board, 'O');
+        assert_eq!(legal.len(), 8);
+        assert!(!legal.contains(&Move::Drop(2)));
+    }
+
+    #[test]
+    pub fn test_standard_outcome() {
+        let position = board("6x7/4 1 2 1 2 1 2");
+        assert_eq!(Standard.outcome(&position, (3, 0), 'X'), None);
+        let (position, cell) = Standard.apply(&position, Move::Drop(1), 'X').unwrap();
+        match Standard.outcome(&position, cell, 'X') {
+            Some(Outcome::Win(line)) => assert_eq!(line.color, 'X'),
+            outcome => panic!("expected a win, got {:?}", outcome),
+        }
+        let position = Standard.initial_board(BoardSize::new(1, 2, 2));
+        let (position, _) = Standard.apply(&position, Move::Drop(1), 'X').unwrap();
+        let (position, cell) = Standard.apply(&position, Move::Drop(2), 'O').unwrap();
+        assert_eq!(Standard.outcome(&position, cell, 'O'), Some(Outcome::Draw));
+    }
+
+    #[test]
+    pub fn test_popout_apply() {
+        let position = board("4x4/3 2 2 3");
+        let (popped, cell) = PopOut.apply(&position, Move::Pop(2), 'X').unwrap();
+        assert_eq!(cell, (3, 1));
+        assert_eq!(popped.to_string(), "-------------OX-");
+        assert_eq!(
+            PopOut.apply(&position, Move::Pop(2), 'O'),
+            Err("You can only pop out your own pieces".to_string())
+        );
+        assert!(PopOut.apply(&position, Move::Pop(1), 'X').is_err());
+        assert!(PopOut.apply(&position, Move::Pop(5), 'X').is_err());
+        assert!(Standard.apply(&position, Move::Pop(2), 'X').is_err());
+        let legal = PopOut.legal_moves(&position, 'X');
+        assert_eq!(legal.len(), 6);
+        assert!(legal.contains(&Move::Pop(3)));
+        assert!(!PopOut.legal_moves(&position, 'O').contains(&Move::Pop(2)));
+    }
+
+    #[test]
+    pub fn test_popout_outcome() {
+        // a pop can complete lines of both players, the one who popped wins
+        let position = Board::parse("----O---OXXXOOXO", BoardSize::new(4, 4, 3)).unwrap();
+        match PopOut.outcome(&position, (3, 0), 'X') {
+            Some(Outcome::Win(line)) => assert_eq!(line.color, 'X'),
+            outcome => panic!("expected a win of X, got {:?}", outcome),
+        }
+        match PopOut.outcome(&position, (3, 0), 'O') {
+            Some(Outcome::Win(line)) => assert_eq!(line.color, 'O'),
+            outcome => panic!("expected a win of O, got {:?}", outcome),
+        }
+
+        // a full board goes on while the player to move can pop
+        let full = Board::parse("XO", BoardSize::new(1, 2, 2)).unwrap();
+        assert_eq!(PopOut.outcome(&full, (0, 1), 'O'), None);
+        let full = Board::parse("XX", BoardSize::new(1, 2, 3)).unwrap();
+        assert_eq!(PopOut.outcome(&full, (0, 1), 'X'), Some(Outcome::Draw));
+    }
+
+    #[test]
+    pub fn test_gomoku_apply() {
+        let board = Board::new(BoardSize::new(3, 3, 3));
+        let (board, cell) = Gomoku.apply(&board, Move::Place(3, 2), 'X').unwrap();
+        assert_eq!(cell, (0, 1));
+        assert_eq!(board.to_string(), "-X-------");
+        assert_eq!(Gomoku.legal_moves(&board, 'O').len(), 8);
+        assert!(Gomoku.apply(&board, Move::Place(3, 2), 'O').is_err());
+        assert!(Gomoku.apply(&board, Move::Place(4, 1), 'O').is_err());
+        assert!(Gomoku.apply(&board, Move::Place(1, 0), 'O').is_err());
+        assert!(Gomoku.apply(&board, Move::Drop(1), 'O').is_err());
+        assert!(Standard.apply(&board, Move::Place(1, 1), 'O').is_err());
+        assert_eq!(Gomoku.default_size(), BoardSize::new(15, 15, 5));
+    }
+
+    #[test]
+    pub fn test_gomoku_outcome() {
+        // stones don't fall, the line is in the top row
+        let moves = [
+            Move::Place(3, 1),
+            Move::Place(1, 1),
+            Move::Place(3, 2),
+            Move::Place(1, 2),
+            Move::Place(3, 3),
+        ];
+        let game = replay(
+            &Gomoku,
+            BoardSize::new(3, 3, 3),
+            WinRule::Freestyle,
+            &moves,
+            ['X', 'O'],
+        )
+        .unwrap();
+        assert_eq!(game.board().to_string(), "XXX---OO-");
+        match game.outcome {
+            Some(Outcome::Win(line)) => assert_eq!(line.color, 'X'),
+            outcome => panic!("expected a win of X, got {:?}", outcome),
+        }
+        let full = Board::parse("XO", BoardSize::new(1, 2, 2)).unwrap();
+        assert_eq!(Gomoku.outcome(&full, (0, 1), 'O'), Some(Outcome::Draw));
+    }
+
+    #[test]
+    pub fn test_popout_repetition() {
+        let size = BoardSize::new(4, 4, 4);
+        let cycle = [Move::Drop(1), Move::Drop(2), Move::Pop(1), Move::Pop(2)];
+        let moves = cycle.iter().cycle().take(8).copied().collect::<Vec<_>>();
+        // the empty board with X to move, for the third time
+        let game = replay(&PopOut, size, WinRule::Freestyle, &moves, ['X', 'O']).unwrap();
+        assert_eq!(game.outcome, Some(Outcome::Draw));
+        let game = replay(&PopOut, size, WinRule::Freestyle, &moves[..7], ['X', 'O']).unwrap();
+        assert_eq!(game.outcome, None);
+        assert!(replay(
+            &PopOut,
+            size,
+            WinRule::Freestyle,
+            &[moves.clone(), moves].concat(),
+            ['X', 'O']
+        )
+        .is_err());
+        assert_eq!(
+            replay(&Standard, size, WinRule::Freestyle, &cycle, ['X', 'O']).unwrap_err(),
+            "Move 3: Only drops are allowed by the standard rules"
+        );
+    }
+}
@@ -8,6 +8,34 @@ table! {
         last_user_id -> Nullable<Text>,
         last_user_color -> Nullable<Text>,
         ended -> Bool,
+        board_rows -> Integer,
+        board_columns -> Integer,
+        win_length -> Integer,
+        draw -> Bool,
+        winner_color -> Nullable<Text>,
+        winning_cells -> Nullable<Text>,
+        ai_engine -> Nullable<Text>,
+        ai_level -> Nullable<Text>,
+        position_key -> Nullable<Text>,
+        move_list -> Text,
+        version -> Integer,
+        takeback_requested_by -> Nullable<Text>,
+        rated -> Bool,
+        rules -> Text,
+        win_rule -> Text,
+    }
+}
+
+table! {
+    moves (id) {
+        id -> Integer,
+        game_id -> Text,
+        ply -> Integer,
+        user_id -> Text,
+        col_num -> Integer,
+        row_num -> Integer,
+        created_at -> Text,
+        kind -> Text,
     }
 }
 
@@ -19,4 +47,6 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(game_state, user,);
+joinable!(moves -> game_state (game_id));
+
+allow_tables_to_appear_in_same_query!(game_state, moves, user,);
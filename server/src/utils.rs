@@ -1,12 +1,9 @@
-pub fn str_to_arr(board_str: &str) -> Vec<Vec<char>> {
-    const ROWS: usize = 6; //TODO: make them parameters
-    const COLUMNS: usize = 9;
-
+pub fn str_to_arr(board_str: &str, rows: usize, columns: usize) -> Vec<Vec<char>> {
     let mut board_arr: Vec<Vec<char>> = Vec::new();
 
-    for y in 0..ROWS {
-        let first_idx = y * COLUMNS;
-        let row = board_str[first_idx..(first_idx + COLUMNS)]
+    for y in 0..rows {
+        let first_idx = y * columns;
+        let row = board_str[first_idx..(first_idx + columns)]
             .chars()
             .collect::<Vec<char>>();
         board_arr.push(row);
@@ -25,7 +22,7 @@ pub mod tests {
     #[test]
     pub fn test_str_to_arr() {
         let s = String::from("-----------------------------------------------X------");
-        let arr = str_to_arr(s.as_str());
+        let arr = str_to_arr(s.as_str(), 6, 9);
 
         let target = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -41,7 +38,7 @@ pub mod tests {
     #[test]
     pub fn test_str_to_arr_2() {
         let s = String::from("---------------------------------------------X--------");
-        let arr = str_to_arr(s.as_str());
+        let arr = str_to_arr(s.as_str(), 6, 9);
 
         let target = vec![
             vec!['-', '-', '-', '-', '-', '-', '-', '-', '-'],
@@ -57,6 +54,6 @@ pub mod tests {
     #[test]
     pub fn test_arr_to_str() {
         let s = String::from("123456789123456789123456789123456789123456789123456789");
-        assert_eq!(arr_to_str(&str_to_arr(&s)), s);
+        assert_eq!(arr_to_str(&str_to_arr(&s, 6, 9)), s);
     }
 }
@@ -0,0 +1,58 @@
+#[cfg(test)]
+pub mod tests {
+    use connect5_rust::board::BoardSize;
+    use connect5_rust::engine::bitboard::BitBoard;
+    use connect5_rust::engine::external::{ExternalEngine, SharedEngine, ENGINE_COMMAND_VAR};
+    use connect5_rust::engine::AiEngine;
+    use std::time::Duration;
+
+    const REFERENCE_ENGINE: &str = env!("CARGO_BIN_EXE_reference_engine");
+
+    #[test]
+    fn test_reference_engine_handshake() {
+        let mut engine = ExternalEngine::new(REFERENCE_ENGINE, Duration::from_millis(50));
+        assert_eq!(engine.start().unwrap(), "connect5-rust minimax");
+        let mcts = format!("{} mcts", REFERENCE_ENGINE);
+        let mut engine = ExternalEngine::new(&mcts, Duration::from_millis(50));
+        assert_eq!(engine.start().unwrap(), "connect5-rust mcts");
+    }
+
+    #[test]
+    fn test_reference_engine_moves() {
+        let mut engine = ExternalEngine::new(REFERENCE_ENGINE, Duration::from_millis(100));
+        let mut position = BitBoard::new(BoardSize::new(6, 7, 4)).unwrap();
+        // x to move, wins in the fourth column
+        position.play_sequence(&[0, 0, 1, 1, 2, 5]).unwrap();
+        assert_eq!(engine.best_move(&position).unwrap(), Some(3));
+        assert!(engine.info().unwrap().starts_with("depth "));
+        // o to move, has to block
+        position.play(6);
+        assert_eq!(engine.best_move(&position).unwrap(), Some(3));
+        // the same process answers the next position
+        position.play(3);
+        position.play(3);
+        assert!(engine.best_move(&position).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_reference_engine_no_move() {
+        let mut engine = ExternalEngine::new(REFERENCE_ENGINE, Duration::from_millis(50));
+        let mut position = BitBoard::new(BoardSize::new(1, 2, 2)).unwrap();
+        position.play_sequence(&[0, 1]).unwrap();
+        assert_eq!(engine.best_move(&position).unwrap(), None);
+    }
+
+    #[test]
+    fn test_shared_engine() {
+        // the only test of this binary reading the variable
+        std::env::set_var(ENGINE_COMMAND_VAR, REFERENCE_ENGINE);
+        assert_eq!(AiEngine::External.check(), Ok(()));
+        assert_eq!(SharedEngine::start().unwrap(), "connect5-rust minimax");
+        let mut position = BitBoard::new(BoardSize::new(6, 7, 4)).unwrap();
+        position.play_sequence(&[0, 0, 1, 1, 2, 5]).unwrap();
+        for movetime in [50, 100].iter() {
+            let engine = SharedEngine::new(Duration::from_millis(*movetime));
+            assert_eq!(engine.best_move(&position).unwrap(), Some(3));
+        }
+    }
+}